@@ -1,25 +1,168 @@
-use rand::Rng;
+use rand::{Rng, SeedableRng, rngs::StdRng};
 use sdl2::rect::Rect;
-use std::time::{Duration, Instant};
+use std::{collections::VecDeque, time::Duration};
 
-use crate::types::{Airt, Dimensions};
+use crate::{
+    collision::{self, Rectangle},
+    drivers::{self, Profile},
+    network::Trip,
+    pedestrians::{self, Pedestrian, Zone},
+    routes::Route,
+    scenario::Arrival,
+    sensing::{self, Decision, Neighbour, Own},
+    types::{self, Airt, Control, Dimensions, Layout},
+    vehicles::{self, Class},
+};
 
+pub const TICK: Duration = Duration::from_millis(16); // The simulation advances in steps of one frame, so that it runs the same with or without a window.
+
+// Two cars on different headings that come within this many lane widths of each other without touching have had a near miss.
+const NEAR_MISS_GAP: f64 = 0.25;
+
+// The most cars a platoon can have. Once it's full, the car behind it waits to form a platoon of its own, so that a long queue doesn't hold up the traffic across its path for as long as it takes to go through.
+const MAX_PLATOON_SIZE: usize = 4;
+
+// How far a car travels, in lane widths, while it moves over into the next lane. It only starts to move over if it can finish before it reaches the intersection, with a lane width to spare.
+const LANE_CHANGE_LENGTH: f64 = 2.0;
+
+const FLASH_TICKS: u64 = 8; // How long each step of an emergency vehicle's flashing lights lasts.
+
+// A car turning right across the bike lane, in global control, lets a cyclist in it going straight on go first if they're no more than this many lane widths further from the middle of the intersection than the car is.
+const CYCLIST_LOOKBACK: f64 = 4.0;
+
+// Everything about a vehicle that's picked when it's spawned, made in advance, so that the same vehicle can be sent through more than one intersection, as in the comparison.
+#[derive(Clone, Copy)]
+pub struct Pick {
+    pub class: Class,
+    pub profile: Profile,
+    pub lane: i32,
+    pub eagerness: f64, // Where the driver's desired speed is in the range for their route, from 0 at the bottom to 1 at the top.
+    pub chance: f64, // Whether the driver gives way when they should: they do if it's below their profile's compliance.
+}
+
+#[derive(Clone)]
+struct Platoon {
+    id: u64, // The `id` of the car that led it when it formed.
+    size: usize,
+    entered: Option<u64>, // The tick when its first member entered the intersection.
+}
+
+#[derive(Clone)]
 pub struct Traffic {
     pub cars: Vec<Car>,
     pub cars_passed: i32,
     pub give_ways: i32,
     pub max_time: Duration,
     pub min_time: Duration,
+    pub ticks: u64,
+    pub platooning: bool,
+    pub overriding: bool, // Whether the smart controller overrides the minor roads' duty to give way, and lets cars through in the order they come, whichever road they're on.
+    pub platoons_crossed: i32,
+    pub platooned_cars: i32,
+    pub largest_platoon: usize,
+    pub platoon_time: Duration, // Total time taken by platoons to cross the intersection, from the first member entering to the last one leaving.
+    pub control: Control,
+    pub crashes: i32,
+    pub near_misses: i32,
+    pub hard_brakes: i32, // How many times a car had to brake harder than is comfortable, or stop dead.
+    pub slowest_speed: f64, // Of any car on any tick, in pixels per tick.
+    pub fastest_speed: f64,
+    pub desired_speeds: Vec<f64>, // Of the cars that have passed.
+    pub mix: Vec<(Class, u32)>, // How often each class of vehicle is spawned, relative to the others.
+    pub random_lanes: bool, // Whether a vehicle arrives in a lane picked at random, or in a lane for its turn.
+    pub random_vehicles: bool, // Whether a vehicle's class, driver and desired speed are picked at random, or it's a car with a normal driver who'd like to go at the lane speed.
+    pub classes_passed: Vec<(i32, Duration)>, // How many vehicles of each class, in the order of `Class::ALL`, have passed, and their total time.
+    pub profiles: Vec<(Profile, u32)>, // How often each kind of driver is behind the wheel, relative to the others.
+    pub profiles_passed: Vec<(i32, Duration)>, // As for `classes_passed`, by driver, in the order of `Profile::ALL`.
+    pub profile_crashes: Vec<i32>,             // How many crashes a driver of each kind was in.
+    pub routes_passed: Vec<((Airt, Airt), i32, Duration)>, // As for `classes_passed`, by the way each car went through, for the movements the layout has roads for.
+    pub lane_changes: i32,
+    pub missed_lanes: i32, // How many cars gave up on getting into the lane for their turn, and went where the lane they were in took them.
+    pub emergency_times: Vec<(Duration, Duration)>, // How long each emergency vehicle that has passed took, and how long it would have taken with the road to itself.
+    pub pedestrians: Vec<Pedestrian>,
+    pub pedestrian_rate: f64, // The chance on each tick that a pedestrian turns up.
+    pub pedestrians_crossed: i32,
+    pub pedestrians_hit: i32,
+    pub pedestrian_wait: Duration, // Total time pedestrians who have stepped out waited on the sidewalk first.
+    pub max_pedestrian_wait: Duration,
+    pub pedestrian_delay: Duration, // Total time cars were held back by pedestrians.
+    pub cyclist_crashes: i32, // The crashes and near misses that a cyclist was in, which are also counted with the rest.
+    pub cyclist_near_misses: i32,
+    pub handing_over: bool, // Whether it's one of the intersections in a road network, which keeps the cars that leave for the next one.
+    pub left_hand: bool, // Whether traffic keeps left. Left-hand traffic is the mirror image of right-hand traffic, so it's simulated as right-hand traffic in a mirror image of the intersection, and only drawn and described the other way round.
+    departures: Vec<Car>,
+    held: Vec<Car>, // Vehicles that have been spawned but are held back at the edge of the window, in the order they came, until there's room for them in their lane.
+    schedule: Option<VecDeque<Arrival>>, // The cars still to come, if a scenario is being played back.
+    platoons: Vec<Platoon>,
+    paths_through: Vec<PathThrough>, // Of each car that's in the intersection or coming up to it, in global control, and nothing for the others.
+    zones: Vec<Zone>,                // Of the pedestrians at the start of the tick.
+    encounters: Vec<((u64, u64), bool, bool)>, // Pairs of cars that are currently close to each other, whether they've touched, and whether either is a bicycle.
+    cars_spawned: u64,
+    rng: StdRng,
 }
 
 impl Traffic {
     pub fn new() -> Self {
+        Self::with_rng(StdRng::from_os_rng())
+    }
+
+    // For reproducible runs, e.g. in the checker.
+    pub fn seeded(seed: u64) -> Self {
+        Self::with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(rng: StdRng) -> Self {
         Traffic {
             cars: Vec::new(),
             cars_passed: 0,
             give_ways: 0,
             max_time: Duration::from_millis(0),
             min_time: Duration::MAX,
+            ticks: 0,
+            platooning: true,
+            overriding: false,
+            platoons_crossed: 0,
+            platooned_cars: 0,
+            largest_platoon: 0,
+            platoon_time: Duration::from_millis(0),
+            control: Control::Global,
+            crashes: 0,
+            near_misses: 0,
+            hard_brakes: 0,
+            slowest_speed: f64::INFINITY,
+            fastest_speed: 0.0,
+            desired_speeds: Vec::new(),
+            mix: vehicles::MIX.to_vec(),
+            random_lanes: true,
+            random_vehicles: true,
+            classes_passed: vec![(0, Duration::from_millis(0)); Class::ALL.len()],
+            profiles: drivers::MIX.to_vec(),
+            profiles_passed: vec![(0, Duration::from_millis(0)); Profile::ALL.len()],
+            profile_crashes: vec![0; Profile::ALL.len()],
+            routes_passed: Vec::new(),
+            lane_changes: 0,
+            missed_lanes: 0,
+            emergency_times: Vec::new(),
+            pedestrians: Vec::new(),
+            pedestrian_rate: pedestrians::RATE,
+            pedestrians_crossed: 0,
+            pedestrians_hit: 0,
+            pedestrian_wait: Duration::from_millis(0),
+            max_pedestrian_wait: Duration::from_millis(0),
+            pedestrian_delay: Duration::from_millis(0),
+            cyclist_crashes: 0,
+            cyclist_near_misses: 0,
+            handing_over: false,
+            left_hand: false,
+            departures: Vec::new(),
+            held: Vec::new(),
+            schedule: None,
+            platoons: Vec::new(),
+            paths_through: Vec::new(),
+            zones: Vec::new(),
+            encounters: Vec::new(),
+            cars_spawned: 0,
+            rng,
         }
     }
 
@@ -27,42 +170,583 @@ impl Traffic {
         &self,
         canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
         dimensions: &Dimensions,
-        car_textures: &[sdl2::render::Texture; 4],
+        car_textures: &[[sdl2::render::Texture; 4]],
     ) {
         for car in &self.cars {
-            car.draw(canvas, &dimensions, &car_textures);
+            car.draw(canvas, &dimensions, &car_textures, self.ticks);
+        }
+        for pedestrian in &self.pedestrians {
+            pedestrian.draw(canvas, dimensions);
         }
     }
 
     pub fn format(&self) -> String {
+        let s = format!(
+            "{}\nLane changes: {}\nMissed lanes: {}\n{}\n{}\n{}\n{}\n{}\n{}",
+            self.format_cars(),
+            self.lane_changes,
+            self.missed_lanes,
+            self.format_classes(),
+            self.format_profiles(),
+            self.format_routes(),
+            self.format_emergencies(),
+            self.format_pedestrians(),
+            self.format_cyclists()
+        );
+        if self.platoons_crossed == 0 {
+            format!("{}\nPlatoons: 0", s)
+        } else {
+            format!(
+                "{}\nPlatoons: {}\nMean platoon size: {:.1}\nLargest platoon: {}\nMean platoon crossing time: {:.2}s",
+                s,
+                self.platoons_crossed,
+                self.platooned_cars as f64 / self.platoons_crossed as f64,
+                self.largest_platoon,
+                self.platoon_time.as_secs_f64() / self.platoons_crossed as f64
+            )
+        }
+    }
+
+    fn format_cars(&self) -> String {
         if self.cars_passed == 0 {
-            "Crashes: 0\nNear misses: 0\nGive ways: 0\nCars passed: 0\nSlowest speed: N/A\nFastest speed: N/A\nMax time: N/A\nMin time: N/A".to_string()
+            format!(
+                "Crashes: {}\nNear misses: {}\nHard brakes: {}\nGive ways: 0\nCars passed: 0\nSlowest speed: N/A\nFastest speed: N/A\nDesired speeds: N/A\nMax time: N/A\nMin time: N/A",
+                self.crashes, self.near_misses, self.hard_brakes
+            )
         } else {
-            let slowest_speed = if self.give_ways == 0 { 250 } else { 0 };
+            let count = self.desired_speeds.len() as f64;
+            let mean = self.desired_speeds.iter().sum::<f64>() / count;
+            let deviation = (self
+                .desired_speeds
+                .iter()
+                .map(|speed| (speed - mean).powi(2))
+                .sum::<f64>()
+                / count)
+                .sqrt();
+            let lowest = self
+                .desired_speeds
+                .iter()
+                .copied()
+                .fold(f64::INFINITY, f64::min);
+            let highest = self.desired_speeds.iter().copied().fold(0.0, f64::max);
             format!(
-                "Crashes: 0\nNear misses: 0\nGive ways: {}\nCars passed: {}\nSlowest speed: {}px/s\nFastest speed: 1000px/s\nMax time: {:.2}s\nMin time: {:.2}s",
+                "Crashes: {}\nNear misses: {}\nHard brakes: {}\nGive ways: {}\nCars passed: {}\nSlowest speed: {:.0}px/s\nFastest speed: {:.0}px/s\nDesired speeds: {:.0} to {:.0}px/s, mean {:.0}px/s, standard deviation {:.0}px/s\nMax time: {:.2}s\nMin time: {:.2}s",
+                self.crashes,
+                self.near_misses,
+                self.hard_brakes,
                 self.give_ways,
                 self.cars_passed,
-                slowest_speed,
+                per_second(self.slowest_speed),
+                per_second(self.fastest_speed),
+                per_second(lowest),
+                per_second(highest),
+                per_second(mean),
+                per_second(deviation),
                 self.max_time.as_secs_f64(),
                 self.min_time.as_secs_f64()
             )
         }
     }
 
+    fn format_classes(&self) -> String {
+        let classes = Class::ALL.iter().zip(&self.classes_passed);
+        let passed = classes
+            .clone()
+            .map(|(class, &(count, _))| format!("{} {}", count, class.spec().name))
+            .collect::<Vec<String>>();
+        let times = classes
+            .map(|(class, &(count, time))| {
+                if count == 0 {
+                    format!("{} N/A", class.spec().name)
+                } else {
+                    format!(
+                        "{} {:.2}s",
+                        class.spec().name,
+                        time.as_secs_f64() / count as f64
+                    )
+                }
+            })
+            .collect::<Vec<String>>();
+        format!(
+            "Passed by class: {}\nMean time by class: {}",
+            passed.join(", "),
+            times.join(", ")
+        )
+    }
+
+    fn format_routes(&self) -> String {
+        let name = |(start, end): (Airt, Airt)| {
+            if self.left_hand {
+                format!("{:?}->{:?}", start.mirror(), end.mirror())
+            } else {
+                format!("{:?}->{:?}", start, end)
+            }
+        };
+        let passed = self
+            .routes_passed
+            .iter()
+            .map(|&(route, count, _)| format!("{} {}", count, name(route)))
+            .collect::<Vec<String>>();
+        let times = self
+            .routes_passed
+            .iter()
+            .map(|&(route, count, time)| {
+                if count == 0 {
+                    format!("{} N/A", name(route))
+                } else {
+                    format!("{} {:.2}s", name(route), time.as_secs_f64() / count as f64)
+                }
+            })
+            .collect::<Vec<String>>();
+        format!(
+            "Passed by route: {}\nMean time by route: {}",
+            passed.join(", "),
+            times.join(", ")
+        )
+    }
+
+    fn format_profiles(&self) -> String {
+        let profiles = Profile::ALL
+            .iter()
+            .zip(&self.profiles_passed)
+            .zip(&self.profile_crashes);
+        let passed = profiles
+            .clone()
+            .map(|((profile, &(count, _)), _)| format!("{} {}", count, profile.behaviour().name))
+            .collect::<Vec<String>>();
+        let times = profiles
+            .clone()
+            .map(|((profile, &(count, time)), _)| {
+                if count == 0 {
+                    format!("{} N/A", profile.behaviour().name)
+                } else {
+                    format!(
+                        "{} {:.2}s",
+                        profile.behaviour().name,
+                        time.as_secs_f64() / count as f64
+                    )
+                }
+            })
+            .collect::<Vec<String>>();
+        let crashes = profiles
+            .map(|((profile, _), &count)| format!("{} {}", count, profile.behaviour().name))
+            .collect::<Vec<String>>();
+        format!(
+            "Passed by driver: {}\nMean time by driver: {}\nCrashes by driver: {}",
+            passed.join(", "),
+            times.join(", "),
+            crashes.join(", ")
+        )
+    }
+
+    fn format_emergencies(&self) -> String {
+        let count = self.emergency_times.len();
+        if count == 0 {
+            return "Emergency vehicles: 0".to_string();
+        }
+        let delays = self
+            .emergency_times
+            .iter()
+            .map(|&(time, free_flow)| (time - free_flow).as_secs_f64())
+            .collect::<Vec<f64>>();
+        let free_flow = self
+            .emergency_times
+            .iter()
+            .map(|&(_, free_flow)| free_flow.as_secs_f64())
+            .sum::<f64>();
+        format!(
+            "Emergency vehicles: {}\nEmergency delay: mean {:.2}s over free flow of {:.2}s, max {:.2}s",
+            count,
+            delays.iter().sum::<f64>() / count as f64,
+            free_flow / count as f64,
+            delays.iter().copied().fold(0.0, f64::max)
+        )
+    }
+
+    fn format_pedestrians(&self) -> String {
+        let stepped_out = self.pedestrians_crossed
+            + self
+                .pedestrians
+                .iter()
+                .filter(|pedestrian| !pedestrian.waiting)
+                .count() as i32;
+        let wait = if stepped_out == 0 {
+            "N/A".to_string()
+        } else {
+            format!(
+                "mean {:.2}s, max {:.2}s",
+                self.pedestrian_wait.as_secs_f64() / stepped_out as f64,
+                self.max_pedestrian_wait.as_secs_f64()
+            )
+        };
+        format!(
+            "Pedestrians crossed: {}\nPedestrians hit: {}\nPedestrian wait: {}\nCar delay from pedestrians: {:.2}s",
+            self.pedestrians_crossed,
+            self.pedestrians_hit,
+            wait,
+            self.pedestrian_delay.as_secs_f64()
+        )
+    }
+
+    fn format_cyclists(&self) -> String {
+        let (passed, _) = self.classes_passed[Class::Bicycle as usize];
+        let minutes = (TICK * self.ticks as u32).as_secs_f64() / 60.0;
+        let rate = if minutes > 0.0 {
+            format!("{:.1} per minute", passed as f64 / minutes)
+        } else {
+            "N/A".to_string()
+        };
+        format!(
+            "Cyclists passed: {}, {}\nCyclist crashes: {}\nCyclist near misses: {}",
+            passed, rate, self.cyclist_crashes, self.cyclist_near_misses
+        )
+    }
+
+    // Spawns a car travelling the given way, unless there's no road for it to come in along.
     pub fn push(&mut self, initial_direction: Airt, dimensions: &Dimensions) {
-        self.cars
-            .push(Car::spawn(initial_direction, self.cars.len(), dimensions));
+        let Some(final_direction) = random_turn(initial_direction, dimensions, &mut self.rng)
+        else {
+            return;
+        };
+        self.push_route(initial_direction, final_direction, dimensions);
     }
 
     pub fn push_random(&mut self, dimensions: &Dimensions) {
-        let directions = [Airt::Up, Airt::Down, Airt::Left, Airt::Right];
-        let random_direction = directions[rand::rng().random_range(0..directions.len())];
-        self.cars
-            .push(Car::spawn(random_direction, self.cars.len(), &dimensions));
+        let random_direction = self.random_direction(dimensions);
+        self.push(random_direction, dimensions);
+    }
+
+    // Calls an ambulance, which arrives from a random direction.
+    pub fn push_emergency(&mut self, dimensions: &Dimensions) {
+        let initial_direction = self.random_direction(dimensions);
+        let Some(final_direction) = random_turn(initial_direction, dimensions, &mut self.rng)
+        else {
+            return;
+        };
+        let pick = Pick {
+            class: Class::Ambulance,
+            lane: home_lane(initial_direction, final_direction, dimensions),
+            ..self.pick(initial_direction, final_direction, dimensions)
+        };
+        self.push_pick(initial_direction, final_direction, pick, dimensions);
+    }
+
+    // Plays back a scenario: each of the given arrivals is spawned once the simulation has run until its time, instead of as keys are pressed.
+    pub fn play(&mut self, arrivals: Vec<Arrival>) {
+        self.schedule = Some(arrivals.into());
+    }
+
+    // Whether a scenario is being played back, which sends in all the cars itself.
+    pub fn playing(&self) -> bool {
+        self.schedule.is_some()
+    }
+
+    // Whether a scenario is being played back, and every car in it has come and gone.
+    pub fn finished(&self) -> bool {
+        self.schedule
+            .as_ref()
+            .is_some_and(|schedule| schedule.is_empty())
+            && self.is_empty()
+    }
+
+    // Whether there are no vehicles on the road, or held back waiting to come onto it.
+    pub fn is_empty(&self) -> bool {
+        self.cars.is_empty() && self.held.is_empty()
+    }
+
+    // How many vehicles are held back at the edge of the window, waiting for room to come onto the road.
+    pub fn held(&self) -> usize {
+        self.held.len()
+    }
+
+    // Spawns a car from a scenario, with a random turn, vehicle and driver for whichever it doesn't give, and in a random lane, unless it's an emergency vehicle, which arrives in a lane for its turn.
+    fn push_scheduled(&mut self, arrival: &Arrival, dimensions: &Dimensions) {
+        let Some(final_direction) = arrival
+            .end
+            .or_else(|| random_turn(arrival.start, dimensions, &mut self.rng))
+        else {
+            return;
+        };
+        let mut pick = self.pick(arrival.start, final_direction, dimensions);
+        if let Some(class) = arrival.class {
+            pick.class = class;
+        }
+        // A car given its turn comes in on a lane for it, as it would have lined up for it before coming into view.
+        if arrival.end.is_some() || pick.class.spec().emergency {
+            pick.lane = home_lane(arrival.start, final_direction, dimensions);
+        }
+        if let Some(profile) = arrival.profile {
+            pick.profile = profile;
+        }
+        self.push_pick(arrival.start, final_direction, pick, dimensions);
+    }
+
+    // Keeps every car where it was relative to the intersection once the window has been resized, given how much further out its edges are now, across and down, with the intersection still in the middle. Each route starts that much further out, so a car has come that much further along it. Pedestrians are placed relative to their crossings already.
+    pub fn recenter(&mut self, grown: (i32, i32), dimensions: &Dimensions) {
+        for car in &mut self.cars {
+            let (in_x, _) = car.direction.start.unit();
+            car.travelled += if in_x != 0.0 { grown.0 } else { grown.1 } as f64;
+            car.place(car.calculate_new_position(0.0, dimensions));
+        }
+    }
+
+    // Sends a pedestrian to wait at a random crossing.
+    pub fn push_pedestrian(&mut self, dimensions: &Dimensions) {
+        let pedestrian = Pedestrian::spawn(&mut self.rng, self.ticks, dimensions);
+        self.pedestrians.push(pedestrian);
+    }
+
+    // A random way to come into the intersection, along one of its arms.
+    fn random_direction(&mut self, dimensions: &Dimensions) -> Airt {
+        let arms = dimensions.arms();
+        arms[self.rng.random_range(0..arms.len())].opposite()
     }
 
-    pub fn update(&mut self, dimensions: &Dimensions) {
+    pub fn push_route(
+        &mut self,
+        initial_direction: Airt,
+        final_direction: Airt,
+        dimensions: &Dimensions,
+    ) {
+        let pick = self.pick(initial_direction, final_direction, dimensions);
+        self.push_pick(initial_direction, final_direction, pick, dimensions);
+    }
+
+    // Spawns a vehicle on a trip across a road network, taking the given turn at this intersection first.
+    pub fn push_trip(
+        &mut self,
+        initial_direction: Airt,
+        final_direction: Airt,
+        trip: Trip,
+        dimensions: &Dimensions,
+    ) {
+        let pick = self.pick(initial_direction, final_direction, dimensions);
+        let mut car = self.vehicle(initial_direction, final_direction, pick, dimensions);
+        car.trip = trip;
+        self.enter(car, dimensions);
+    }
+
+    // Spawns the vehicle that was picked for the given route.
+    pub fn push_pick(
+        &mut self,
+        initial_direction: Airt,
+        final_direction: Airt,
+        pick: Pick,
+        dimensions: &Dimensions,
+    ) {
+        let car = self.vehicle(initial_direction, final_direction, pick, dimensions);
+        self.enter(car, dimensions);
+    }
+
+    // Picks a vehicle for the given route, at random or not, as the traffic is set up, arriving in a random lane or a lane for its turn. An emergency vehicle always arrives in a lane for its turn.
+    pub fn pick(
+        &mut self,
+        initial_direction: Airt,
+        final_direction: Airt,
+        dimensions: &Dimensions,
+    ) -> Pick {
+        let class = if self.random_vehicles {
+            weighted(&self.mix, &mut self.rng).unwrap_or(Class::Car) // Cars if the mix is all zeros.
+        } else {
+            Class::Car
+        };
+        let lane = if self.random_lanes && !class.spec().emergency {
+            self.random_lane(initial_direction, final_direction, class, dimensions)
+        } else {
+            home_lane(initial_direction, final_direction, dimensions)
+        };
+        if !self.random_vehicles {
+            return Pick {
+                class,
+                profile: Profile::Normal,
+                lane,
+                eagerness: 0.5,
+                chance: 0.0,
+            };
+        }
+        Pick {
+            class,
+            profile: weighted(&self.profiles, &mut self.rng).unwrap_or(Profile::Normal),
+            lane,
+            eagerness: self.rng.random_range(0.0..=1.0),
+            chance: self.rng.random_range(0.0..1.0),
+        }
+    }
+
+    // A random lane for a vehicle of the given class to arrive in, out of those where there's room for it now, or out of all of them if there's room in none, in which case it's held back until there is.
+    fn random_lane(
+        &mut self,
+        initial_direction: Airt,
+        final_direction: Airt,
+        class: Class,
+        dimensions: &Dimensions,
+    ) -> i32 {
+        let lanes = (first_open_lane(initial_direction, dimensions)
+            ..dimensions.lanes_in(initial_direction.opposite()))
+            .collect::<Vec<i32>>();
+        let clear = lanes
+            .iter()
+            .copied()
+            .filter(|&lane| {
+                let mut ghost = Car::spawn(
+                    initial_direction,
+                    final_direction,
+                    class,
+                    self.cars.len(),
+                    self.cars_spawned,
+                    self.ticks,
+                    dimensions,
+                );
+                ghost.move_to_lane(lane, false, dimensions);
+                self.has_room(&ghost, dimensions)
+            })
+            .collect::<Vec<i32>>();
+        let choices = if clear.is_empty() { lanes } else { clear };
+        choices[self.rng.random_range(0..choices.len())]
+    }
+
+    // Takes over a car that has just driven out of a neighbouring intersection in a road network. It comes in along the road it turned into, in the lane it came out in, or the nearest open one there is, and heads on the given way, at the speed it was going. Returns false if there's no room for it yet, with a car in its lane too close to where it would come in.
+    pub fn push_arrival(
+        &mut self,
+        car: &Car,
+        next: Airt,
+        lanes: i32,
+        dimensions: &Dimensions,
+    ) -> bool {
+        let mut arrival = car.clone();
+        let start = car.direction.end;
+        arrival.direction = Direction { start, end: next };
+        arrival.destination = next;
+        arrival.travelled = 0.0;
+        arrival.shift = 0.0;
+        arrival.platoon = None;
+        arrival.gone = false;
+        arrival.birthday = self.ticks;
+        let lane = car
+            .lane
+            .min(lanes - 1)
+            .max(first_open_lane(start, dimensions));
+        arrival.move_to_lane(lane, car.bike_lane, dimensions);
+        if arrival.class.spec().emergency {
+            arrival.free_flow = Some(arrival.free_flow_ticks(dimensions));
+        }
+
+        let clear = self.has_room(&arrival, dimensions);
+        if clear {
+            self.admit(arrival, dimensions);
+        }
+        clear
+    }
+
+    // Whether a vehicle about to come in has room to: it wouldn't overlap any car, it would be at least the minimum gap behind any car in its lane, and no vehicle is held back waiting to come into its lane before it.
+    fn has_room(&self, car: &Car, dimensions: &Dimensions) -> bool {
+        self.cars.iter().all(|other| {
+            !overlap(&car.pose(), &other.pose(), dimensions)
+                && (other.lane() != car.lane()
+                    || other.travelled - (other.length + car.length) / 2.0
+                        >= dimensions.following.min_gap)
+        }) && self.held.iter().all(|other| other.lane() != car.lane())
+    }
+
+    // Puts a new vehicle on the road if there's room for it, and otherwise holds it back until there is.
+    fn enter(&mut self, car: Car, dimensions: &Dimensions) {
+        if self.has_room(&car, dimensions) {
+            self.admit(car, dimensions);
+        } else {
+            self.held.push(car);
+        }
+    }
+
+    // Lets the vehicles that were held back onto the road, in the order they came, as their lanes clear. The time they take starts when they do.
+    fn admit_held(&mut self, dimensions: &Dimensions) {
+        for mut car in std::mem::take(&mut self.held) {
+            if self.has_room(&car, dimensions) {
+                car.birthday = self.ticks;
+                self.admit(car, dimensions);
+            } else {
+                self.held.push(car);
+            }
+        }
+    }
+
+    // The cars that have left for the next intersection in a road network since the last time.
+    pub fn take_departures(&mut self) -> Vec<Car> {
+        std::mem::take(&mut self.departures)
+    }
+
+    // A new vehicle, as picked, heading for the given destination. A cyclist arrives in the bike lane, if there is one. Nobody arrives in a turn pocket, which doesn't open up until further on, so a vehicle that would arrives in the lane beside it instead.
+    fn vehicle(
+        &mut self,
+        initial_direction: Airt,
+        final_direction: Airt,
+        pick: Pick,
+        dimensions: &Dimensions,
+    ) -> Car {
+        let Pick { class, profile, .. } = pick;
+        let lane = pick
+            .lane
+            .max(first_open_lane(initial_direction, dimensions));
+        let bike_lane = class == Class::Bicycle && dimensions.bike_lane > 0;
+        let mut car = Car::spawn(
+            initial_direction,
+            final_direction,
+            class,
+            self.cars.len(),
+            self.cars_spawned,
+            self.ticks,
+            dimensions,
+        );
+        car.move_to_lane(lane, bike_lane, dimensions);
+        // Each driver has their own idea of how fast to go, and arrives at that speed. An emergency vehicle goes as fast as it can.
+        let top_speed = class.spec().top_speed * dimensions.speed.fast as f64;
+        car.cruise = if class.spec().emergency {
+            top_speed
+        } else {
+            let desired = dimensions.desired_speed(initial_direction, final_direction);
+            (desired.low + pick.eagerness * (desired.high - desired.low)).min(top_speed)
+        };
+        car.speed = car.cruise;
+        // Each driver also has their own way of driving.
+        car.profile = profile;
+        car.complies = pick.chance < profile.behaviour().compliance;
+        if class.spec().emergency {
+            car.free_flow = Some(car.free_flow_ticks(dimensions));
+        }
+        car
+    }
+
+    // Puts a car on the road at the speed it's arriving at, unless there's a car just ahead of it in its lane, which it would already have slowed down for before it came into view.
+    fn admit(&mut self, mut car: Car, dimensions: &Dimensions) {
+        car.index = self.cars.len();
+        car.id = self.cars_spawned;
+        if let Some(gap) = self
+            .cars
+            .iter()
+            .filter_map(|other| car.gap_behind(other))
+            .reduce(f64::min)
+        {
+            let safe = (2.0 * dimensions.dynamics.comfortable_braking * gap).sqrt();
+            car.speed = car.speed.min(safe);
+        }
+        self.cars.push(car);
+        self.cars_spawned += 1;
+    }
+
+    // Returns the pairs of cars whose bodies overlap.
+    pub fn collisions(&self, dimensions: &Dimensions) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for (i, car) in self.cars.iter().enumerate() {
+            for other in &self.cars[i + 1..] {
+                if overlap(&car.pose(), &other.pose(), dimensions) {
+                    pairs.push((car.index, other.index));
+                }
+            }
+        }
+        pairs
+    }
+
+    // Advances the simulation by one tick. Returns false if no car moved or left, i.e. if the traffic is at a standstill.
+    pub fn update(&mut self, dimensions: &Dimensions) -> bool {
         for (i, car) in self.cars.iter().enumerate() {
             debug_assert!(
                 car.index == i,
@@ -72,325 +756,1657 @@ impl Traffic {
             );
         }
 
+        // The cars in a scenario that are due come into view before anything moves.
+        while let Some(arrival) = self
+            .schedule
+            .as_mut()
+            .and_then(|schedule| schedule.pop_front_if(|arrival| arrival.tick <= self.ticks))
+        {
+            self.push_scheduled(&arrival, dimensions);
+        }
+        self.admit_held(dimensions);
+
+        self.ticks += 1;
+        if self.pedestrian_rate > 0.0 && self.rng.random_bool(self.pedestrian_rate.min(1.0)) {
+            self.push_pedestrian(dimensions);
+        }
+        self.zones = self
+            .pedestrians
+            .iter()
+            .filter_map(|pedestrian| pedestrian.zone(dimensions))
+            .collect();
+        self.change_lanes(dimensions);
+        let moved = match self.control {
+            Control::Global => self.update_global(dimensions),
+            Control::Local => self.update_local(dimensions),
+        };
+        let walked = self.update_pedestrians(dimensions);
+
+        if self.routes_passed.is_empty() {
+            self.routes_passed = dimensions
+                .movements()
+                .into_iter()
+                .map(|route| (route, 0, Duration::from_millis(0)))
+                .collect();
+        }
+        for car in &self.cars {
+            self.slowest_speed = self.slowest_speed.min(car.speed);
+            self.fastest_speed = self.fastest_speed.max(car.speed);
+            if car.gone {
+                if self.handing_over {
+                    self.departures.push(car.clone());
+                }
+                self.desired_speeds.push(car.cruise);
+                let passed = &mut self.classes_passed[car.class as usize];
+                passed.0 += 1;
+                passed.1 += TICK * (self.ticks - car.birthday) as u32;
+                let passed = &mut self.profiles_passed[car.profile as usize];
+                passed.0 += 1;
+                passed.1 += TICK * (self.ticks - car.birthday) as u32;
+                let route = (car.direction.start, car.direction.end);
+                if let Some(passed) = self.routes_passed.iter_mut().find(|(r, _, _)| *r == route) {
+                    passed.1 += 1;
+                    passed.2 += TICK * (self.ticks - car.birthday) as u32;
+                }
+                if let Some(free_flow) = car.free_flow {
+                    self.emergency_times.push((
+                        TICK * (self.ticks - car.birthday) as u32,
+                        TICK * free_flow as u32,
+                    ));
+                }
+            }
+        }
+
+        self.cars.retain(|car| !car.gone);
+
+        for (index, car) in self.cars.iter_mut().enumerate() {
+            car.index = index;
+            let stopped = car.speed <= 0.0;
+            if stopped && !car.stopped {
+                car.trip.stops += 1;
+            }
+            car.stopped = stopped;
+        }
+
+        self.track_platoons(dimensions);
+        self.count_encounters(dimensions);
+
+        moved || walked
+    }
+
+    // Each pedestrian waiting to cross steps out once no car is on the crossing or too close to stop comfortably before it, and the rest walk on until they're across. Returns whether any of them moved.
+    fn update_pedestrians(&mut self, dimensions: &Dimensions) -> bool {
+        let mut walked = false;
+        for pedestrian in &mut self.pedestrians {
+            if pedestrian.waiting {
+                let crossing = pedestrians::crossing(pedestrian.arm, dimensions);
+                if self
+                    .cars
+                    .iter()
+                    .any(|car| !car.gone && car.must_cross(&crossing, dimensions))
+                {
+                    continue;
+                }
+                pedestrian.waiting = false;
+                let wait = TICK * (self.ticks - pedestrian.birthday) as u32;
+                self.pedestrian_wait += wait;
+                self.max_pedestrian_wait = self.max_pedestrian_wait.max(wait);
+            }
+
+            pedestrian.walk(dimensions);
+            walked = true;
+
+            let body = pedestrian.body(dimensions);
+            if !pedestrian.hit
+                && self
+                    .cars
+                    .iter()
+                    .any(|car| !car.gone && collision::overlap(&car.pose().body(dimensions), &body))
+            {
+                pedestrian.hit = true;
+                self.pedestrians_hit += 1;
+            }
+            if pedestrian.has_crossed(dimensions) {
+                self.pedestrians_crossed += 1;
+            }
+        }
+        self.pedestrians
+            .retain(|pedestrian| !pedestrian.has_crossed(dimensions));
+        walked
+    }
+
+    // Each car in turn moves if it wouldn't collide with any of the others, taking into account the moves of those that went before.
+    fn update_global(&mut self, dimensions: &Dimensions) -> bool {
+        if self.platooning {
+            self.form_platoons(dimensions);
+        }
+
         let mut prospective_positions = self
             .cars
             .iter()
-            .map(|car| (car.x, car.y, car.index)) // The `index` is used to ignore "collisions" of a car with itself.
-            .collect::<Vec<(i32, i32, usize)>>();
-        let birthdays = self
+            .map(|car| (car.pose(), car.index)) // The `index` is used to ignore "collisions" of a car with itself.
+            .collect::<Vec<(Pose, usize)>>();
+        let lanes = self.cars.iter().map(Car::lane).collect::<Vec<Lane>>();
+        let platoons = self
             .cars
             .iter()
-            .map(|car| car.birthday)
-            .collect::<Vec<Instant>>();
-        let colors = self
+            .map(|car| if self.platooning { car.platoon } else { None })
+            .collect::<Vec<Option<u64>>>();
+        self.paths_through = vec![PathThrough::default(); self.cars.len()];
+        self.trace_paths_through(dimensions);
+
+        let mut moved = false;
+
+        for i in 0..self.cars.len() {
+            if let (true, Some(id)) = (self.platooning, self.cars[i].platoon) {
+                if self.cars[..i].iter().any(|car| car.platoon == Some(id)) {
+                    continue; // Followers are moved by their leader.
+                }
+                let members = (i..self.cars.len())
+                    .filter(|&j| self.cars[j].platoon == Some(id))
+                    .collect::<Vec<usize>>();
+                if self.update_platoon(
+                    &members,
+                    &mut prospective_positions,
+                    dimensions,
+                    &lanes,
+                    &platoons,
+                ) {
+                    moved = true;
+                }
+                self.trace_paths_through(dimensions);
+                continue;
+            }
+
+            let leader = self.leader(i);
+            let car = &mut self.cars[i];
+            if car.has_left(dimensions) {
+                car.leave(
+                    &mut self.cars_passed,
+                    &mut self.max_time,
+                    &mut self.min_time,
+                    self.ticks,
+                );
+                moved = true;
+                continue;
+            }
+            let surroundings = Surroundings {
+                paths_through: &self.paths_through,
+                lanes: &lanes,
+                platoons: &platoons,
+                zones: &self.zones,
+            };
+            let step = car.update(
+                &mut prospective_positions,
+                &surroundings,
+                leader.as_ref(),
+                dimensions,
+            );
+            if step.moved {
+                moved = true;
+            }
+            if step.yielding {
+                self.give_ways += 1;
+            }
+            if step.held {
+                self.pedestrian_delay += TICK;
+            }
+            if step.hard_braking {
+                self.hard_brakes += 1;
+            }
+            self.trace_paths_through(dimensions);
+        }
+
+        moved
+    }
+
+    // The nearest car in front of the given one in its lane, until it turns off a different way. The cars in a lane have all come the same way, so how far they've travelled says which is in front.
+    fn leader(&self, i: usize) -> Option<Leader> {
+        let car = &self.cars[i];
+        let (index, ahead) = self
             .cars
             .iter()
-            .map(|car| car.color_code)
-            .collect::<Vec<usize>>();
+            .enumerate()
+            .filter(|(_, other)| {
+                !other.gone
+                    && other.lane() == car.lane()
+                    && (other.direction.end == car.direction.end
+                        || other.heading() == car.direction.start)
+                    && other.travelled > car.travelled
+            })
+            .min_by(|(_, a), (_, b)| a.travelled.total_cmp(&b.travelled))?;
+        Some(Leader {
+            index,
+            gap: ahead.travelled - car.travelled - (ahead.length + car.length) / 2.0,
+            speed: ahead.speed,
+        })
+    }
+
+    // Notes the path through the intersection of every car that's in it or coming up to it and hasn't had its path noted yet, or has gone in since it was.
+    fn trace_paths_through(&mut self, dimensions: &Dimensions) {
+        let emergencies = self
+            .cars
+            .iter()
+            .filter(|car| !car.gone && car.class.spec().emergency && car.is_approaching(dimensions))
+            .map(|car| (car.lane(), car.distance_to_center(dimensions)))
+            .collect::<Vec<(Lane, f64)>>();
+        for (car, path) in self.cars.iter().zip(&mut self.paths_through) {
+            if car.gone {
+                continue;
+            }
+            if car.is_in_intersection(dimensions) {
+                if path.to_go.is_some() || path.poses.is_empty() {
+                    *path = PathThrough {
+                        poses: car.path_through(dimensions),
+                        to_go: None,
+                        priority: false,
+                        yields: false,
+                        speed: car.speed,
+                    };
+                }
+            } else if path.poses.is_empty() && car.is_approaching(dimensions) {
+                let to_go = car.distance_to_center(dimensions);
+                let priority = car.class.spec().emergency
+                    || emergencies
+                        .iter()
+                        .any(|&(lane, behind)| lane == car.lane() && behind > to_go);
+                // Its route runs through the intersection, so it gets there before the end of it, but it stops there regardless.
+                let mut ghost = car.clone();
+                let end = car.route(dimensions).length();
+                while !ghost.is_in_intersection(dimensions) && ghost.travelled < end {
+                    ghost.place(
+                        ghost
+                            .calculate_new_position(dimensions.lane_width as f64 / 8.0, dimensions),
+                    );
+                }
+                *path = PathThrough {
+                    poses: ghost.path_through(dimensions),
+                    to_go: Some((to_go, car.birthday)),
+                    priority,
+                    yields: !self.overriding && car.yields(dimensions),
+                    speed: car.speed,
+                };
+            }
+        }
+    }
+
+    // Every car decides how to accelerate based on a snapshot of what it can sense, then they all move at once. Nothing stops them from crashing.
+    fn update_local(&mut self, dimensions: &Dimensions) -> bool {
+        let snapshot = self
+            .cars
+            .iter()
+            .map(|car| car.as_neighbour(dimensions))
+            .collect::<Vec<Neighbour>>();
+
+        let decisions = self
+            .cars
+            .iter()
+            .map(|car| {
+                if car.has_left(dimensions) {
+                    return None;
+                }
+                let neighbours = snapshot
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, other)| {
+                        j != car.index
+                            && sensing::can_sense((car.x, car.y), (other.x, other.y), dimensions)
+                    })
+                    .map(|(_, other)| other)
+                    .collect::<Vec<&Neighbour>>();
+                Some(car.decide(&neighbours, &self.zones, dimensions))
+            })
+            .collect::<Vec<Option<Decision>>>();
+
+        let mut moved = false;
+
+        for (car, decision) in self.cars.iter_mut().zip(decisions) {
+            let Some(decision) = decision else {
+                car.leave(
+                    &mut self.cars_passed,
+                    &mut self.max_time,
+                    &mut self.min_time,
+                    self.ticks,
+                );
+                moved = true;
+                continue;
+            };
+
+            if decision.yielding {
+                self.give_ways += 1;
+            }
+            if decision.held {
+                self.pedestrian_delay += TICK;
+            }
+
+            let speed = car.react((car.speed + decision.acceleration).max(0.0));
+            if car.set_speed(speed, dimensions) {
+                self.hard_brakes += 1;
+            }
+            if car.speed > 0.0 {
+                car.place(car.calculate_new_position(car.speed, dimensions));
+                moved = true;
+            }
+        }
+
+        moved
+    }
+
+    // Counts a crash the first time two cars touch, and a near miss when two cars on different headings part without having touched, and either again for cyclists if one of them is a bicycle.
+    fn count_encounters(&mut self, dimensions: &Dimensions) {
+        let lane_width = dimensions.lane_width as f64;
+        let mut encounters = Vec::new();
+
+        for (i, car) in self.cars.iter().enumerate() {
+            for other in &self.cars[i + 1..] {
+                let touching = overlap(&car.pose(), &other.pose(), dimensions);
+                let close = collision::within(
+                    &car.pose().body(dimensions),
+                    &other.pose().body(dimensions),
+                    lane_width * NEAR_MISS_GAP,
+                ) && car.heading() != other.heading()
+                    && car.lane() != other.lane();
+                if !touching && !close {
+                    continue;
+                }
+
+                let pair = (car.id, other.id);
+                let cyclist = car.class == Class::Bicycle || other.class == Class::Bicycle;
+                let touched = self
+                    .encounters
+                    .iter()
+                    .find(|&&(p, _, _)| p == pair)
+                    .is_some_and(|&(_, touched, _)| touched);
+                if touching && !touched {
+                    self.crashes += 1;
+                    self.profile_crashes[car.profile as usize] += 1;
+                    self.profile_crashes[other.profile as usize] += 1;
+                    if cyclist {
+                        self.cyclist_crashes += 1;
+                    }
+                }
+                encounters.push((pair, touched || touching, cyclist));
+            }
+        }
+
+        for &(pair, touched, cyclist) in &self.encounters {
+            if !touched && !encounters.iter().any(|&(p, _, _)| p == pair) {
+                self.near_misses += 1;
+                if cyclist {
+                    self.cyclist_near_misses += 1;
+                }
+            }
+        }
+
+        self.encounters = encounters;
+    }
+
+    // Each car that isn't in a lane for its turn moves over a lane toward the nearest one if there's a gap it's happy with. It only moves over if it's moving, and if it can finish before it reaches the intersection. Once it can't, it gives up and goes where the lane it's in takes it. It can't move into a turn pocket until it's past where the pocket opens up.
+    fn change_lanes(&mut self, dimensions: &Dimensions) {
+        let lane_width = dimensions.lane_width as f64;
+        for i in 0..self.cars.len() {
+            let car = &self.cars[i];
+            if car.direction.end == car.destination
+                || car.shift != 0.0
+                || car.platoon.is_some()
+                || car.gone
+            {
+                continue;
+            }
+            let to_go = car.distance_to_center(dimensions)
+                - dimensions.reach(car.direction.start.opposite()) as f64
+                - car.length / 2.0;
+            if to_go < (LANE_CHANGE_LENGTH + 1.0) * lane_width {
+                self.cars[i].destination = car.direction.end;
+                self.missed_lanes += 1;
+                continue;
+            }
+            if car.speed <= 0.0 {
+                continue;
+            }
+
+            let start = car.direction.start;
+            let lane = car.lane;
+            let (first, last) = lanes_for(start, car.destination, dimensions);
+            let next = lane + (lane.clamp(first, last) - lane).signum();
+            let reach = dimensions.reach(start.opposite()) as f64;
+            if next == 0
+                && dimensions
+                    .pocket_opening(start.opposite())
+                    .is_some_and(|opening| to_go + reach > opening as f64)
+            {
+                continue;
+            }
+            if !self.has_gap(car, next, dimensions) {
+                continue;
+            }
+
+            // The car keeps its place on the road, but now it's in the next lane over, and it drifts across to the middle of it as it goes.
+            let offset = (next - lane) as f64 * lane_width;
+            let car = &mut self.cars[i];
+            car.shift = -offset;
+            car.lane = next;
+            car.direction.end = lane_turn(start, next, car.destination, dimensions);
+            self.lane_changes += 1;
+        }
+    }
+
+    // Whether the car could move over into the given lane, leaving the driver's headway and clearance to any car in that lane in front of it, and at least as much to any car behind it as that car's driver would leave, plus whatever more either would need to brake comfortably to the other's speed.
+    fn has_gap(&self, car: &Car, lane: i32, dimensions: &Dimensions) -> bool {
+        let lane_width = dimensions.lane_width as f64;
+        let dynamics = &dimensions.dynamics;
+        let start = car.direction.start;
+        let (in_x, in_y) = start.unit();
+        let along = |other: &Car| other.x * in_x + other.y * in_y;
+        let across = |other: &Car| other.y * in_x - other.x * in_y;
+        let offset = (lane - car.lane) as f64;
+        let target = across(car) + offset * lane_width;
+        let clearance = dimensions.following.min_gap + car.profile.behaviour().gap * lane_width;
+
+        self.cars
+            .iter()
+            .filter(|other| {
+                other.index != car.index
+                    && !other.gone
+                    && !other.bike_lane
+                    && other.direction.start == start
+                    && other.heading() == start
+                    && (across(other) - target).abs() < lane_width
+            })
+            .all(|other| {
+                let ahead = along(other) - along(car);
+                let gap = ahead.abs() - (other.length + car.length) / 2.0;
+                let needed = if ahead >= 0.0 {
+                    car.headway(car.speed)
+                        + (dynamics.stopping_distance(car.speed)
+                            - dynamics.stopping_distance(other.speed))
+                        .max(0.0)
+                } else {
+                    other.headway(other.speed)
+                        + (dynamics.stopping_distance(other.speed)
+                            - dynamics.stopping_distance(car.speed))
+                        .max(0.0)
+                };
+                gap >= clearance + needed
+            })
+    }
+
+    // A car joins the platoon of the car in front of it if they're following the same route, neither has reached the intersection, and the gap between them is less than half a lane width. Keeping the gaps that small means no other car can slip between members.
+    fn form_platoons(&mut self, dimensions: &Dimensions) {
+        for i in 0..self.cars.len() {
+            let car = &self.cars[i];
+            if car.platoon.is_some() || car.is_in_intersection(dimensions) || !car.settled() {
+                continue;
+            }
+
+            // The nearest car ahead in the same lane that spawned before this one. No other car fits in a gap small enough to platoon across, so searching only those keeps the members of a platoon in order of spawning.
+            let Some(j) = (0..i)
+                .rev()
+                .find(|&j| car.gap_behind(&self.cars[j]).is_some())
+            else {
+                continue;
+            };
+            let ahead = &self.cars[j];
+            if ahead.is_in_intersection(dimensions)
+                || ahead.direction.end != car.direction.end
+                || !ahead.settled()
+                || car.gap_behind(ahead) >= Some(dimensions.lane_width as f64 / 2.0)
+            {
+                continue;
+            }
+
+            let id = match ahead.platoon {
+                Some(id) => {
+                    match self.platoons.iter_mut().find(|platoon| platoon.id == id) {
+                        Some(platoon)
+                            if platoon.entered.is_none() && platoon.size < MAX_PLATOON_SIZE =>
+                        {
+                            platoon.size += 1;
+                        }
+                        _ => continue,
+                    }
+                    id
+                }
+                None => {
+                    let id = ahead.id;
+                    self.platoons.push(Platoon {
+                        id,
+                        size: 2,
+                        entered: None,
+                    });
+                    self.cars[j].platoon = Some(id);
+                    id
+                }
+            };
+            self.cars[i].platoon = Some(id);
+        }
+    }
+
+    // Moves all the members of a platoon, in order from front to back.
+    fn update_platoon(
+        &mut self,
+        members: &[usize],
+        prospective_positions: &mut [(Pose, usize)],
+        dimensions: &Dimensions,
+        lanes: &[Lane],
+        platoons: &[Option<u64>],
+    ) -> bool {
+        let mut moved = false;
+        let mut remaining = Vec::new();
+
+        for &m in members {
+            let car = &mut self.cars[m];
+            if car.has_left(dimensions) {
+                car.leave(
+                    &mut self.cars_passed,
+                    &mut self.max_time,
+                    &mut self.min_time,
+                    self.ticks,
+                );
+                moved = true;
+            } else {
+                remaining.push(m);
+            }
+        }
+
+        let mut speeds = remaining
+            .iter()
+            .map(|&m| {
+                let car = &self.cars[m];
+                let room = car
+                    .room(
+                        prospective_positions,
+                        &self.paths_through,
+                        dimensions,
+                        lanes,
+                        platoons,
+                    )
+                    .min(car.pedestrian_room(&self.zones, dimensions));
+                car.safe_speed(room, 0.0, dimensions) // The members of a platoon keep close together, whoever is driving.
+            })
+            .collect::<Vec<f64>>();
+
+        // The platoon enters the intersection as a unit: until its leader is inside, every member keeps to the speed of the most hemmed-in one, and they all stop if any would collide with a car outside the platoon.
+        let entering = remaining
+            .first()
+            .is_some_and(|&m| !self.cars[m].is_in_intersection(dimensions));
+        if entering {
+            let slowest = speeds.iter().copied().fold(f64::INFINITY, f64::min);
+            let blocked = remaining.iter().any(|&m| {
+                let car = &self.cars[m];
+                car.will_collide(
+                    &car.calculate_new_position(slowest, dimensions),
+                    prospective_positions,
+                    dimensions,
+                    lanes,
+                    platoons,
+                )
+            });
+            speeds.fill(if blocked { 0.0 } else { slowest });
+        }
+
+        // Once it's in, each member moves up unless it's blocked or would run into the member in front, e.g. while that one is turning.
+        let mut placed = Vec::new();
+        for (&m, speed) in remaining.iter().zip(speeds) {
+            let car = &mut self.cars[m];
+            let pose = car.calculate_new_position(speed, dimensions);
+            let blocked = speed <= 0.0
+                || car.will_collide(&pose, prospective_positions, dimensions, lanes, platoons)
+                || placed.iter().any(|other| overlap(&pose, other, dimensions));
+
+            let speed = if blocked { 0.0 } else { speed };
+            if speed < car.top_speed(dimensions) {
+                self.give_ways += 1;
+            }
+            if car.set_speed(speed, dimensions) {
+                self.hard_brakes += 1;
+            }
+            if blocked {
+                placed.push(car.pose());
+                continue;
+            }
+
+            prospective_positions[m] = (pose, m);
+            car.place(pose);
+            placed.push(pose);
+            moved = true;
+        }
+
+        moved
+    }
+
+    fn track_platoons(&mut self, dimensions: &Dimensions) {
+        let ticks = self.ticks;
+        let cars = &self.cars;
+        let mut crossed = Vec::new();
+
+        // A platoon has crossed once none of its members is in the intersection or still on the way to it, so it keeps its right of way until the last of them is in.
+        self.platoons.retain_mut(|platoon| {
+            let members = || cars.iter().filter(|car| car.platoon == Some(platoon.id));
+            let inside = members().any(|car| car.is_in_intersection(dimensions));
+            let approaching = members().any(|car| car.is_approaching(dimensions));
+            match platoon.entered {
+                None if inside => platoon.entered = Some(ticks),
+                Some(entered) if !inside && !approaching => {
+                    crossed.push((platoon.size, TICK * (ticks - entered) as u32));
+                    return false;
+                }
+                _ => {}
+            }
+            // Drop platoons whose members all left the screen without being seen in the intersection, which can only happen if platooning was switched off and on again.
+            cars.iter().any(|car| car.platoon == Some(platoon.id))
+        });
+
+        for (size, time) in crossed {
+            self.platoons_crossed += 1;
+            self.platooned_cars += size as i32;
+            self.largest_platoon = self.largest_platoon.max(size);
+            self.platoon_time += time;
+        }
+    }
+}
+
+// Converts a speed in pixels per tick to pixels per second.
+fn per_second(speed: f64) -> f64 {
+    speed / TICK.as_secs_f64()
+}
+
+// Whether the bodies of two cars overlap.
+fn overlap(a: &Pose, b: &Pose, dimensions: &Dimensions) -> bool {
+    collision::overlap(&a.body(dimensions), &b.body(dimensions))
+}
+
+// Whether two cars are closer than global control lets them get. Their paths are looked at a step of an eighth of a lane at a time, and the gap covers what happens between the steps: the back of a long vehicle swings out further than its middle moves.
+fn crowd(a: &Pose, b: &Pose, dimensions: &Dimensions) -> bool {
+    collision::within(
+        &a.body(dimensions),
+        &b.body(dimensions),
+        dimensions.lane_width as f64 / 8.0,
+    )
+}
+
+// Picks one of the choices at random, weighted by the mix. None if the weights are all zeros.
+fn weighted<T: Copy>(mix: &[(T, u32)], rng: &mut StdRng) -> Option<T> {
+    let total = mix.iter().map(|&(_, weight)| weight).sum::<u32>();
+    if total == 0 {
+        return None;
+    }
+    let mut pick = rng.random_range(0..total);
+    for &(choice, weight) in mix {
+        if pick < weight {
+            return Some(choice);
+        }
+        pick -= weight;
+    }
+    unreachable!("The pick is less than the total weight")
+}
+
+// A random way out for a car coming in the given way, or None if there's no road for it to come in along.
+fn random_turn(initial_direction: Airt, dimensions: &Dimensions, rng: &mut StdRng) -> Option<Airt> {
+    let turns = dimensions
+        .movements()
+        .into_iter()
+        .filter(|&(start, _)| start == initial_direction)
+        .map(|(_, end)| end)
+        .collect::<Vec<Airt>>();
+    (!turns.is_empty()).then(|| turns[rng.random_range(0..turns.len())])
+}
+
+// The turns are numbered from left to right, as the driver sees them: 0 for turning left, 1 for going straight on and 2 for turning right, with -1 for a U-turn, further left still.
+fn turn_index(start: Airt, end: Airt) -> i32 {
+    let (in_x, in_y) = start.unit();
+    let (out_x, out_y) = end.unit();
+    let turn = in_x * out_y - in_y * out_x; // Negative for a left turn, as in `Car::turn`.
+    if start == end.opposite() {
+        -1
+    } else if turn < 0.0 {
+        0
+    } else if turn > 0.0 {
+        2
+    } else {
+        1
+    }
+}
+
+// The first and last of the lanes that the given turn can be made from, with the lanes on each side numbered from the middle of the road outward. Left turns are made from the innermost lane and right turns from the outermost, and with three lanes or more, cars go straight on from the ones in between. With fewer, they go straight on from either lane, sharing it with the cars turning. Where there are only two ways to go, as at a T-junction, the lanes are shared out between them, the inner half for the one further left and the outer half for the other, with the middle lane for both if there's an odd number. U-turns are made from the innermost lane, whatever else it's for. Where the innermost lane is a turn pocket, it's only for turning left, and the other turns share the rest.
+fn lanes_for(start: Airt, end: Airt, dimensions: &Dimensions) -> (i32, i32) {
+    let lanes = dimensions.lanes_in(start.opposite());
+    let turn = turn_index(start, end);
+    if turn < 0 {
+        return (0, 0);
+    }
+    let pocket = dimensions.pocket_opening(start.opposite()).is_some();
+    let turns = dimensions
+        .movements()
+        .into_iter()
+        .filter(|&(s, e)| s == start && turn_index(s, e) >= 0)
+        .map(|(s, e)| turn_index(s, e))
+        .collect::<Vec<i32>>();
+    match turns.as_slice() {
+        [_, _, _] => match turn {
+            0 => (0, 0),
+            2 => (lanes - 1, lanes - 1),
+            _ if lanes < 3 => (pocket as i32, lanes - 1),
+            _ => (1, lanes - 2),
+        },
+        [_, _] if pocket && turn == 0 => (0, 0),
+        [_, _] if pocket => (1, lanes - 1),
+        &[one, other] if turn == one.min(other) => (0, (lanes - 1) / 2),
+        [_, _] => (lanes / 2, lanes - 1),
+        _ => (0, lanes - 1),
+    }
+}
+
+// The innermost lane a car can arrive in, coming in the given way: the one beside the turn pocket, if there is one.
+fn first_open_lane(start: Airt, dimensions: &Dimensions) -> i32 {
+    dimensions.pocket_opening(start.opposite()).is_some() as i32
+}
+
+// The lane a car arrives in if it arrives in a lane for its turn: the middle one of those it can take it from.
+pub fn home_lane(start: Airt, end: Airt, dimensions: &Dimensions) -> i32 {
+    let (first, last) = lanes_for(start, end, dimensions);
+    (first + last) / 2
+}
+
+// Where a car in the given lane goes: its destination, if it can turn that way from there, and otherwise the nearest way it can.
+fn lane_turn(start: Airt, lane: i32, destination: Airt, dimensions: &Dimensions) -> Airt {
+    let wanted = turn_index(start, destination);
+    dimensions
+        .movements()
+        .into_iter()
+        .filter(|&(s, e)| {
+            let (first, last) = lanes_for(s, e, dimensions);
+            s == start && (first..=last).contains(&lane)
+        })
+        .min_by_key(|&(s, e)| (turn_index(s, e) - wanted).abs())
+        .map(|(_, end)| end)
+        .expect("Invalid lane")
+}
+
+// Where a car is and which way it's facing: `x` and `y` are the top left corner of the lane-wide square centered on its body, and `angle` is its heading in degrees clockwise from straight up, as `copy_ex` expects. `travelled` is how far it's come along its route. `length` and `width` are the size of its body in pixels.
+#[derive(Clone, Copy)]
+struct Pose {
+    x: f64,
+    y: f64,
+    angle: f64,
+    travelled: f64,
+    shift: f64,
+    length: f64,
+    width: f64,
+}
 
-        for car in self.cars.iter_mut() {
-            if !car.update(
-                &mut prospective_positions,
-                &mut self.cars_passed,
-                &mut self.max_time,
-                &mut self.min_time,
-                &dimensions,
-                &birthdays,
-                &colors,
-            ) {
-                self.give_ways += 1;
-            }
+impl Pose {
+    // The car's body, centered on its square and turned to face the way it's going.
+    fn body(&self, dimensions: &Dimensions) -> Rectangle {
+        let half_lane = dimensions.lane_width as f64 / 2.0;
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+        Rectangle {
+            center: (self.x + half_lane, self.y + half_lane),
+            heading: (sin, -cos),
+            half_length: self.length / 2.0,
+            half_width: self.width / 2.0,
         }
+    }
+}
 
-        self.cars.retain(|car| !car.gone);
+// The way a car will go through the intersection, from where it is now if it's in it, or from where it'll enter it. `to_go` is how far a car that's still coming up to it has to go to get to the middle, and its birthday, to tell which of two cars gets there first. A car coming up to it has `priority` if it's an emergency vehicle, or ahead of one in its lane and so in its way, and `yields` if it's on a minor road, unless the smart controller is overriding that.
+#[derive(Clone, Default)]
+struct PathThrough {
+    poses: Vec<Pose>,
+    to_go: Option<(f64, u64)>,
+    priority: bool,
+    yields: bool,
+    speed: f64, // Of the car now.
+}
 
-        for (index, car) in self.cars.iter_mut().enumerate() {
-            car.index = index;
-        }
+// The angle, as in `Pose`, of a vector pointing the given way.
+fn angle_of(direction: (f64, f64)) -> f64 {
+    direction.0.atan2(-direction.1).to_degrees()
+}
+
+// The lane a car is in, on the side it came from, or None for the bike lane on the outside of them, and the turn it's making from there. Two cars are in the same lane whichever ways they're turning, since a lane can be for more than one turn.
+#[derive(Copy, Clone)]
+struct Lane {
+    start: Airt,
+    end: Airt,
+    index: Option<i32>,
+}
+
+impl PartialEq for Lane {
+    fn eq(&self, other: &Lane) -> bool {
+        self.start == other.start && self.index == other.index
     }
 }
 
+impl Lane {
+    // Whether a car in this lane turns right across the bike lane beside it, and so across the way of a cyclist going straight on in the other.
+    fn crosses(&self, other: &Lane) -> bool {
+        self.index.is_some()
+            && turn_index(self.start, self.end) == 2
+            && other.index.is_none()
+            && other.start == self.start
+            && other.end == other.start
+    }
+}
+
+// The car in front of another in its lane, as seen by the car-following model.
+struct Leader {
+    index: usize,
+    gap: f64, // Between its back and the front of the car behind, along the route they share.
+    speed: f64,
+}
+
+// What a car under global control knows of the others as it moves, besides where each of them is about to be: their paths through the intersection, their lanes and platoons, by index, and what pedestrians have yet to cross.
+struct Surroundings<'a> {
+    paths_through: &'a [PathThrough],
+    lanes: &'a [Lane],
+    platoons: &'a [Option<u64>],
+    zones: &'a [Zone],
+}
+
+// What a car did on one tick.
+struct Step {
+    moved: bool,
+    yielding: bool, // Held below the speed it would otherwise have reached by a car in its way.
+    held: bool,     // Held back by a pedestrian in particular.
+    hard_braking: bool, // Started braking harder than is comfortable, or stopped dead.
+}
+
+#[derive(Clone)]
 pub struct Car {
-    x: i32,
-    y: i32,
+    x: f64,
+    y: f64,
+    angle: f64,
+    travelled: f64,
+    shift: f64, // How far it has yet to move over, to its right, into the middle of that lane.
+    destination: Airt, // The way it wants to go. Until it's in a lane for that turn, `direction.end` is where the lane it's in takes it.
     color_code: usize,
+    class: Class,
+    profile: Profile,
+    complies: bool, // Whether the driver gives way when they should, in local control.
+    hesitation: u64, // How many ticks the driver has sat still since the way ahead cleared.
+    length: f64,    // Of the body, in pixels.
+    width: f64,
     direction: Direction,
-    speed: i32,
-    vertical: bool,
+    speed: f64,        // Distance moved per tick.
+    acceleration: f64, // The change in speed on the last tick.
+    cruise: f64,       // The speed the car goes at when nothing is in its way.
     gone: bool,
     index: usize,
-    birthday: Instant,
+    id: u64,
+    birthday: u64,
+    platoon: Option<u64>,
+    free_flow: Option<u64>, // For an emergency vehicle, how many ticks it would take to pass with the road to itself.
+    lane: i32, // Which lane it's in, counting from the middle of the road outward, from 0.
+    bike_lane: bool, // Whether it's a bicycle in the bike lane, whatever `lane` says.
+    stopped: bool, // Whether it was at a standstill at the end of the last tick.
+    trip: Trip,
 }
 
+#[derive(Clone)]
 struct Direction {
     start: Airt,
     end: Airt,
 }
 
 impl Car {
-    pub fn spawn(initial_direction: Airt, index: usize, dimensions: &Dimensions) -> Self {
-        let x;
-        let y;
-        let final_direction;
-        let color_code;
-        let mut speed = dimensions.speed.default;
-        let vertical;
-
-        let r = rand::rng().random_range(0..3);
-
-        match &initial_direction {
-            Airt::Up => {
-                vertical = true;
-                color_code = 0; // red
-                match r {
-                    0 => {
-                        x = dimensions.half_width;
-                        y = dimensions.window_height - dimensions.lane_width;
-                        final_direction = Airt::Left;
-                    }
-                    1 => {
-                        x = dimensions.half_width + dimensions.lane_width;
-                        y = dimensions.window_height - dimensions.lane_width;
-                        final_direction = Airt::Up;
-                        speed = dimensions.speed.fast;
-                    }
-                    2 => {
-                        x = dimensions.half_width + 2 * dimensions.lane_width;
-                        y = dimensions.window_height - dimensions.lane_width;
-                        final_direction = Airt::Right;
-                        speed = dimensions.speed.slow;
-                    }
-                    _ => {
-                        panic!("Invalid turn");
-                    }
-                }
-            }
-
-            Airt::Down => {
-                vertical = true;
-                color_code = 1; // green
-                match r {
-                    0 => {
-                        x = dimensions.half_width - 3 * dimensions.lane_width;
-                        y = 0;
-                        final_direction = Airt::Left;
-                        speed = dimensions.speed.slow;
-                    }
-                    1 => {
-                        x = dimensions.half_width - 2 * dimensions.lane_width;
-                        y = 0;
-                        final_direction = Airt::Down;
-                        speed = dimensions.speed.fast;
-                    }
-                    _ => {
-                        x = dimensions.half_width - dimensions.lane_width;
-                        y = 0;
-                        final_direction = Airt::Right;
-                    }
-                }
-            }
-
-            Airt::Right => {
-                vertical = false;
-                color_code = 2; // blue
-                match r {
-                    0 => {
-                        x = 0;
-                        y = dimensions.half_height;
-                        final_direction = Airt::Up;
-                    }
-                    1 => {
-                        x = 0;
-                        y = dimensions.half_height + dimensions.lane_width;
-                        final_direction = Airt::Right;
-                        speed = dimensions.speed.fast;
-                    }
-                    _ => {
-                        x = 0;
-                        y = dimensions.half_height + 2 * dimensions.lane_width;
-                        final_direction = Airt::Down;
-                        speed = dimensions.speed.slow;
-                    }
-                }
-            }
-
-            Airt::Left => {
-                vertical = false;
-                color_code = 3; // yellow
-                match r {
-                    0 => {
-                        x = dimensions.window_width - dimensions.lane_width;
-                        y = dimensions.half_height - 3 * dimensions.lane_width;
-                        final_direction = Airt::Up;
-                        speed = dimensions.speed.slow;
-                    }
-                    1 => {
-                        x = dimensions.window_width - dimensions.lane_width;
-                        y = dimensions.half_height - 2 * dimensions.lane_width;
-                        final_direction = Airt::Left;
-                        speed = dimensions.speed.fast;
-                    }
-                    2 => {
-                        x = dimensions.window_width - dimensions.lane_width;
-                        y = dimensions.half_height - dimensions.lane_width;
-                        final_direction = Airt::Down;
-                    }
-                    _ => {
-                        panic!("Invalid turn");
-                    }
-                }
-            }
-        }
+    pub fn spawn(
+        initial_direction: Airt,
+        final_direction: Airt,
+        class: Class,
+        index: usize,
+        id: u64,
+        birthday: u64,
+        dimensions: &Dimensions,
+    ) -> Self {
+        let color_code = match initial_direction {
+            Airt::Up => 0,    // red
+            Airt::Down => 1,  // green
+            Airt::Right => 2, // blue
+            Airt::Left => 3,  // yellow
+        };
+        let lane = home_lane(initial_direction, final_direction, dimensions);
+        let end = lane_turn(initial_direction, lane, final_direction, dimensions);
+        let route = dimensions.route(initial_direction, end, Some(lane));
+        let ((x, y), heading) = route.at(0.0);
 
         Car {
             x,
             y,
+            angle: angle_of(heading),
+            travelled: 0.0,
+            shift: 0.0,
+            destination: final_direction,
             color_code,
+            class,
+            profile: Profile::Normal,
+            complies: true,
+            hesitation: 0,
+            length: class.spec().length as f64 * dimensions.lane_width as f64 / 16.0,
+            width: class.spec().width as f64 * dimensions.lane_width as f64 / 16.0,
             direction: Direction {
                 start: initial_direction,
-                end: final_direction,
+                end,
             },
-            speed,
-            vertical,
+            speed: route.speed as f64,
+            acceleration: 0.0,
+            cruise: route.speed as f64,
             gone: false,
             index,
-            birthday: Instant::now(),
+            id,
+            birthday,
+            platoon: None,
+            free_flow: None,
+            lane,
+            bike_lane: false,
+            stopped: false,
+            trip: Trip {
+                started: birthday,
+                stops: 0,
+                exit: None,
+            },
+        }
+    }
+
+    // Moves the car sideways into the given lane, or into the bike lane beside them, keeping its place along the road. It then goes where that lane takes it.
+    fn move_to_lane(&mut self, lane: i32, bike_lane: bool, dimensions: &Dimensions) {
+        self.lane = lane;
+        self.bike_lane = bike_lane;
+        if !bike_lane {
+            self.direction.end =
+                lane_turn(self.direction.start, lane, self.destination, dimensions);
+        }
+        self.place(self.calculate_new_position(0.0, dimensions));
+    }
+
+    // The way it goes out of the intersection.
+    pub fn heading_out(&self) -> Airt {
+        self.direction.end
+    }
+
+    pub fn trip(&self) -> &Trip {
+        &self.trip
+    }
+
+    // Whether it comes out of the intersection in the same lane as the other car.
+    pub fn same_lane_out(&self, other: &Car) -> bool {
+        self.direction.end == other.direction.end
+            && self.lane == other.lane
+            && self.bike_lane == other.bike_lane
+    }
+
+    // The route it's on, from the lane it's in.
+    fn route<'a>(&self, dimensions: &'a Dimensions) -> &'a Route {
+        dimensions.route(
+            self.direction.start,
+            self.direction.end,
+            (!self.bike_lane).then_some(self.lane),
+        )
+    }
+
+    fn lane(&self) -> Lane {
+        Lane {
+            start: self.direction.start,
+            end: self.direction.end,
+            index: (!self.bike_lane).then_some(self.lane),
         }
     }
 
     fn will_collide(
         &self,
-        new_x: i32,
-        new_y: i32,
-        prospective_positions: &Vec<(i32, i32, usize)>,
+        new_pose: &Pose,
+        prospective_positions: &[(Pose, usize)],
         dimensions: &Dimensions,
-        birthdays: &Vec<Instant>,
-        colors: &Vec<usize>,
+        lanes: &[Lane],
+        platoons: &[Option<u64>],
     ) -> bool {
-        for other in prospective_positions {
-            if other.2 == self.index {
+        for (other, other_index) in prospective_positions {
+            let other_index = *other_index;
+            if other_index == self.index {
                 continue; // Don't collide with yourself.
             }
 
-            let other_birthday = birthdays[other.2];
-            let self_birthday = birthdays[self.index];
-            if other_birthday > self_birthday && colors[other.2] == colors[self.index] {
-                continue; // Don't collide with cars of the same color that spawned after you; you have right of way, e.g. if you're turning and they're right behind you.
+            if platoons[self.index].is_some() && platoons[other_index] == platoons[self.index] {
+                continue; // Members of a platoon keep out of each other's way in `Traffic::update_platoon`.
             }
 
-            if new_x < other.0 + dimensions.lane_width
-                && new_x + dimensions.lane_width > other.0
-                && new_y < other.1 + dimensions.lane_width
-                && new_y + dimensions.lane_width > other.1
-            {
+            // Two cars level with each other in a lane, as when they've just come in, are taken in the order they came, so that exactly one of them goes first.
+            let behind = other.travelled < self.travelled
+                || (other.travelled == self.travelled && other_index > self.index);
+            if behind && lanes[other_index] == lanes[self.index] {
+                continue; // Don't collide with cars behind you in your lane; you have right of way, e.g. if you're turning and they're right behind you. Cars in the lanes beside you are another matter, since you swing a little way into them as you turn.
+            }
+
+            if overlap(new_pose, other, dimensions) {
                 return true;
             }
         }
         false
     }
 
-    fn update(
+    fn pose(&self) -> Pose {
+        Pose {
+            x: self.x,
+            y: self.y,
+            angle: self.angle,
+            travelled: self.travelled,
+            shift: self.shift,
+            length: self.length,
+            width: self.width,
+        }
+    }
+
+    fn place(&mut self, pose: Pose) {
+        self.x = pose.x;
+        self.y = pose.y;
+        self.angle = pose.angle;
+        self.travelled = pose.travelled;
+        self.shift = pose.shift;
+    }
+
+    // A unit vector in the direction the car is facing.
+    fn facing(&self) -> (f64, f64) {
+        let radians = self.angle.to_radians();
+        (radians.sin(), -radians.cos())
+    }
+
+    // The way the car is going now, to the nearest right angle: its initial direction until it's halfway through its turn, and its final direction after.
+    fn heading(&self) -> Airt {
+        let (dx, dy) = self.facing();
+        if dx.abs() > dy.abs() {
+            if dx > 0.0 { Airt::Right } else { Airt::Left }
+        } else if dy > 0.0 {
+            Airt::Down
+        } else {
+            Airt::Up
+        }
+    }
+
+    // What other cars can see of this one.
+    fn as_neighbour(&self, dimensions: &Dimensions) -> Neighbour {
+        Neighbour {
+            x: self.x,
+            y: self.y,
+            heading: self.facing(),
+            body: self.pose().body(dimensions),
+            speed: self.speed,
+            path: self.path(self.speed, dimensions),
+            route: self.path(dimensions.speed.fast as f64, dimensions),
+            emergency: self.class.spec().emergency,
+            cyclist: self.bike_lane && self.direction.start == self.direction.end,
+            crosses_bike_lane: self.crosses_bike_lane(dimensions),
+            circulating: self.circulating(dimensions),
+            yields: self.yields(dimensions),
+        }
+    }
+
+    // Where the car would be on each tick within the sensing horizon if it kept to the given speed.
+    fn path(&self, speed: f64, dimensions: &Dimensions) -> Vec<Rectangle> {
+        let mut ghost = self.clone();
+        (0..sensing::HORIZON)
+            .map(|_| {
+                ghost.place(ghost.calculate_new_position(speed, dimensions));
+                ghost.pose().body(dimensions)
+            })
+            .collect()
+    }
+
+    fn decide(
+        &self,
+        neighbours: &[&Neighbour],
+        zones: &[Zone],
+        dimensions: &Dimensions,
+    ) -> Decision {
+        let path = self.path(self.cruise, dimensions);
+        let center = self.pose().body(dimensions).center;
+        let pedestrians = zones
+            .iter()
+            .filter(|zone| {
+                sensing::can_sense(center, zone.area.center, dimensions)
+                    && self.stops_for(zone, dimensions)
+            })
+            .map(|zone| &zone.area)
+            .collect::<Vec<&Rectangle>>();
+        let route = self.path(dimensions.speed.fast as f64, dimensions);
+        let own = Own {
+            x: self.x,
+            y: self.y,
+            heading: self.facing(),
+            body: self.pose().body(dimensions),
+            speed: self.speed,
+            cruise: self.cruise,
+            acceleration: self.acceleration(dimensions),
+            path: &path,
+            route: &route,
+            emergency: self.class.spec().emergency,
+            headway: self.headway(self.speed),
+            gap: self.profile.behaviour().gap,
+            complies: self.complies,
+            cyclist: self.bike_lane && self.direction.start == self.direction.end,
+            crosses_bike_lane: self.crosses_bike_lane(dimensions),
+            circulating: self.circulating(dimensions),
+            yields: self.yields(dimensions),
+        };
+        sensing::decide(&own, neighbours, &pedestrians, dimensions)
+    }
+
+    // Whether any corner of the car's body is on the ring of a roundabout. A long vehicle's body reaches past its square, and it has to wait for a gap before it pokes out onto the ring.
+    fn reaches_ring(&self, dimensions: &Dimensions) -> bool {
+        let reach = dimensions.reach(self.direction.start.opposite()) as f64;
+        self.pose()
+            .body(dimensions)
+            .corners()
+            .iter()
+            .any(|&(x, y)| {
+                (x - dimensions.half_width as f64).hypot(y - dimensions.half_height as f64) < reach
+            })
+    }
+
+    // Whether the car is coming up to the intersection on a minor road, and so gives way to those on the major road, and to those already in the intersection. At a roundabout, the ring decides instead.
+    fn yields(&self, dimensions: &Dimensions) -> bool {
+        dimensions.layout != Layout::Roundabout
+            && dimensions.minor(self.direction.start.opposite())
+            && self.is_approaching(dimensions)
+    }
+
+    // Whether the car is on the ring of a roundabout, where it has right of way over those coming onto it.
+    fn circulating(&self, dimensions: &Dimensions) -> bool {
+        dimensions.layout == Layout::Roundabout && self.is_in_intersection(dimensions)
+    }
+
+    // Whether the car is turning right across a bike lane, and hasn't turned yet. At a roundabout, the bike lane goes round the outside of the ring, and a car turning right stays inside it.
+    fn crosses_bike_lane(&self, dimensions: &Dimensions) -> bool {
+        dimensions.layout != Layout::Roundabout
+            && dimensions.bike_lane > 0
+            && !self.bike_lane
+            && turn_index(self.direction.start, self.direction.end) == 2
+            && self.heading() == self.direction.start
+    }
+
+    // How long the car would take to pass if nothing got in its way, counted like the time it actually takes: up to the tick after it goes off screen, when it leaves. It goes off screen once it's past the end of its route, which ends at the edge of the window, so it's worked out in closed form from how much of the route is left, and a car that barely moves doesn't take forever to work it out.
+    fn free_flow_ticks(&self, dimensions: &Dimensions) -> u64 {
+        let left = self.route(dimensions).length() - self.travelled;
+        if left < 0.0 {
+            return 1;
+        }
+        2 + (left / self.cruise).floor() as u64
+    }
+
+    fn has_left(&self, dimensions: &Dimensions) -> bool {
+        self.x < 0.0
+            || self.x + dimensions.lane_width as f64 > dimensions.window_width as f64
+            || self.y < 0.0
+            || self.y + dimensions.lane_width as f64 > dimensions.window_height as f64
+    }
+
+    fn leave(
         &mut self,
-        prospective_positions: &mut Vec<(i32, i32, usize)>,
         cars_passed: &mut i32,
         max_time: &mut Duration,
         min_time: &mut Duration,
-        dimensions: &Dimensions,
-        birthdays: &Vec<Instant>,
-        colors: &Vec<usize>,
-    ) -> bool {
-        if self.x < 0
-            || self.x + dimensions.lane_width > dimensions.window_width
-            || self.y < 0
-            || self.y + dimensions.lane_width > dimensions.window_height
+        ticks: u64,
+    ) {
+        *cars_passed += 1;
+        self.gone = true;
+        let elapsed = TICK * (ticks - self.birthday) as u32;
+        if *max_time < elapsed {
+            *max_time = elapsed;
+        }
+        if *min_time > elapsed {
+            *min_time = elapsed;
+        }
+    }
+
+    // Whether the car is in a lane for its turn, and not still moving over into it.
+    fn settled(&self) -> bool {
+        self.direction.end == self.destination && self.shift == 0.0
+    }
+
+    // Whether the car has yet to enter the intersection.
+    fn is_approaching(&self, dimensions: &Dimensions) -> bool {
+        !self.is_in_intersection(dimensions) && self.distance_to_center(dimensions) > 0.0
+    }
+
+    // How far the middle of the intersection is ahead of the car, or behind it if that's negative.
+    fn distance_to_center(&self, dimensions: &Dimensions) -> f64 {
+        let half_lane = dimensions.lane_width as f64 / 2.0;
+        let (dx, dy) = self.facing();
+        dx * (dimensions.half_width as f64 - self.x - half_lane)
+            + dy * (dimensions.half_height as f64 - self.y - half_lane)
+    }
+
+    // Whether any part of the car is inside the rectangle where the roads cross, or, at a roundabout, whether its square or its body reaches onto the ring.
+    fn is_in_intersection(&self, dimensions: &Dimensions) -> bool {
+        let lane_width = dimensions.lane_width as f64;
+        let half_width = dimensions.half_width as f64;
+        let half_height = dimensions.half_height as f64;
+        let reach = |arm: Airt| dimensions.reach(arm) as f64;
+        if dimensions.layout == Layout::Roundabout {
+            let half_lane = lane_width / 2.0;
+            let (dx, dy) = (
+                self.x + half_lane - half_width,
+                self.y + half_lane - half_height,
+            );
+            return (dx * dx + dy * dy).sqrt() < reach(Airt::Up) + half_lane
+                || self.reaches_ring(dimensions);
+        }
+        self.x + lane_width > half_width - reach(Airt::Left)
+            && self.x < half_width + reach(Airt::Right)
+            && self.y + lane_width > half_height - reach(Airt::Up)
+            && self.y < half_height + reach(Airt::Down)
+    }
+
+    // The gap between the back of `ahead` and the front of this car, if `ahead` is in front of it in the same lane and hasn't turned yet.
+    fn gap_behind(&self, ahead: &Car) -> Option<f64> {
+        if ahead.lane() != self.lane()
+            || ahead.heading() != self.direction.start
+            || self.heading() != self.direction.start
         {
-            *cars_passed += 1;
-            self.gone = true;
-            let elapsed = Instant::now().duration_since(self.birthday);
-            if *max_time < elapsed {
-                *max_time = elapsed;
+            return None;
+        }
+
+        let gap = match self.direction.start {
+            Airt::Up => self.y - ahead.y,
+            Airt::Down => ahead.y - self.y,
+            Airt::Left => self.x - ahead.x,
+            Airt::Right => ahead.x - self.x,
+        } - (self.length + ahead.length) / 2.0;
+
+        if gap < 0.0 { None } else { Some(gap) }
+    }
+
+    // How much faster the car can go on each tick, depending on its class.
+    fn acceleration(&self, dimensions: &Dimensions) -> f64 {
+        dimensions.dynamics.acceleration * self.class.spec().acceleration
+    }
+
+    // The speed the car would reach on this tick by the Intelligent Driver Model, following the car in front of it in its lane, if any. The driver eases off as they near their desired speed, and as they close in on the car in front, aiming to keep a minimum gap to it plus their headway, and to brake no harder than is comfortable as long as they start braking in good time.
+    fn following_speed(&self, leader: Option<&Leader>, dimensions: &Dimensions) -> f64 {
+        let following = &dimensions.following;
+        let dynamics = &dimensions.dynamics;
+        let acceleration = self.acceleration(dimensions);
+        let free_road = 1.0 - (self.speed / self.cruise).powf(following.exponent);
+        let interaction = leader.map_or(0.0, |leader| {
+            let closing = self.speed - leader.speed;
+            let desired_gap = following.min_gap
+                + (self.headway(self.speed)
+                    + self.speed * closing
+                        / (2.0 * (acceleration * dynamics.comfortable_braking).sqrt()))
+                .max(0.0);
+            (desired_gap / leader.gap.max(f64::EPSILON)).powi(2)
+        });
+        (self.speed + acceleration * (free_road - interaction))
+            .max(self.speed - dynamics.emergency_braking)
+            .max(0.0)
+    }
+
+    // The speed the car would reach on this tick if nothing were in its way.
+    fn top_speed(&self, dimensions: &Dimensions) -> f64 {
+        (self.speed + self.acceleration(dimensions)).min(self.cruise.max(self.speed))
+    }
+
+    // The distance the driver likes to keep behind the car in front at the given speed.
+    fn headway(&self, speed: f64) -> f64 {
+        self.profile.behaviour().headway as f64 * speed
+    }
+
+    // The speed the driver actually goes at on this tick, given the one they could go at: once stopped, they take their reaction time to notice that the way ahead has cleared before they move off.
+    fn react(&mut self, speed: f64) -> f64 {
+        if self.speed > 0.0 || speed <= 0.0 {
+            self.hesitation = 0;
+            speed
+        } else if self.hesitation < self.profile.behaviour().reaction {
+            self.hesitation += 1;
+            0.0
+        } else {
+            speed
+        }
+    }
+
+    // Returns true if the new speed means the car has just started braking harder than is comfortable.
+    fn set_speed(&mut self, speed: f64, dimensions: &Dimensions) -> bool {
+        let comfortable = -dimensions.dynamics.comfortable_braking - f64::EPSILON;
+        let was_braking_hard = self.acceleration < comfortable;
+        self.acceleration = speed - self.speed;
+        self.speed = speed;
+        !was_braking_hard && self.acceleration < comfortable
+    }
+
+    // How far the car can go along its route before it would touch another car where that car is now, looking no further ahead than it needs to stop comfortably from its top speed.
+    fn room(
+        &self,
+        prospective_positions: &[(Pose, usize)],
+        paths_through: &[PathThrough],
+        dimensions: &Dimensions,
+        lanes: &[Lane],
+        platoons: &[Option<u64>],
+    ) -> f64 {
+        let horizon = self.horizon(dimensions);
+        let step = dimensions.lane_width as f64 / 8.0;
+
+        let mut ghost = self.clone();
+        let mut travelled = 0.0;
+        let mut outside = self.is_approaching(dimensions);
+        let roundabout = dimensions.layout == Layout::Roundabout;
+        let ahead = self.paths_ahead(paths_through, dimensions, lanes, platoons);
+        let inside = ahead
+            .iter()
+            .filter(|&&(other_index, path)| {
+                path.to_go.is_none()
+                    || path.priority
+                    || lanes[self.index].crosses(&lanes[other_index]) && !roundabout
+            })
+            .copied()
+            .collect::<Vec<(usize, &PathThrough)>>();
+        let crossing = ahead
+            .iter()
+            .flat_map(|(_, path)| &path.poses)
+            .collect::<Vec<&Pose>>();
+        while travelled < horizon {
+            let distance = step.min(horizon - travelled);
+            let pose = ghost.calculate_new_position(distance, dimensions);
+            if ghost.will_collide(&pose, prospective_positions, dimensions, lanes, platoons) {
+                return travelled;
+            }
+            if outside
+                && !roundabout
+                && crossing.iter().any(|other| crowd(&pose, other, dimensions))
+            {
+                return travelled;
             }
-            if *min_time > elapsed {
-                *min_time = elapsed;
+            ghost.place(pose);
+            if outside && ghost.is_in_intersection(dimensions) {
+                outside = false;
+                let stopped = if roundabout {
+                    ghost.would_cut_in(&inside, prospective_positions, dimensions)
+                } else {
+                    let inside = inside
+                        .iter()
+                        .flat_map(|(_, path)| &path.poses)
+                        .collect::<Vec<&Pose>>();
+                    ghost.would_get_stuck(&inside, dimensions)
+                };
+                if stopped {
+                    return travelled;
+                }
+            }
+            travelled += distance;
+        }
+        f64::INFINITY
+    }
+
+    // How far ahead the car looks for anything in its way.
+    fn horizon(&self, dimensions: &Dimensions) -> f64 {
+        let top_speed = self.top_speed(dimensions);
+        top_speed + dimensions.dynamics.stopping_distance(top_speed) + self.headway(top_speed)
+    }
+
+    // How far the car can go along its route, looking as far ahead as it does for other cars, before it would drive onto the rest of the way across of a pedestrian it has to stop for.
+    fn pedestrian_room(&self, zones: &[Zone], dimensions: &Dimensions) -> f64 {
+        let areas = zones
+            .iter()
+            .filter(|zone| self.stops_for(zone, dimensions))
+            .map(|zone| &zone.area)
+            .collect::<Vec<&Rectangle>>();
+        if areas.is_empty() {
+            return f64::INFINITY;
+        }
+        self.distance_to(&areas, self.horizon(dimensions), dimensions)
+    }
+
+    // A car stops for anyone on a crossing ahead of it, and for anyone waiting to cross if it can still stop comfortably before the crossing. If it's already on it, it's too late, and it carries on out of the way. An emergency vehicle doesn't stop for anyone waiting, who in turn waits for it while it's on its way.
+    fn stops_for(&self, zone: &Zone, dimensions: &Dimensions) -> bool {
+        !(collision::overlap(&self.pose().body(dimensions), &zone.area)
+            || zone.waiting && self.must_cross(&zone.area, dimensions))
+    }
+
+    // Whether the car is on the given part of the road, or too close to stop comfortably before it if its route crosses it. An emergency vehicle counts as on its way there from as far off as it looks ahead.
+    fn must_cross(&self, area: &Rectangle, dimensions: &Dimensions) -> bool {
+        let reach = if self.class.spec().emergency {
+            self.horizon(dimensions)
+        } else {
+            self.speed + dimensions.dynamics.stopping_distance(self.speed)
+        };
+        self.distance_to(&[area], reach, dimensions) < f64::INFINITY
+    }
+
+    // How far the car can go along its route, up to the given distance, before its body touches any of the given areas, or infinity if it doesn't.
+    fn distance_to(&self, areas: &[&Rectangle], reach: f64, dimensions: &Dimensions) -> f64 {
+        let touches = |pose: &Pose| {
+            let body = pose.body(dimensions);
+            areas.iter().any(|area| collision::overlap(&body, area))
+        };
+        if touches(&self.pose()) {
+            return 0.0;
+        }
+        let step = dimensions.lane_width as f64 / 8.0;
+        let mut ghost = self.clone();
+        let mut travelled = 0.0;
+        while travelled < reach {
+            let distance = step.min(reach - travelled);
+            let pose = ghost.calculate_new_position(distance, dimensions);
+            if touches(&pose) {
+                return travelled;
             }
+            ghost.place(pose);
+            travelled += distance;
+        }
+        f64::INFINITY
+    }
+
+    // Whether a car that has just entered the intersection would cross the path of any of the cars there now before it got out the other side, or of any emergency vehicle on its way, or of a cyclist it's letting go first. If so, it waits outside until they've gone by: two cars that each stopped halfway across in the other's way would never get out, and an emergency vehicle shouldn't have to slow down for anyone. How close it's prepared to pass them is up to the driver, but never closer than global control lets any two cars get.
+    fn would_get_stuck(&self, inside: &[&Pose], dimensions: &Dimensions) -> bool {
+        let lane_width = dimensions.lane_width as f64;
+        let gap = (self.profile.behaviour().gap * lane_width).max(lane_width / 8.0);
+        !inside.is_empty()
+            && self.path_through(dimensions).iter().any(|pose| {
+                inside.iter().any(|other| {
+                    collision::within(&pose.body(dimensions), &other.body(dimensions), gap)
+                })
+            })
+    }
+
+    // Whether a car that has just reached the ring of a roundabout would cut in on any of the cars already going round it, or on any emergency vehicle on its way: if any of them could get to where it's joining the ring, or crossing the lanes outside its own on the way, before it's clear of there, going at the speed it's going now and then braking comfortably. If so, it waits outside. The cars on the ring don't give way, so it's up to the car joining to find a big enough gap. How big a gap it's happy with, on top of that, is up to the driver, as when crossing paths at a crossroads.
+    fn would_cut_in(
+        &self,
+        inside: &[(usize, &PathThrough)],
+        prospective_positions: &[(Pose, usize)],
+        dimensions: &Dimensions,
+    ) -> bool {
+        let lane_width = dimensions.lane_width as f64;
+        let step = lane_width / 8.0;
+        let joined = self.route(dimensions).joined() + self.length;
+        let mut ghost = self.clone();
+        let mut joining = vec![ghost.pose()];
+        while ghost.travelled < joined {
+            ghost.place(ghost.calculate_new_position(step, dimensions));
+            joining.push(ghost.pose());
+        }
+
+        // How many ticks it would take to get clear, speeding up from its current speed. If it can't get going, it's in everyone's way.
+        let Some(ticks) = types::ticks_to_cover(
+            joined - self.travelled,
+            self.speed,
+            self.acceleration(dimensions),
+            self.cruise,
+        ) else {
             return true;
+        };
+
+        let gap = (self.profile.behaviour().gap * lane_width).max(step);
+        inside.iter().any(|&(other_index, path)| {
+            let Some(&(now, _)) = prospective_positions
+                .iter()
+                .find(|&&(_, index)| index == other_index)
+            else {
+                return false;
+            };
+            let now = now.travelled;
+            let reach = path.speed * ticks as f64
+                + dimensions.dynamics.stopping_distance(path.speed)
+                + step;
+            path.poses
+                .iter()
+                .filter(|pose| pose.travelled <= now + reach)
+                .any(|pose| {
+                    joining.iter().any(|own| {
+                        collision::within(&own.body(dimensions), &pose.body(dimensions), gap)
+                    })
+                })
+        })
+    }
+
+    // The paths of the cars that will go through the intersection before this one, and that it might cross: the ones in it, and the ones closer to it while it's still coming up to it, except that those with priority go before those without, and a car turning right across the bike lane lets a cyclist going straight on in it go first unless they're well behind it. Cars in its own lane are going the same way, and it only has to keep its distance from them. While it's still outside, it doesn't pull up alongside where they're going to be either: a long vehicle turning swings its tail out behind it, and it couldn't finish the turn.
+    fn paths_ahead<'a>(
+        &self,
+        paths_through: &'a [PathThrough],
+        dimensions: &Dimensions,
+        lanes: &[Lane],
+        platoons: &[Option<u64>],
+    ) -> Vec<(usize, &'a PathThrough)> {
+        let to_go = (self.distance_to_center(dimensions), self.birthday);
+        let priority = paths_through[self.index].priority;
+        let yields = paths_through[self.index].yields;
+        let lookback = CYCLIST_LOOKBACK * dimensions.lane_width as f64;
+        paths_through
+            .iter()
+            .enumerate()
+            .filter(|&(other_index, path)| {
+                lanes[other_index] != lanes[self.index]
+                    && (platoons[self.index].is_none()
+                        || platoons[other_index] != platoons[self.index])
+                    && path.to_go.is_none_or(|other| {
+                        if path.priority != priority {
+                            path.priority
+                        } else if path.yields != yields {
+                            yields
+                        } else if lanes[self.index].crosses(&lanes[other_index]) {
+                            other.0 < to_go.0 + lookback
+                        } else if lanes[other_index].crosses(&lanes[self.index]) {
+                            to_go.0 >= other.0 + lookback
+                        } else {
+                            other < to_go
+                        }
+                    })
+            })
+            .collect()
+    }
+
+    // Where the car would be at each step from here until it's out of the intersection.
+    fn path_through(&self, dimensions: &Dimensions) -> Vec<Pose> {
+        let step = dimensions.lane_width as f64 / 8.0;
+        let mut ghost = self.clone();
+        let mut path = vec![ghost.pose()];
+        while ghost.is_in_intersection(dimensions) {
+            let pose = ghost.calculate_new_position(step, dimensions);
+            ghost.place(pose);
+            path.push(pose);
         }
+        path
+    }
+
+    // The fastest the car can go on this tick and still stop comfortably within the given room, short of the given headway. If it's already going too fast for that, it brakes as hard as it can, and if even that isn't enough, it stops dead at the end of the room. Keeping its headway never calls for braking harder than is comfortable, though: only running out of room does.
+    fn safe_speed(&self, room: f64, headway: f64, dimensions: &Dimensions) -> f64 {
+        let dynamics = &dimensions.dynamics;
+        let braking = dynamics.comfortable_braking;
+        // The largest v with v + v * v / (2 * braking) <= room.
+        let stopping_within = |room: f64| braking * ((1.0 + 2.0 * room / braking).sqrt() - 1.0);
+        let comfortable = stopping_within((room - headway).max(0.0))
+            .max(stopping_within(room).min(self.speed - braking));
+        let hardest = (self.speed - dynamics.emergency_braking).max(0.0);
+        self.top_speed(dimensions)
+            .min(comfortable.max(hardest))
+            .min(room)
+    }
 
-        let (new_x, new_y) = self.calculate_new_position(dimensions);
+    fn update(
+        &mut self,
+        prospective_positions: &mut [(Pose, usize)],
+        surroundings: &Surroundings,
+        leader: Option<&Leader>,
+        dimensions: &Dimensions,
+    ) -> Step {
+        let Surroundings {
+            paths_through,
+            lanes,
+            platoons,
+            zones,
+        } = *surroundings;
+        // The car it's following, if any, is left to the car-following model, and the room is for everything else: cars crossing its path, and cars it would otherwise run into.
+        let others = prospective_positions
+            .iter()
+            .filter(|&&(_, index)| leader.is_none_or(|leader| leader.index != index))
+            .copied()
+            .collect::<Vec<(Pose, usize)>>();
+        let room = self.room(&others, paths_through, dimensions, lanes, platoons);
+        let crossing = self.pedestrian_room(zones, dimensions);
+        // Following the car in front isn't giving way: only being held back by anything else is.
+        let following = self.following_speed(leader, dimensions);
+        let headway = self.headway(self.speed);
+        let safe = self.safe_speed(room.min(crossing), headway, dimensions);
+        let yielding = safe < following;
+        let held =
+            crossing < room && safe < following.min(self.safe_speed(room, headway, dimensions));
+        let mut speed = self.react(following.min(safe));
+        let pose = self.calculate_new_position(speed, dimensions);
 
-        if self.will_collide(
-            new_x,
-            new_y,
-            prospective_positions,
-            dimensions,
-            birthdays,
-            colors,
-        ) {
-            return false;
+        // The room is only measured in small steps, so make sure of the final position.
+        let blocked = speed <= 0.0
+            || self.will_collide(&pose, prospective_positions, dimensions, lanes, platoons);
+        if blocked {
+            speed = 0.0;
         }
 
-        prospective_positions[self.index] = (new_x, new_y, self.index);
+        let step = Step {
+            moved: !blocked,
+            yielding,
+            held,
+            hard_braking: self.set_speed(speed, dimensions),
+        };
+        if blocked {
+            return step;
+        }
 
-        self.x = new_x;
-        self.y = new_y;
+        prospective_positions[self.index] = (pose, self.index);
+        self.place(pose);
 
-        return true;
+        step
     }
 
     fn draw(
         &self,
         canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
         dimensions: &Dimensions,
-        car_textures: &[sdl2::render::Texture; 4],
+        car_textures: &[[sdl2::render::Texture; 4]],
+        ticks: u64,
     ) {
-        if self.x < 0
-            || self.x + dimensions.lane_width > dimensions.window_width
-            || self.y < 0
-            || self.y + dimensions.lane_width > dimensions.window_height
-        {
+        if self.has_left(dimensions) {
             return;
         }
 
-        let x = self.x as i32;
-        let y = self.y as i32;
-        let lane_width = dimensions.lane_width as u32;
-
-        let angle = if self.vertical {
-            match self.direction.start {
-                Airt::Up => 0.0,
-                Airt::Down => 180.0,
-                Airt::Left | Airt::Right => {
-                    if self.direction.end == Airt::Up {
-                        0.0
-                    } else {
-                        180.0
-                    }
-                }
-            }
+        // An emergency vehicle's textures are the steps of the flashing of its lights.
+        let texture_code = if self.class.spec().emergency {
+            (ticks / FLASH_TICKS) as usize % 4
         } else {
-            match self.direction.start {
-                Airt::Up | Airt::Down => {
-                    if self.direction.end == Airt::Left {
-                        -90.0
-                    } else {
-                        90.0
-                    }
-                }
-                Airt::Left => -90.0,
-                Airt::Right => 90.0,
-            }
+            self.color_code
         };
+        let car_texture = &car_textures[self.class as usize][texture_code];
+        let query = car_texture.query();
+        let (width, height) = (query.width, query.height);
 
-        let center = sdl2::rect::Point::new(lane_width as i32 / 2, lane_width as i32 / 2);
+        // The texture is centered on the car's square, however long it is.
+        let lane_width = dimensions.lane_width as f64;
+        let x = (self.x + (lane_width - width as f64) / 2.0).round() as i32;
+        let y = (self.y + (lane_width - height as f64) / 2.0).round() as i32;
 
-        let car_texture = &car_textures[self.color_code];
+        let center = sdl2::rect::Point::new(width as i32 / 2, height as i32 / 2);
 
         canvas
             .copy_ex(
                 car_texture,
                 None, // No cropping (draw the whole texture).
-                Some(Rect::new(x, y, lane_width, lane_width)),
-                angle,
+                Some(Rect::new(x, y, width, height)),
+                self.angle,
                 Some(center),
                 false,
                 false,
@@ -398,109 +2414,220 @@ impl Car {
             .expect("Failed to draw car with rotation");
     }
 
-    fn calculate_new_position(&mut self, dimensions: &Dimensions) -> (i32, i32) {
-        let mut new_x = self.x;
-        let mut new_y = self.y;
+    // Returns where a car would be after moving the given distance along its route. On the way to its turn, it drifts across into the middle of its lane if it's still moving over into it, angled the way it's going.
+    fn calculate_new_position(&self, distance: f64, dimensions: &Dimensions) -> Pose {
+        let travelled = self.travelled + distance;
+        let shift =
+            self.shift.signum() * (self.shift.abs() - distance / LANE_CHANGE_LENGTH).max(0.0);
+        let drift = if shift == 0.0 {
+            0.0
+        } else {
+            -shift.signum() / LANE_CHANGE_LENGTH
+        };
+        let ((x, y), (dx, dy)) = self.route(dimensions).at(travelled);
+        Pose {
+            x: x - shift * dy,
+            y: y + shift * dx,
+            angle: angle_of((dx - drift * dy, dy + drift * dx)),
+            travelled,
+            shift,
+            length: self.length,
+            width: self.width,
+        }
+    }
+}
 
-        match self.direction.start {
-            Airt::Up => match self.direction.end {
-                Airt::Left => {
-                    if self.y > dimensions.half_height - dimensions.lane_width {
-                        new_y = self.y - self.speed;
-                    } else {
-                        new_y = dimensions.half_height - dimensions.lane_width;
-                        self.vertical = false;
-                        new_x = self.x - self.speed;
-                    }
-                }
-                Airt::Up => {
-                    new_y = self.y - self.speed;
-                }
-                Airt::Right => {
-                    if self.y > dimensions.half_height + 2 * dimensions.lane_width {
-                        new_y = self.y - self.speed;
-                    } else {
-                        new_y = dimensions.half_height + 2 * dimensions.lane_width;
-                        self.vertical = false;
-                        new_x = self.x + self.speed;
-                    }
-                }
-                _ => panic!("Invalid turn"),
-            },
-            Airt::Down => match self.direction.end {
-                Airt::Left => {
-                    if self.y < dimensions.half_height - 3 * dimensions.lane_width {
-                        new_y = self.y + self.speed;
-                    } else {
-                        new_y = dimensions.half_height - 3 * dimensions.lane_width;
-                        new_x = self.x - self.speed;
-                        self.vertical = false;
-                    }
-                }
-                Airt::Down => {
-                    new_y = self.y + self.speed;
-                }
-                Airt::Right => {
-                    if self.y < dimensions.half_height {
-                        new_y = self.y + self.speed;
-                    } else {
-                        new_y = dimensions.half_height;
-                        new_x = self.x + self.speed;
-                        self.vertical = false;
-                    }
-                }
-                _ => panic!("Invalid turn"),
-            },
-            Airt::Left => match self.direction.end {
-                Airt::Up => {
-                    if self.x > dimensions.half_width + 2 * dimensions.lane_width {
-                        new_x = self.x - self.speed;
-                    } else {
-                        new_x = dimensions.half_width + 2 * dimensions.lane_width;
-                        self.vertical = true;
-                        new_y = self.y - self.speed;
-                    }
-                }
-                Airt::Left => {
-                    new_x = self.x - self.speed;
-                }
-                Airt::Down => {
-                    if self.x > dimensions.half_width - dimensions.lane_width {
-                        new_x = self.x - self.speed;
-                    } else {
-                        new_x = dimensions.half_width - dimensions.lane_width;
-                        self.vertical = true;
-                        new_y = self.y + self.speed;
-                    }
-                }
-                _ => panic!("Invalid turn"),
-            },
-            Airt::Right => match self.direction.end {
-                Airt::Up => {
-                    if self.x < dimensions.half_width {
-                        new_x = self.x + self.speed;
-                    } else {
-                        new_x = dimensions.half_width;
-                        self.vertical = true;
-                        new_y = self.y - self.speed;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_by_the_intelligent_driver_model() {
+        let dimensions = Dimensions::new(800, 800, 32);
+        let mut car = Car::spawn(Airt::Up, Airt::Up, Class::Car, 0, 0, 0, &dimensions);
+        let acceleration = car.acceleration(&dimensions);
+        let emergency = dimensions.dynamics.emergency_braking;
+
+        // On a free road, it speeds up from a standstill to its desired speed, no faster than it can, and no further.
+        car.speed = 0.0;
+        for _ in 0..1000 {
+            let speed = car.following_speed(None, &dimensions);
+            assert!(speed >= car.speed && speed <= car.speed + acceleration + 1e-9);
+            assert!(speed <= car.cruise);
+            car.speed = speed;
+        }
+        assert!(car.cruise - car.speed < 0.01 * car.cruise);
+
+        // Behind a slower car, it settles at that car's speed, at the gap where closing in on it balances wanting to go faster.
+        let ahead = car.cruise / 2.0;
+        let mut gap = 10.0 * dimensions.lane_width as f64;
+        for _ in 0..5000 {
+            let leader = Leader {
+                index: 1,
+                gap,
+                speed: ahead,
+            };
+            let speed = car.following_speed(Some(&leader), &dimensions);
+            assert!(speed >= car.speed - emergency - 1e-9);
+            gap += ahead - speed;
+            car.speed = speed;
+            assert!(gap > 0.0);
+        }
+        let following = &dimensions.following;
+        let settled = (following.min_gap + car.headway(ahead))
+            / (1.0 - (ahead / car.cruise).powf(following.exponent)).sqrt();
+        assert!((car.speed - ahead).abs() < 1e-6);
+        assert!((gap - settled).abs() < 1e-3 * settled);
+
+        // Coming up on a car that's stopped, it stops behind it, about the minimum gap short of it: it works a tick at a time, so it can overshoot a little.
+        let mut gap = 20.0 * dimensions.lane_width as f64;
+        car.speed = car.cruise;
+        for _ in 0..5000 {
+            let leader = Leader {
+                index: 1,
+                gap,
+                speed: 0.0,
+            };
+            let speed = car.following_speed(Some(&leader), &dimensions);
+            assert!(speed >= car.speed - emergency - 1e-9);
+            gap -= speed;
+            car.speed = speed;
+        }
+        assert!(car.speed < 1e-3);
+        assert!((gap - following.min_gap).abs() < dimensions.lane_width as f64 / 16.0);
+    }
+
+    #[test]
+    fn shares_the_lanes_out_between_the_turns() {
+        let mut layouts = vec![Layout::Crossroads, Layout::Roundabout];
+        layouts.extend(Airt::ALL.map(Layout::TJunction));
+        for layout in layouts {
+            for pockets in [false, true] {
+                for lanes in 1..=5 {
+                    let mut dimensions = Dimensions::new(1000, 800, 32);
+                    dimensions.layout = layout;
+                    dimensions.pockets = pockets;
+                    dimensions.u_turns = true;
+                    for road in &mut dimensions.roads {
+                        road.lanes_in = lanes;
                     }
-                }
-                Airt::Right => {
-                    new_x = self.x + self.speed;
-                }
-                Airt::Down => {
-                    if self.x < dimensions.half_width - 3 * dimensions.lane_width {
-                        new_x = self.x + self.speed;
-                    } else {
-                        new_x = dimensions.half_width - 3 * dimensions.lane_width;
-                        self.vertical = true;
-                        new_y = self.y + self.speed;
+                    for start in dimensions.arms().iter().map(Airt::opposite) {
+                        // Each turn, from left to right, takes a run of lanes no further in than the one before it. A U-turn is made from the innermost lane.
+                        let mut turns = dimensions
+                            .movements()
+                            .into_iter()
+                            .filter(|&(s, _)| s == start)
+                            .map(|(s, e)| (turn_index(s, e), lanes_for(s, e, &dimensions)))
+                            .collect::<Vec<(i32, (i32, i32))>>();
+                        turns.sort();
+                        let (u_turn, turns) = turns.split_first().unwrap();
+                        assert_eq!(*u_turn, (-1, (0, 0)));
+                        for pair in turns.windows(2) {
+                            let ((_, (first, last)), (_, (next_first, next_last))) =
+                                (pair[0], pair[1]);
+                            assert!(first <= next_first && last <= next_last);
+                        }
+
+                        // Every lane leads somewhere, and when there are enough lanes for every turn to have its own, no two turns share one, except that two turns without a pocket share the middle one of an odd number of lanes.
+                        let pocket = dimensions.pocket_opening(start.opposite()).is_some();
+                        for lane in 0..lanes {
+                            let ways = turns
+                                .iter()
+                                .filter(|(_, (first, last))| (*first..=*last).contains(&lane))
+                                .count();
+                            assert!(ways >= 1, "{layout:?} lane {lane} of {lanes} leads nowhere");
+                            let shared =
+                                turns.len() == 2 && !pocket && lanes % 2 == 1 && lane == lanes / 2;
+                            if lanes as usize >= turns.len() {
+                                assert_eq!(
+                                    ways,
+                                    1 + shared as usize,
+                                    "{layout:?} lane {lane} of {lanes}"
+                                );
+                            }
+                        }
+                        for &(turn, (first, last)) in turns {
+                            let end = dimensions
+                                .movements()
+                                .into_iter()
+                                .find(|&(s, e)| s == start && turn_index(s, e) == turn)
+                                .unwrap()
+                                .1;
+                            assert!(0 <= first && first <= last && last < lanes);
+                            assert!((first..=last).contains(&home_lane(start, end, &dimensions)));
+                            for lane in first..=last {
+                                assert_eq!(lane_turn(start, lane, end, &dimensions), end);
+                            }
+                        }
                     }
                 }
-                _ => panic!("Invalid turn"),
-            },
+            }
+        }
+    }
+
+    #[test]
+    fn moves_over_into_a_lane_for_its_turn() {
+        let pick = |lane| Pick {
+            class: Class::Car,
+            profile: Profile::Normal,
+            lane,
+            eagerness: 0.5,
+            chance: 0.0,
+        };
+
+        // A car that arrives in the outside lane, wanting to turn left, moves over a lane at a time until it's in the inside lane, before it reaches the intersection.
+        let dimensions = Dimensions::new(1000, 800, 32);
+        let mut traffic = Traffic::seeded(0);
+        traffic.push_pick(Airt::Up, Airt::Left, pick(2), &dimensions);
+        let reach = dimensions.reach(Airt::Down) as f64;
+        while traffic.cars[0].distance_to_center(&dimensions) > reach {
+            traffic.update(&dimensions);
         }
+        let car = &traffic.cars[0];
+        assert_eq!((car.lane, car.direction.end), (0, Airt::Left));
+        assert_eq!((traffic.lane_changes, traffic.missed_lanes), (2, 0));
+
+        // With too short a way in to move over, it gives up, and turns the way the lane it's in takes it.
+        let dimensions = Dimensions::new(1000, 400, 32);
+        let mut traffic = Traffic::seeded(0);
+        traffic.push_pick(Airt::Up, Airt::Left, pick(2), &dimensions);
+        traffic.update(&dimensions);
+        let car = &traffic.cars[0];
+        assert_eq!(
+            (car.lane, car.direction.end, car.destination),
+            (2, Airt::Right, Airt::Right)
+        );
+        assert_eq!((traffic.lane_changes, traffic.missed_lanes), (0, 1));
+    }
 
-        (new_x, new_y)
+    #[test]
+    fn comes_in_only_where_there_is_room() {
+        let dimensions = Dimensions::new(1000, 800, 32);
+        let mut traffic = Traffic::seeded(0);
+        traffic.random_lanes = false;
+        traffic.random_vehicles = false;
+        traffic.push_route(Airt::Up, Airt::Up, &dimensions);
+        let ghost = |lane| {
+            let mut car = Car::spawn(Airt::Up, Airt::Up, Class::Car, 1, 1, 0, &dimensions);
+            car.move_to_lane(lane, false, &dimensions);
+            car
+        };
+
+        // Not right behind a car in the same lane, but beside it in the next one.
+        assert!(!traffic.has_room(&ghost(1), &dimensions));
+        assert!(traffic.has_room(&ghost(0), &dimensions));
+
+        // A car that comes in right behind it is held back, until the car ahead has gone at least the minimum gap further on.
+        traffic.push_route(Airt::Up, Airt::Up, &dimensions);
+        assert_eq!((traffic.cars.len(), traffic.held.len()), (1, 1));
+        assert!(!traffic.has_room(&ghost(1), &dimensions));
+        while !traffic.held.is_empty() {
+            traffic.update(&dimensions);
+        }
+        let (ahead, behind) = (&traffic.cars[0], &traffic.cars[1]);
+        assert!(
+            ahead.travelled - behind.travelled - (ahead.length + behind.length) / 2.0
+                >= dimensions.following.min_gap
+        );
     }
 }
@@ -0,0 +1,690 @@
+// An exhaustive search for gridlocks and crashes. Instead of opening a window, this runs the simulation headless, one tick at a time, for every sequence of spawns up to a given length, with the gaps between spawns drawn from a small set of multiples of a time step. Any sequence that ends in gridlock or a crash is shrunk to a minimal reproducer and reported.
+
+use std::fmt;
+
+use crate::{
+    cars::{TICK, Traffic},
+    drivers::{self, Profile},
+    routes::Route,
+    sim::KEYPRESS_INTERVAL,
+    types::{
+        self, ACCELERATION_EXPONENT, ACCELERATION_TICKS, Airt, BIKE_LANE_WIDTH,
+        COMFORTABLE_BRAKING_TICKS, Control, DesiredSpeed, Dimensions, Dynamics,
+        EMERGENCY_BRAKING_TICKS, Following, Layout, MIN_GAP, Road, SPEED_SPREAD,
+    },
+    vehicles::{self, Class},
+};
+
+const WINDOW_SIZE: i32 = 800;
+const LANE_WIDTH: i32 = 32;
+
+pub struct Settings {
+    pub length: usize, // Maximum number of cars in a sequence.
+    pub step: u64,     // Granularity of the gaps between spawns, in ticks.
+    pub gaps: u64,     // Gaps range from 1 to this many steps.
+    pub patience: u64, // How many ticks of standstill count as gridlock.
+    pub max_ticks: u64,
+    pub platooning: bool,
+    pub control: Control,
+    pub acceleration: u64, // Ticks to get from a standstill to the fast lane speed.
+    pub braking: u64,      // Ticks to stop from the fast lane speed, braking comfortably.
+    pub emergency: u64,    // Likewise, braking as hard as possible.
+    pub spread: u64, // How far desired speeds can be from the lane speed, as a percentage of it.
+    pub min_gap: u64, // The gap a car leaves when it stops behind another, in sixteenths of a lane width.
+    pub exponent: u64, // How sharply a car eases off as it approaches its desired speed.
+    pub mix: Vec<(Class, u32)>, // How often each class of vehicle is spawned, relative to the others.
+    pub profiles: Vec<(Profile, u32)>, // Likewise, each kind of driver.
+    pub pedestrians: u64,       // How many pedestrians turn up per thousand ticks, on average.
+    pub bike_lane: u64, // The width of the bike lanes, in sixteenths of a lane width, or 0 for none.
+    pub roads: [Road; 4], // The road along each arm, in the order of `Airt::ALL`.
+    pub overriding: bool, // Whether the smart controller lets cars through in the order they come, whichever road they're on.
+    pub layout: Layout,
+    pub pockets: bool, // Whether the innermost lanes coming in are turn pockets.
+    pub u_turns: bool, // Whether cars can turn back the way they came.
+    pub random_lanes: bool, // Whether each car arrives in a lane picked at random, rather than the middle one of those for its turn.
+    pub random_vehicles: bool, // Whether each vehicle's class, driver and desired speed are picked at random, by the mixes, rather than it being a car with a normal driver at the lane speed.
+    pub left_hand: bool, // Whether traffic keeps left, in which case the roads and layout, as given, are mirrored for the simulation, and the routes it reports mirrored back.
+}
+
+impl Settings {
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut settings = Settings {
+            length: 3,
+            step: KEYPRESS_INTERVAL.as_millis().div_ceil(TICK.as_millis()) as u64,
+            gaps: 3,
+            patience: 60,
+            max_ticks: 10_000,
+            platooning: true,
+            control: Control::Global,
+            acceleration: ACCELERATION_TICKS,
+            braking: COMFORTABLE_BRAKING_TICKS,
+            emergency: EMERGENCY_BRAKING_TICKS,
+            spread: (SPEED_SPREAD * 100.0).round() as u64,
+            min_gap: MIN_GAP,
+            exponent: ACCELERATION_EXPONENT,
+            mix: vehicles::MIX.to_vec(),
+            profiles: drivers::MIX.to_vec(),
+            pedestrians: 0,
+            bike_lane: BIKE_LANE_WIDTH,
+            roads: [Road::default(); 4],
+            overriding: false,
+            layout: Layout::Crossroads,
+            pockets: false,
+            u_turns: false,
+            random_lanes: false,
+            random_vehicles: false,
+            left_hand: false,
+        };
+
+        for arg in args {
+            let (key, value) = arg
+                .split_once('=')
+                .ok_or_else(|| format!("Expected --option=value, got {}", arg))?;
+            if road_option(key, value, &mut settings.roads)? {
+                continue;
+            }
+            let value = value
+                .parse::<u64>()
+                .map_err(|_| format!("Expected a whole number for {}, got {}", key, value))?;
+            match key {
+                "--length" => settings.length = value as usize,
+                "--step" => settings.step = value,
+                "--gaps" => settings.gaps = value,
+                "--patience" => settings.patience = value,
+                "--max-ticks" => settings.max_ticks = value,
+                "--platoons" => settings.platooning = value != 0,
+                "--local" => {
+                    settings.control = if value == 0 {
+                        Control::Global
+                    } else {
+                        Control::Local
+                    }
+                }
+                "--acceleration" => settings.acceleration = value,
+                "--braking" => settings.braking = value,
+                "--emergency" => settings.emergency = value,
+                "--spread" => settings.spread = value,
+                "--min-gap" => settings.min_gap = value,
+                "--exponent" => settings.exponent = value,
+                "--pedestrians" => settings.pedestrians = value,
+                "--bike-lane" => settings.bike_lane = value,
+                "--override" => settings.overriding = value != 0,
+                "--pockets" => settings.pockets = value != 0,
+                "--u-turns" => settings.u_turns = value != 0,
+                "--left-hand" => settings.left_hand = value != 0,
+                "--random-lanes" => settings.random_lanes = value != 0,
+                "--random-vehicles" => settings.random_vehicles = value != 0,
+                "--roundabout" => {
+                    settings.layout = if value == 0 {
+                        Layout::Crossroads
+                    } else {
+                        Layout::Roundabout
+                    }
+                }
+                "--t-junction" => {
+                    settings.layout = match value {
+                        0 => Layout::Crossroads,
+                        1..=4 => Layout::TJunction(Airt::ALL[value as usize - 1]),
+                        _ => {
+                            return Err(format!("Expected 0 to 4 for --t-junction, got {}", value));
+                        }
+                    }
+                }
+                "--staggered" => {
+                    settings.layout = match value {
+                        0 => Layout::Crossroads,
+                        1 => Layout::Staggered(Airt::Left),
+                        2 => Layout::Staggered(Airt::Right),
+                        _ => {
+                            return Err(format!("Expected 0 to 2 for --staggered, got {}", value));
+                        }
+                    }
+                }
+                _ => {
+                    let Some(weight) = settings
+                        .mix
+                        .iter_mut()
+                        .find(|(class, _)| key == format!("--{}", class.spec().name))
+                        .map(|(_, weight)| weight)
+                        .or_else(|| {
+                            settings
+                                .profiles
+                                .iter_mut()
+                                .find(|(profile, _)| {
+                                    key == format!("--{}", profile.behaviour().name)
+                                })
+                                .map(|(_, weight)| weight)
+                        })
+                    else {
+                        return Err(format!("Unknown option {}", key));
+                    };
+                    *weight = value as u32;
+                    settings.random_vehicles = true; // A mix is only for picking from.
+                }
+            }
+        }
+        if settings.left_hand {
+            settings.roads = types::mirror_roads(settings.roads);
+            settings.layout = settings.layout.mirror();
+        }
+
+        if settings.length == 0
+            || settings.step == 0
+            || settings.gaps == 0
+            || settings.patience == 0
+        {
+            return Err("--length, --step, --gaps and --patience must be at least 1".to_string());
+        }
+        if settings.acceleration == 0 || settings.braking == 0 || settings.emergency == 0 {
+            return Err("--acceleration, --braking and --emergency must be at least 1".to_string());
+        }
+        if settings.exponent == 0 {
+            return Err("--exponent must be at least 1".to_string());
+        }
+        if settings.pedestrians > 1000 {
+            return Err("--pedestrians can't be more than 1000".to_string());
+        }
+        if settings.bike_lane > 16 {
+            return Err("--bike-lane can't be more than 16".to_string());
+        }
+        check_roads(&settings.roads, settings.layout)?;
+        if settings.spread >= 100 {
+            return Err("--spread must be less than 100".to_string());
+        }
+        if settings.emergency > settings.braking {
+            return Err("--emergency can't be more than --braking".to_string());
+        }
+        if settings.mix.iter().all(|&(_, weight)| weight == 0) {
+            return Err("At least one class of vehicle needs a weight above 0".to_string());
+        }
+        if settings.profiles.iter().all(|&(_, weight)| weight == 0) {
+            return Err("At least one kind of driver needs a weight above 0".to_string());
+        }
+
+        Ok(settings)
+    }
+}
+
+// Applies an option for the roads to them, given its value, and returns whether it was one: `--lanes` for the lanes each way along every road, `--lanes-in` and `--lanes-out` for just those coming in or going out, and per-arm options like `--up-lanes`, `--up-lanes-in`, `--up-speed` or `--left-minor`. The window takes these too.
+pub fn road_option(key: &str, value: &str, roads: &mut [Road; 4]) -> Result<bool, String> {
+    let (arms, setting) = match arm_option(key) {
+        Some((arm, setting)) => (vec![arm], setting),
+        None => match key.strip_prefix("--") {
+            Some(setting @ ("lanes" | "lanes-in" | "lanes-out")) => (Airt::ALL.to_vec(), setting),
+            _ => return Ok(false),
+        },
+    };
+    let value = value
+        .parse::<u64>()
+        .map_err(|_| format!("Expected a whole number for {}, got {}", key, value))?;
+    for arm in arms {
+        let road = &mut roads[arm as usize];
+        match setting {
+            "lanes" => {
+                road.lanes_in = value as i32;
+                road.lanes_out = value as i32;
+            }
+            "lanes-in" => road.lanes_in = value as i32,
+            "lanes-out" => road.lanes_out = value as i32,
+            "speed" => road.speed = value as i32,
+            _ => road.minor = value != 0,
+        }
+    }
+    Ok(true)
+}
+
+// Fails unless every road has from 1 to 5 lanes each way and a speed from 25 to 150, and, at a roundabout, the same number of lanes each way as every other road.
+pub fn check_roads(roads: &[Road; 4], layout: Layout) -> Result<(), String> {
+    if roads
+        .iter()
+        .any(|road| !(1..=5).contains(&road.lanes_in) || !(1..=5).contains(&road.lanes_out))
+    {
+        return Err("--lanes and --up-lanes etc. must be from 1 to 5".to_string());
+    }
+    if roads.iter().any(|road| !(25..=150).contains(&road.speed)) {
+        return Err("--up-speed etc. must be from 25 to 150".to_string());
+    }
+    if layout == Layout::Roundabout
+        && roads
+            .iter()
+            .any(|road| road.lanes_in != roads[0].lanes_in || road.lanes_out != roads[0].lanes_in)
+    {
+        return Err(
+            "Every road into a roundabout needs the same number of lanes each way".to_string(),
+        );
+    }
+    Ok(())
+}
+
+// The arm and the setting for it that a per-arm option, like `--up-lanes` or `--left-minor`, is for. Other options that start with the name of an arm, like `--left-hand`, aren't per-arm options.
+fn arm_option(key: &str) -> Option<(Airt, &str)> {
+    let (arm, setting) = key
+        .strip_prefix("--")?
+        .split_once('-')
+        .filter(|(_, setting)| {
+            ["lanes", "lanes-in", "lanes-out", "speed", "minor"].contains(setting)
+        })?;
+    let arm = Airt::ALL
+        .into_iter()
+        .find(|candidate| format!("{:?}", candidate).to_lowercase() == arm)?;
+    Some((arm, setting))
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Spawn {
+    pub start: Airt,
+    pub end: Airt,
+    pub gap: u64, // Ticks since the previous spawn.
+}
+
+impl fmt::Display for Spawn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "+{} ticks: {:?} -> {:?}", self.gap, self.start, self.end)
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Outcome {
+    Clear,
+    Gridlock { tick: u64 },
+    Crash { tick: u64 },
+    Timeout { tick: u64 }, // Still going at `max_ticks`, without having stalled for long enough to count as gridlock.
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Clear => write!(f, "clear"),
+            Outcome::Gridlock { tick } => write!(f, "gridlock at tick {}", tick),
+            Outcome::Crash { tick } => write!(f, "crash at tick {}", tick),
+            Outcome::Timeout { tick } => write!(f, "still going at tick {}", tick),
+        }
+    }
+}
+
+pub struct Finding {
+    pub sequence: Vec<Spawn>,
+    pub outcome: Outcome,
+}
+
+// The state of one headless run, cheap enough to clone so that sequences sharing a prefix can share its simulation.
+#[derive(Clone)]
+struct Run {
+    traffic: Traffic,
+    stalled: u64,
+    outcome: Option<Outcome>,
+}
+
+impl Run {
+    fn new(settings: &Settings) -> Self {
+        Run {
+            traffic: traffic(settings),
+            stalled: 0,
+            outcome: None,
+        }
+    }
+
+    fn check_crashes(&mut self, dimensions: &Dimensions) {
+        if !self.traffic.collisions(dimensions).is_empty() || self.traffic.pedestrians_hit > 0 {
+            self.outcome = Some(Outcome::Crash {
+                tick: self.traffic.ticks,
+            });
+        }
+    }
+
+    fn wait(&mut self, ticks: u64, dimensions: &Dimensions, settings: &Settings) {
+        for _ in 0..ticks {
+            if self.outcome.is_some() {
+                return;
+            }
+
+            if self.traffic.update(dimensions) || self.traffic.cars.is_empty() {
+                self.stalled = 0;
+            } else {
+                self.stalled += 1;
+                if self.stalled >= settings.patience {
+                    self.outcome = Some(Outcome::Gridlock {
+                        tick: self.traffic.ticks,
+                    });
+                }
+            }
+
+            self.check_crashes(dimensions);
+        }
+    }
+
+    fn spawn(&mut self, start: Airt, end: Airt, dimensions: &Dimensions) {
+        if self.outcome.is_some() {
+            return;
+        }
+        self.traffic.push_route(start, end, dimensions);
+        self.stalled = 0;
+        self.check_crashes(dimensions);
+    }
+
+    fn finish(mut self, dimensions: &Dimensions, settings: &Settings) -> Outcome {
+        while self.outcome.is_none() && !self.traffic.is_empty() {
+            if self.traffic.ticks >= settings.max_ticks {
+                return Outcome::Timeout {
+                    tick: self.traffic.ticks,
+                };
+            }
+            self.wait(1, dimensions, settings);
+        }
+        self.outcome.unwrap_or(Outcome::Clear)
+    }
+}
+
+// Headless traffic with the given settings, always with the same seed, so a run can be repeated exactly.
+pub fn traffic(settings: &Settings) -> Traffic {
+    let mut traffic = Traffic::seeded(0);
+    traffic.platooning = settings.platooning;
+    traffic.control = settings.control;
+    traffic.mix = settings.mix.clone();
+    traffic.profiles = settings.profiles.clone();
+    traffic.pedestrian_rate = settings.pedestrians as f64 / 1000.0;
+    traffic.overriding = settings.overriding;
+    traffic.random_lanes = settings.random_lanes;
+    traffic.random_vehicles = settings.random_vehicles;
+    traffic.left_hand = settings.left_hand;
+    traffic
+}
+
+pub fn dimensions(settings: &Settings) -> Dimensions {
+    let mut dimensions = Dimensions::new(WINDOW_SIZE, WINDOW_SIZE, LANE_WIDTH);
+    dimensions.dynamics = Dynamics::new(
+        dimensions.speed.fast,
+        settings.acceleration,
+        settings.braking,
+        settings.emergency,
+    );
+    dimensions.desired_speeds =
+        DesiredSpeed::table(&dimensions.speed, settings.spread as f64 / 100.0);
+    dimensions.following = Following::new(LANE_WIDTH, settings.min_gap, settings.exponent);
+    dimensions.bike_lane = LANE_WIDTH * settings.bike_lane as i32 / 16;
+    dimensions.roads = settings.roads;
+    dimensions.layout = settings.layout;
+    dimensions.pockets = settings.pockets;
+    dimensions.u_turns = settings.u_turns;
+    dimensions.routes = Route::table(&dimensions);
+    dimensions
+}
+
+pub fn run(sequence: &[Spawn], dimensions: &Dimensions, settings: &Settings) -> Outcome {
+    let mut run = Run::new(settings);
+    for spawn in sequence {
+        run.wait(spawn.gap, dimensions, settings);
+        run.spawn(spawn.start, spawn.end, dimensions);
+    }
+    run.finish(dimensions, settings)
+}
+
+// Returns the number of sequences tried and the minimal reproducers of every failure found, without duplicates.
+pub fn explore(dimensions: &Dimensions, settings: &Settings) -> (u64, Vec<Finding>) {
+    let mut tried = 0;
+    let mut findings = Vec::new();
+    search(
+        &Run::new(settings),
+        &mut Vec::new(),
+        dimensions,
+        settings,
+        &mut tried,
+        &mut findings,
+    );
+    (tried, findings)
+}
+
+// Depth-first, so each sequence is only extended once it's known to clear. A failing sequence therefore never contains a shorter failing prefix. One that's still going at `max_ticks` isn't shrunk, since it may just need longer, and it's reported as it is.
+fn search(
+    run: &Run,
+    sequence: &mut Vec<Spawn>,
+    dimensions: &Dimensions,
+    settings: &Settings,
+    tried: &mut u64,
+    findings: &mut Vec<Finding>,
+) {
+    if sequence.len() >= settings.length {
+        return;
+    }
+
+    let gaps = if sequence.is_empty() {
+        vec![0]
+    } else {
+        (1..=settings.gaps).map(|k| k * settings.step).collect()
+    };
+
+    for gap in gaps {
+        let mut waited = run.clone();
+        waited.wait(gap, dimensions, settings);
+
+        for (start, end) in dimensions.movements() {
+            let mut next = waited.clone();
+            next.spawn(start, end, dimensions);
+            sequence.push(Spawn { start, end, gap });
+            *tried += 1;
+
+            match next.clone().finish(dimensions, settings) {
+                Outcome::Clear => {
+                    search(&next, sequence, dimensions, settings, tried, findings);
+                }
+                outcome @ Outcome::Timeout { .. } => findings.push(Finding {
+                    sequence: sequence.clone(),
+                    outcome,
+                }),
+                _ => {
+                    let (minimal, outcome) = shrink(sequence.clone(), settings, |sequence| {
+                        self::run(sequence, dimensions, settings)
+                    });
+                    if !findings.iter().any(|finding| finding.sequence == minimal) {
+                        findings.push(Finding {
+                            sequence: minimal,
+                            outcome,
+                        });
+                    }
+                }
+            }
+
+            sequence.pop();
+        }
+    }
+}
+
+// Greedily removes spawns and shortens gaps for as long as the sequence still ends in gridlock or a crash, given how a sequence plays out.
+fn shrink(
+    mut sequence: Vec<Spawn>,
+    settings: &Settings,
+    run: impl Fn(&[Spawn]) -> Outcome,
+) -> (Vec<Spawn>, Outcome) {
+    let mut outcome = run(&sequence);
+
+    'shrinking: loop {
+        for candidate in candidates(&sequence, settings) {
+            let candidate_outcome = run(&candidate);
+            if matches!(
+                candidate_outcome,
+                Outcome::Gridlock { .. } | Outcome::Crash { .. }
+            ) {
+                sequence = candidate;
+                outcome = candidate_outcome;
+                continue 'shrinking;
+            }
+        }
+        return (sequence, outcome);
+    }
+}
+
+fn candidates(sequence: &[Spawn], settings: &Settings) -> Vec<Vec<Spawn>> {
+    let mut candidates = Vec::new();
+
+    for i in 0..sequence.len() {
+        let mut shorter = sequence.to_vec();
+        let removed = shorter.remove(i);
+        if let Some(next) = shorter.get_mut(i) {
+            next.gap = if i == 0 { 0 } else { next.gap + removed.gap };
+        }
+        candidates.push(shorter);
+    }
+
+    for i in 1..sequence.len() {
+        if sequence[i].gap > settings.step {
+            let mut quicker = sequence.to_vec();
+            quicker[i].gap -= settings.step;
+            candidates.push(quicker);
+        }
+    }
+
+    candidates
+}
+
+// Runs the search from the command line arguments that follow `--check`. Returns false if any gridlock or crash was found, or any sequence was still going at `max_ticks`.
+pub fn check(args: &[String]) -> Result<bool, String> {
+    let settings = Settings::from_args(args)?;
+    let dimensions = dimensions(&settings);
+    let (tried, findings) = explore(&dimensions, &settings);
+
+    println!(
+        "Tried {} spawn sequences of up to {} cars, with gaps of 1 to {} steps of {} ticks.",
+        tried, settings.length, settings.gaps, settings.step
+    );
+    if settings.random_lanes || settings.random_vehicles {
+        println!(
+            "Lanes or vehicles were picked at random, from the same seed for each sequence, so a sequence plays out the same way every time, but one that differs only by a car or a gap may not pick the same for the cars they share."
+        );
+    }
+
+    if findings.is_empty() {
+        println!("No gridlocks or crashes found.");
+        return Ok(true);
+    }
+
+    let (timeouts, failures): (Vec<&Finding>, Vec<&Finding>) = findings
+        .iter()
+        .partition(|finding| matches!(finding.outcome, Outcome::Timeout { .. }));
+    if failures.is_empty() {
+        println!("No gridlocks or crashes found.");
+    }
+    for finding in failures {
+        print_finding(finding, &settings);
+    }
+    if !timeouts.is_empty() {
+        println!(
+            "\n{} sequences were still going at --max-ticks={}, without standing still for long enough to count as gridlock. They may just need longer, so they're given as they were tried, not shrunk.",
+            timeouts.len(),
+            settings.max_ticks
+        );
+    }
+    for finding in timeouts {
+        print_finding(finding, &settings);
+    }
+
+    Ok(false)
+}
+
+// What happened, and the spawns that led to it, with the ways they came from and went mirrored back for left-hand traffic.
+fn print_finding(finding: &Finding, settings: &Settings) {
+    println!("\n{}:", finding.outcome);
+    for spawn in &finding.sequence {
+        if settings.left_hand {
+            let (start, end) = (spawn.start.mirror(), spawn.end.mirror());
+            println!(
+                "  {}",
+                Spawn {
+                    start,
+                    end,
+                    ..*spawn
+                }
+            );
+        } else {
+            println!("  {}", spawn);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(args: &[&str]) -> Settings {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        Settings::from_args(&args).unwrap()
+    }
+
+    #[test]
+    fn rejects_a_length_of_zero() {
+        let e = Settings::from_args(&["--length=0".to_string()])
+            .err()
+            .unwrap();
+        assert_eq!(
+            e,
+            "--length, --step, --gaps and --patience must be at least 1"
+        );
+    }
+
+    #[test]
+    fn tries_every_sequence() {
+        // Six movements at a T-junction, each followed by each of them after each of the gaps.
+        for (length, gaps, expected) in [(1, 3, 6), (2, 1, 6 + 6 * 6), (2, 2, 6 + 6 * 2 * 6)] {
+            let settings = settings(&[
+                "--t-junction=1",
+                &format!("--length={}", length),
+                &format!("--gaps={}", gaps),
+            ]);
+            let (tried, findings) = explore(&dimensions(&settings), &settings);
+            assert_eq!(tried, expected, "length {}, gaps {}", length, gaps);
+            assert!(findings.is_empty());
+        }
+    }
+
+    #[test]
+    fn shrinks_to_a_minimal_failing_sequence() {
+        let settings = settings(&[]);
+        let step = settings.step;
+        let spawn = |start: Airt, end: Airt, steps: u64| Spawn {
+            start,
+            end,
+            gap: steps * step,
+        };
+        // It fails if a car goes up and then, within two steps, another goes right, whatever else is sent.
+        let fails = |sequence: &[Spawn]| {
+            let mut since = None;
+            for spawn in sequence {
+                since = since.map(|ticks| ticks + spawn.gap);
+                if spawn.end == Airt::Right && since.is_some_and(|ticks| ticks <= 2 * step) {
+                    return Outcome::Gridlock { tick: 0 };
+                }
+                if spawn.end == Airt::Up {
+                    since = Some(0);
+                }
+            }
+            Outcome::Clear
+        };
+        let sequence = vec![
+            spawn(Airt::Down, Airt::Down, 0),
+            spawn(Airt::Up, Airt::Up, 3),
+            spawn(Airt::Left, Airt::Left, 1),
+            spawn(Airt::Right, Airt::Right, 1),
+        ];
+        let (minimal, outcome) = shrink(sequence, &settings, fails);
+        assert_eq!(
+            minimal,
+            [
+                spawn(Airt::Up, Airt::Up, 0),
+                spawn(Airt::Right, Airt::Right, 1)
+            ]
+        );
+        assert_eq!(outcome, Outcome::Gridlock { tick: 0 });
+    }
+
+    #[test]
+    fn reports_a_timeout_without_shrinking_or_extending_it() {
+        let settings = settings(&["--t-junction=1", "--length=2", "--max-ticks=1"]);
+        let dimensions = dimensions(&settings);
+        let (tried, findings) = explore(&dimensions, &settings);
+        assert_eq!(tried, 6);
+        assert_eq!(findings.len(), 6);
+        for finding in findings {
+            assert_eq!(finding.sequence.len(), 1);
+            assert!(matches!(finding.outcome, Outcome::Timeout { .. }));
+        }
+    }
+}
@@ -0,0 +1,155 @@
+// Collision geometry. A vehicle's body is a rectangle turned to face the way it's going, and two of them overlap unless there's a gap between them along one of their four edge directions: the separating axis test.
+
+#[derive(Clone, Copy)]
+pub struct Rectangle {
+    pub center: (f64, f64),
+    pub heading: (f64, f64), // Unit vector along its length.
+    pub half_length: f64,
+    pub half_width: f64,
+}
+
+impl Rectangle {
+    // The same rectangle with the given margin added all round.
+    pub fn grown(&self, margin: f64) -> Rectangle {
+        Rectangle {
+            half_length: self.half_length + margin,
+            half_width: self.half_width + margin,
+            ..*self
+        }
+    }
+
+    pub fn corners(&self) -> [(f64, f64); 4] {
+        let [along, across] = self.axes();
+        [(1.0, 1.0), (1.0, -1.0), (-1.0, -1.0), (-1.0, 1.0)].map(|(a, b)| {
+            (
+                self.center.0 + a * self.half_length * along.0 + b * self.half_width * across.0,
+                self.center.1 + a * self.half_length * along.1 + b * self.half_width * across.1,
+            )
+        })
+    }
+
+    fn axes(&self) -> [(f64, f64); 2] {
+        let (x, y) = self.heading;
+        [(x, y), (-y, x)]
+    }
+
+    // Half the length of the shadow the rectangle casts on the given axis.
+    fn radius(&self, axis: (f64, f64)) -> f64 {
+        let [along, across] = self.axes();
+        self.half_length * dot(along, axis).abs() + self.half_width * dot(across, axis).abs()
+    }
+}
+
+// Whether two rectangles overlap. Rectangles that only touch along an edge don't, less a hair, so that rounding errors in the trigonometry don't make cars side by side in neighbouring lanes collide.
+pub fn overlap(a: &Rectangle, b: &Rectangle) -> bool {
+    let offset = (b.center.0 - a.center.0, b.center.1 - a.center.1);
+    a.axes()
+        .into_iter()
+        .chain(b.axes())
+        .all(|axis| dot(offset, axis).abs() < a.radius(axis) + b.radius(axis) - 1e-9)
+}
+
+// Whether two rectangles come within the given distance of each other. Measured at the corners, it's a little more than the distance, as if they had square rather than rounded corners.
+pub fn within(a: &Rectangle, b: &Rectangle, distance: f64) -> bool {
+    overlap(&a.grown(distance / 2.0), &b.grown(distance / 2.0))
+}
+
+fn dot(a: (f64, f64), b: (f64, f64)) -> f64 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(center: (f64, f64), heading: (f64, f64)) -> Rectangle {
+        Rectangle {
+            center,
+            heading,
+            half_length: 1.0,
+            half_width: 1.0,
+        }
+    }
+
+    fn car(center: (f64, f64), heading: (f64, f64)) -> Rectangle {
+        Rectangle {
+            center,
+            heading,
+            half_length: 2.0,
+            half_width: 1.0,
+        }
+    }
+
+    const EAST: (f64, f64) = (1.0, 0.0);
+
+    #[test]
+    fn overlaps_only_with_no_gap() {
+        let a = car((0.0, 0.0), EAST);
+        assert!(!overlap(&a, &car((10.0, 0.0), EAST)));
+        assert!(!overlap(&a, &car((0.0, 5.0), EAST)));
+        // Nose to tail and side by side, touching but not overlapping.
+        assert!(!overlap(&a, &car((4.0, 0.0), EAST)));
+        assert!(!overlap(&a, &car((0.0, 2.0), EAST)));
+        // The same, a little closer.
+        assert!(overlap(&a, &car((3.999, 0.0), EAST)));
+        assert!(overlap(&a, &car((0.0, 1.999), EAST)));
+        // One inside the other, and one on top of the other.
+        assert!(overlap(&a, &square((0.5, 0.0), EAST)));
+        assert!(overlap(&a, &a));
+    }
+
+    #[test]
+    fn overlaps_either_way_round() {
+        let a = car((0.0, 0.0), EAST);
+        let b = car((2.5, 2.0), (0.0, 1.0));
+        assert_eq!(overlap(&a, &b), overlap(&b, &a));
+        // Crossing at right angles, with the nose of one just short of the side of the other.
+        assert!(!overlap(&a, &car((0.0, 3.0), (0.0, -1.0))));
+        assert!(overlap(&a, &car((0.0, 2.9), (0.0, -1.0))));
+    }
+
+    #[test]
+    fn finds_a_gap_along_a_turned_edge() {
+        let a = square((0.0, 0.0), EAST);
+        let diagonal = (0.5f64.sqrt(), 0.5f64.sqrt());
+        // Turned 45°, corner on, its bounding box overlaps the other square, but there's a gap along its own edges.
+        let b = square((2.2, 2.2), diagonal);
+        let corner = b
+            .corners()
+            .map(|(x, _)| x)
+            .into_iter()
+            .fold(f64::MAX, f64::min);
+        assert!(corner < 1.0);
+        assert!(!overlap(&a, &b));
+        assert!(!overlap(&b, &a));
+        assert!(overlap(&a, &square((1.6, 1.6), diagonal)));
+        // Corner on to an edge, touching at the tip.
+        assert!(!overlap(&a, &square((1.0 + 2f64.sqrt(), 0.0), diagonal)));
+        assert!(overlap(&a, &square((0.99 + 2f64.sqrt(), 0.0), diagonal)));
+    }
+
+    #[test]
+    fn within_a_distance() {
+        let a = car((0.0, 0.0), EAST);
+        let b = car((4.5, 0.0), EAST);
+        assert!(!overlap(&a, &b));
+        assert!(within(&a, &b, 0.6));
+        assert!(!within(&a, &b, 0.4));
+        assert!(within(&a, &a, 0.0));
+    }
+
+    #[test]
+    fn has_its_corners_where_it_faces() {
+        let corners = car((1.0, 1.0), (0.0, 1.0)).corners();
+        for corner in [(0.0, 3.0), (2.0, 3.0), (2.0, -1.0), (0.0, -1.0)] {
+            assert!(
+                corners
+                    .iter()
+                    .any(|&(x, y)| (x - corner.0).abs() < 1e-9 && (y - corner.1).abs() < 1e-9),
+                "{:?} isn't among {:?}",
+                corner,
+                corners
+            );
+        }
+    }
+}
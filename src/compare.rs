@@ -0,0 +1,93 @@
+// A headless comparison of the crossroads and the roundabout under the same demand: the same cars, on the same routes, at the same times, from the same seed, with everything else as set by the checker's options. Each layout is run until its cars have all passed through, or it runs out of ticks, and their stats are printed side by side.
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::{
+    cars::{self, Pick, TICK, Traffic},
+    checker::{self, Settings},
+    types::{Airt, Layout},
+};
+
+// Runs the comparison from the command line arguments that follow `--compare`. `--count=N` is how many cars to send through, one every `--step` ticks, and `--seed=N` picks their routes and the vehicles that take them.
+pub fn compare(args: &[String]) -> Result<(), String> {
+    let mut count = 100;
+    let mut seed = 0;
+    let mut rest = Vec::new();
+    for arg in args {
+        if let Some(value) = arg.strip_prefix("--count=") {
+            count = value
+                .parse::<usize>()
+                .map_err(|_| format!("Expected a whole number for --count, got {}", value))?;
+        } else if let Some(value) = arg.strip_prefix("--seed=") {
+            seed = value
+                .parse::<u64>()
+                .map_err(|_| format!("Expected a whole number for --seed, got {}", value))?;
+        } else {
+            rest.push(arg.clone());
+        }
+    }
+    let mut settings = Settings::from_args(&rest)?;
+    checker::check_roads(&settings.roads, Layout::Roundabout)?;
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    // Both layouts have all four arms, and U-turns if they're allowed. Every vehicle is picked in advance, class, driver and all, so that both get exactly the same ones. Each arrives in a lane for its turn, picked afresh for each layout, whose lanes for a turn can differ, so that it takes the route it was given through both, rather than missing its lane in one and not the other, and if the lane is taken when it arrives, it waits for it.
+    settings.layout = Layout::Crossroads;
+    let dimensions = checker::dimensions(&settings);
+    let movements = dimensions.movements();
+    let mut picker = Traffic::seeded(seed);
+    picker.mix = settings.mix.clone();
+    picker.profiles = settings.profiles.clone();
+    picker.random_lanes = false;
+    let demand = (0..count)
+        .map(|_| {
+            let (start, end) = movements[rng.random_range(0..movements.len())];
+            (start, end, picker.pick(start, end, &dimensions))
+        })
+        .collect::<Vec<(Airt, Airt, Pick)>>();
+
+    let [crossroads, roundabout] = [Layout::Crossroads, Layout::Roundabout].map(|layout| {
+        settings.layout = layout;
+        let traffic = run(&demand, &settings);
+        format!(
+            "{}\nTime to clear: {:.2}s\nLeft on the road: {}",
+            traffic.format(),
+            (TICK * traffic.ticks as u32).as_secs_f64(),
+            traffic.cars.len() + traffic.held()
+        )
+    });
+
+    println!(
+        "{} cars on random routes, one every {} ticks, seed {}.\n",
+        count, settings.step, seed
+    );
+    let width = crossroads.lines().map(str::len).max().unwrap_or(0);
+    println!("{:<width$}    Roundabout", "Crossroads");
+    let mut left = crossroads.lines();
+    let mut right = roundabout.lines();
+    loop {
+        match (left.next(), right.next()) {
+            (None, None) => break,
+            (left, right) => println!("{:<width$}    {}", left.unwrap_or(""), right.unwrap_or("")),
+        }
+    }
+    Ok(())
+}
+
+fn run(demand: &[(Airt, Airt, Pick)], settings: &Settings) -> Traffic {
+    let dimensions = checker::dimensions(settings);
+    let mut traffic = checker::traffic(settings);
+    for &(start, end, pick) in demand {
+        let pick = Pick {
+            lane: cars::home_lane(start, end, &dimensions),
+            ..pick
+        };
+        traffic.push_pick(start, end, pick, &dimensions);
+        for _ in 0..settings.step {
+            traffic.update(&dimensions);
+        }
+    }
+    while !traffic.is_empty() && traffic.ticks < settings.max_ticks {
+        traffic.update(&dimensions);
+    }
+    traffic
+}
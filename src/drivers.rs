@@ -0,0 +1,53 @@
+// The kinds of driver behind the wheel. Times are in ticks and distances in lane widths.
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Profile {
+    Cautious,
+    Normal,
+    Aggressive,
+}
+
+pub struct Behaviour {
+    pub name: &'static str, // As in the stats and the checker's options.
+    pub reaction: u64,      // How long the driver takes to move off once the way ahead clears.
+    pub headway: u64, // The gap they leave behind the car in front, as the time it takes them to cover it.
+    pub gap: f64,     // The clearance they want from other cars before they'll cross their paths.
+    pub compliance: f64, // The chance that they give way when they should.
+}
+
+// How often each profile is assigned, relative to the others.
+pub const MIX: [(Profile, u32); 3] = [
+    (Profile::Cautious, 20),
+    (Profile::Normal, 60),
+    (Profile::Aggressive, 20),
+];
+
+impl Profile {
+    pub const ALL: [Profile; 3] = [Profile::Cautious, Profile::Normal, Profile::Aggressive];
+
+    pub fn behaviour(&self) -> Behaviour {
+        match self {
+            Profile::Cautious => Behaviour {
+                name: "cautious",
+                reaction: 15,
+                headway: 8,
+                gap: 0.5,
+                compliance: 1.0,
+            },
+            Profile::Normal => Behaviour {
+                name: "normal",
+                reaction: 8,
+                headway: 4,
+                gap: 0.25,
+                compliance: 1.0,
+            },
+            Profile::Aggressive => Behaviour {
+                name: "aggressive",
+                reaction: 4,
+                headway: 1,
+                gap: 0.125,
+                compliance: 0.9,
+            },
+        }
+    }
+}
@@ -0,0 +1,208 @@
+// Just enough JSON to read a map: serde_json does the parsing, into a tree of values that keeps the keys of each object in the order they were written and rejects any written twice, with errors that say where in the text it went wrong.
+
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>), // In the order the keys were written.
+}
+
+impl Json {
+    // What kind of value it is, for error messages.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Json::Null => "null",
+            Json::Bool(_) => "true or false",
+            Json::Number(_) => "a number",
+            Json::String(_) => "a string",
+            Json::Array(_) => "a list",
+            Json::Object(_) => "an object",
+        }
+    }
+}
+
+pub fn parse(text: &str) -> Result<Json, String> {
+    parse_at(text, 1)
+}
+
+// Parses text that starts on the given line of a file, as each line of a file of JSON lines does, so that errors say where in the file it went wrong.
+pub fn parse_at(text: &str, line: usize) -> Result<Json, String> {
+    serde_json::from_str(text).map_err(|e| {
+        // serde_json ends the message with where it went wrong, counting from the start of the text.
+        let message = e.to_string();
+        let position = format!(" at line {} column {}", e.line(), e.column());
+        format!(
+            "Invalid JSON at line {}, column {}: {}",
+            e.line() + line - 1,
+            e.column(),
+            message.strip_suffix(&position).unwrap_or(&message)
+        )
+    })
+}
+
+impl<'de> Deserialize<'de> for Json {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Json, D::Error> {
+        deserializer.deserialize_any(JsonVisitor)
+    }
+}
+
+struct JsonVisitor;
+
+impl<'de> Visitor<'de> for JsonVisitor {
+    type Value = Json;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON value")
+    }
+
+    fn visit_unit<E>(self) -> Result<Json, E> {
+        Ok(Json::Null)
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<Json, E> {
+        Ok(Json::Bool(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Json, E> {
+        Ok(Json::Number(value as f64))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Json, E> {
+        Ok(Json::Number(value as f64))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Json, E> {
+        Ok(Json::Number(value))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Json, E> {
+        Ok(Json::String(value.to_string()))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<Json, E> {
+        Ok(Json::String(value))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Json, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Json::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Json, A::Error> {
+        let mut fields: Vec<(String, Json)> = Vec::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if fields.iter().any(|(other, _)| *other == key) {
+                return Err(de::Error::custom(format!("duplicate key \"{}\"", key)));
+            }
+            fields.push((key, map.next_value()?));
+        }
+        Ok(Json::Object(fields))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_kind_of_value() {
+        let value = parse(r#"{"b": [1, -2.5e2, true, null], "a": "x\"\u00e9\n"}"#).unwrap();
+        assert_eq!(
+            value,
+            Json::Object(vec![
+                (
+                    "b".to_string(),
+                    Json::Array(vec![
+                        Json::Number(1.0),
+                        Json::Number(-250.0),
+                        Json::Bool(true),
+                        Json::Null,
+                    ])
+                ),
+                ("a".to_string(), Json::String("x\"é\n".to_string())),
+            ])
+        );
+        assert_eq!(parse(" [ ] ").unwrap(), Json::Array(Vec::new()));
+        assert_eq!(parse("{}").unwrap(), Json::Object(Vec::new()));
+    }
+
+    #[test]
+    fn keeps_the_order_of_keys() {
+        let value = parse(r#"{"z": 1, "a": 2, "m": 3}"#).unwrap();
+        let Json::Object(pairs) = value else {
+            panic!("{:?} isn't an object", value);
+        };
+        let keys: Vec<&str> = pairs.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, ["z", "a", "m"]);
+    }
+
+    #[test]
+    fn says_where_it_went_wrong() {
+        assert_eq!(
+            parse("{\n  \"a\": tru,\n}").unwrap_err(),
+            "Invalid JSON at line 2, column 11: expected ident"
+        );
+        assert_eq!(
+            parse_at("[1 2]", 7).unwrap_err(),
+            "Invalid JSON at line 7, column 4: expected `,` or `]`"
+        );
+        assert_eq!(
+            parse("[1] 2").unwrap_err(),
+            "Invalid JSON at line 1, column 5: trailing characters"
+        );
+        assert_eq!(
+            parse("{\"a\": 1,\n \"a\": 2}").unwrap_err(),
+            "Invalid JSON at line 2, column 4: duplicate key \"a\""
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_text() {
+        let deep = "[".repeat(1000) + &"]".repeat(1000);
+        for (text, error) in [
+            ("", "EOF while parsing a value"),
+            ("[1,", "EOF while parsing a value"),
+            ("[1,]", "trailing comma"),
+            ("{\"a\": 1,}", "trailing comma"),
+            ("{\"a\" 1}", "expected `:`"),
+            ("{\"a\": 1 \"b\": 2}", "expected `,` or `}`"),
+            ("{a: 1}", "key must be a string"),
+            ("\"abc", "EOF while parsing a string"),
+            ("\"\\q\"", "invalid escape"),
+            ("\"\\u12zz\"", "invalid escape"),
+            ("\"\\ud800\"", "unexpected end of hex escape"),
+            (
+                "\"a\tb\"",
+                "control character (\\u0000-\\u001F) found while parsing a string",
+            ),
+            ("-", "EOF while parsing a value"),
+            ("1.2.3", "trailing characters"),
+            ("NaN", "expected value"),
+            ("// comment\n[1]", "expected value"),
+            ("nul]", "expected ident"),
+            (&deep, "recursion limit exceeded"),
+        ] {
+            let e = parse(text).unwrap_err();
+            assert!(e.ends_with(error), "{:?} gave {:?}", text, e);
+        }
+    }
+
+    #[test]
+    fn reads_numbers_past_the_range_of_integers() {
+        assert_eq!(
+            parse("123456789012345678901234567890").unwrap(),
+            Json::Number(1.2345678901234568e29)
+        );
+        assert_eq!(parse("-0").unwrap(), Json::Number(0.0));
+        assert!(parse("1e400").unwrap_err().ends_with("number out of range"));
+    }
+}
@@ -1,10 +1,14 @@
 use sdl2::{
     pixels::{Color, PixelFormatEnum},
+    rect::Rect,
     render::{BlendMode, Canvas, Texture, TextureCreator},
     video::{Window, WindowContext},
 };
 
-use crate::types::Dimensions;
+use crate::{
+    pedestrians,
+    types::{Airt, Dimensions, Layout},
+};
 
 pub fn draw<'a>(
     canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
@@ -24,239 +28,404 @@ pub fn draw<'a>(
     canvas
         .with_texture_canvas(&mut texture, |texture_canvas| {
             texture_canvas.set_draw_color(Color::RGB(64, 64, 64));
-            texture_canvas
-                .fill_rect(sdl2::rect::Rect::new(
-                    dimensions.half_width - 3 * dimensions.lane_width,
-                    0,
-                    6 * dimensions.lane_width as u32,
-                    dimensions.window_height as u32,
-                ))
-                .unwrap();
+            for arm in dimensions.arms() {
+                texture_canvas
+                    .fill_rect(along_arm(
+                        dimensions,
+                        arm,
+                        (0, window_edge(arm, dimensions)),
+                        (-dimensions.half_road_out(arm), dimensions.half_road_in(arm)),
+                    ))
+                    .unwrap();
+            }
+            if dimensions.layout != Layout::Roundabout {
+                texture_canvas
+                    .fill_rect(Rect::new(
+                        dimensions.half_width - dimensions.reach(Airt::Left),
+                        dimensions.half_height - dimensions.reach(Airt::Up),
+                        (dimensions.reach(Airt::Left) + dimensions.reach(Airt::Right)) as u32,
+                        (dimensions.reach(Airt::Up) + dimensions.reach(Airt::Down)) as u32,
+                    ))
+                    .unwrap();
+            }
 
-            texture_canvas
-                .fill_rect(sdl2::rect::Rect::new(
-                    0,
-                    dimensions.half_height - 3 * dimensions.lane_width,
-                    dimensions.window_width as u32,
-                    6 * dimensions.lane_width as u32,
-                ))
-                .unwrap();
+            draw_bike_lanes_to_texture(texture_canvas, dimensions);
 
             texture_canvas.set_draw_color(Color::RGB(255, 255, 255));
             draw_center_lines_to_texture(texture_canvas, dimensions);
             draw_edge_lines_to_texture(texture_canvas, dimensions);
             draw_lane_lines_to_texture(texture_canvas, dimensions);
-            draw_give_way_lines_to_texture(texture_canvas, dimensions);
+            draw_pockets_to_texture(texture_canvas, dimensions);
+            draw_u_turn_arrows_to_texture(texture_canvas, dimensions);
+            if dimensions.layout == Layout::Roundabout {
+                draw_roundabout_to_texture(texture_canvas, dimensions);
+            } else {
+                draw_give_way_lines_to_texture(texture_canvas, dimensions);
+                draw_box_edges_to_texture(texture_canvas, dimensions);
+            }
+            draw_crossings_to_texture(texture_canvas, dimensions);
         })
         .expect("Failed to render everything on texture");
 
     texture
 }
 
-fn draw_edge_lines_to_texture(texture_canvas: &mut Canvas<Window>, dimensions: &Dimensions) {
-    texture_canvas
-        .draw_line(
-            (dimensions.half_width - 3 * dimensions.lane_width, 0),
-            (
-                dimensions.half_width - 3 * dimensions.lane_width,
-                dimensions.half_height - 3 * dimensions.lane_width,
-            ),
-        )
-        .unwrap();
-    texture_canvas
-        .draw_line(
-            (
-                dimensions.half_width - 3 * dimensions.lane_width,
-                dimensions.half_height + 3 * dimensions.lane_width,
-            ),
-            (
-                dimensions.half_width - 3 * dimensions.lane_width,
-                dimensions.window_height,
-            ),
-        )
-        .unwrap();
+// How far out along the given arm the edge of the window is, where the markings along it stop.
+fn window_edge(arm: Airt, dimensions: &Dimensions) -> i32 {
+    match arm {
+        Airt::Up => dimensions.half_height,
+        Airt::Down => dimensions.window_height - dimensions.half_height,
+        Airt::Left => dimensions.half_width,
+        Airt::Right => dimensions.window_width - dimensions.half_width,
+    }
+}
 
-    texture_canvas
-        .draw_line(
-            (0, dimensions.half_height - 3 * dimensions.lane_width),
-            (
-                dimensions.half_width - 3 * dimensions.lane_width,
-                dimensions.half_height - 3 * dimensions.lane_width,
-            ),
-        )
-        .unwrap();
-    texture_canvas
-        .draw_line(
-            (
-                dimensions.half_width + 3 * dimensions.lane_width,
-                dimensions.half_height - 3 * dimensions.lane_width,
-            ),
-            (
-                dimensions.window_width,
-                dimensions.half_height - 3 * dimensions.lane_width,
-            ),
-        )
-        .unwrap();
+// The lanes on the given side of the road along the given arm, as in `on_arm`, and how far its edge is from its middle on that side: the lanes coming in if the side is 1, and those going out if it's -1.
+fn side_of(dimensions: &Dimensions, arm: Airt, side: i32) -> (i32, i32) {
+    if side > 0 {
+        (dimensions.lanes_in(arm), dimensions.half_road_in(arm))
+    } else {
+        (dimensions.lanes_out(arm), dimensions.half_road_out(arm))
+    }
+}
 
-    texture_canvas
-        .draw_line(
-            (dimensions.half_width + 3 * dimensions.lane_width, 0),
-            (
-                dimensions.half_width + 3 * dimensions.lane_width,
-                dimensions.half_height - 3 * dimensions.lane_width,
-            ),
-        )
-        .unwrap();
-    texture_canvas
-        .draw_line(
-            (
-                dimensions.half_width + 3 * dimensions.lane_width,
-                dimensions.half_height + 3 * dimensions.lane_width,
-            ),
-            (
-                dimensions.half_width + 3 * dimensions.lane_width,
-                dimensions.window_height,
-            ),
-        )
-        .unwrap();
+// The way across the road along the given arm that the given side of it faces, as in `on_arm`.
+fn facing(arm: Airt, side: i32) -> Airt {
+    let (out_x, out_y) = arm.unit();
+    let (x, y) = (side as f64 * out_y, -side as f64 * out_x);
+    Airt::ALL
+        .into_iter()
+        .find(|way| way.unit() == (x, y))
+        .expect("No way across")
+}
 
+// A point the given distance out from the center along the given arm, and the given distance across it from the middle of its road, to the right of a car coming in along it.
+fn on_arm(dimensions: &Dimensions, arm: Airt, along: i32, across: i32) -> (i32, i32) {
+    let across = across + dimensions.stagger(arm);
+    let (out_x, out_y) = arm.unit();
+    let (out_x, out_y) = (out_x as i32, out_y as i32);
+    (
+        dimensions.half_width + along * out_x + across * out_y,
+        dimensions.half_height + along * out_y - across * out_x,
+    )
+}
+
+// The rectangle between the given distances out along the given arm, and the given distances across it, as in `on_arm`. It's at least a pixel wide each way, so it can stand in for a thick line.
+fn along_arm(dimensions: &Dimensions, arm: Airt, along: (i32, i32), across: (i32, i32)) -> Rect {
+    let (x0, y0) = on_arm(dimensions, arm, along.0, across.0);
+    let (x1, y1) = on_arm(dimensions, arm, along.1, across.1);
+    Rect::new(
+        x0.min(x1),
+        y0.min(y1),
+        (x1 - x0).unsigned_abs().max(1),
+        (y1 - y0).unsigned_abs().max(1),
+    )
+}
+
+// A line along the given arm, the given distance across it, between the given distances out.
+fn draw_line_along_arm(
+    texture_canvas: &mut Canvas<Window>,
+    dimensions: &Dimensions,
+    arm: Airt,
+    along: (i32, i32),
+    across: i32,
+) {
     texture_canvas
         .draw_line(
-            (0, dimensions.half_height + 3 * dimensions.lane_width),
-            (
-                dimensions.half_width - 3 * dimensions.lane_width,
-                dimensions.half_height + 3 * dimensions.lane_width,
-            ),
-        )
-        .unwrap();
-    texture_canvas
-        .draw_line(
-            (
-                dimensions.half_width + 3 * dimensions.lane_width,
-                dimensions.half_height + 3 * dimensions.lane_width,
-            ),
-            (
-                dimensions.window_width,
-                dimensions.half_height + 3 * dimensions.lane_width,
-            ),
+            on_arm(dimensions, arm, along.0, across),
+            on_arm(dimensions, arm, along.1, across),
         )
         .unwrap();
 }
 
+// Green strips along the outside of each road, up to the intersection, where the cyclists ride.
+fn draw_bike_lanes_to_texture(texture_canvas: &mut Canvas<Window>, dimensions: &Dimensions) {
+    if dimensions.bike_lane == 0 {
+        return;
+    }
+    texture_canvas.set_draw_color(Color::RGB(48, 96, 64));
+    for arm in dimensions.arms() {
+        for side in [-1, 1] {
+            let (lanes, half_road) = side_of(dimensions, arm, side);
+            let edge = lanes * dimensions.lane_width;
+            texture_canvas
+                .fill_rect(along_arm(
+                    dimensions,
+                    arm,
+                    (arm_end(arm, dimensions), window_edge(arm, dimensions)),
+                    (side * edge, side * half_road),
+                ))
+                .unwrap();
+        }
+    }
+}
+
+// Solid lines along the edges of the roads, up to the intersection, and between the lanes for cars and the bike lanes, if there are any.
+fn draw_edge_lines_to_texture(texture_canvas: &mut Canvas<Window>, dimensions: &Dimensions) {
+    for arm in dimensions.arms() {
+        for side in [-1, 1] {
+            let (lanes, half_road) = side_of(dimensions, arm, side);
+            let mut edges = vec![lanes * dimensions.lane_width];
+            if dimensions.bike_lane > 0 {
+                edges.push(half_road);
+            }
+            for edge in edges {
+                let along = (arm_end(arm, dimensions), window_edge(arm, dimensions));
+                draw_line_along_arm(texture_canvas, dimensions, arm, along, side * edge);
+            }
+        }
+    }
+}
+
+// How far from the middle the markings along the given arm stop: at the edge of the intersection. At a roundabout they run on to the middle, and the ring is drawn over them.
+fn arm_end(arm: Airt, dimensions: &Dimensions) -> i32 {
+    match dimensions.layout {
+        Layout::Crossroads | Layout::TJunction(_) | Layout::Staggered(_) => dimensions.reach(arm),
+        Layout::Roundabout => 0,
+    }
+}
+
+// The ring round the island, with a dashed line between each of its lanes and round the bike lane on the outside, if there is one. Its outside edge is dashed across the lanes coming in, where cars give way, and open across the lanes going out.
+fn draw_roundabout_to_texture(texture_canvas: &mut Canvas<Window>, dimensions: &Dimensions) {
+    // Every road into a roundabout has the same number of lanes each way.
+    let island = dimensions.island();
+    let reach = dimensions.reach(Airt::Up);
+    let lanes = island + dimensions.lanes_in(Airt::Up) * dimensions.lane_width;
+    texture_canvas.set_draw_color(Color::RGB(64, 64, 64));
+    fill_ring_to_texture(texture_canvas, dimensions, island, reach);
+    texture_canvas.set_draw_color(Color::RGB(48, 96, 64));
+    fill_ring_to_texture(texture_canvas, dimensions, lanes, reach);
+    fill_ring_to_texture(texture_canvas, dimensions, 0, island);
+
+    texture_canvas.set_draw_color(Color::RGB(255, 255, 255));
+    let dash = dimensions.lane_width / 4;
+    let half_road = dimensions.half_road_in(Airt::Up) as f64;
+    for i in 1..dimensions.lanes_in(Airt::Up) {
+        draw_circle_to_texture(
+            texture_canvas,
+            dimensions,
+            island + dimensions.lane_width * i,
+            |_, length| (length as i32) % (2 * dash) < dash,
+        );
+    }
+    draw_circle_to_texture(texture_canvas, dimensions, island, |_, _| true);
+    if dimensions.bike_lane > 0 {
+        draw_circle_to_texture(texture_canvas, dimensions, lanes, |_, _| true);
+    }
+    draw_circle_to_texture(texture_canvas, dimensions, reach, |(x, y), length| {
+        let arm = Airt::ALL.into_iter().find(|arm| {
+            let (out_x, out_y) = arm.unit();
+            x * out_x + y * out_y > 0.0 && (x * out_y - y * out_x).abs() < half_road
+        });
+        match arm {
+            // The lanes coming in are on the right of the cars coming in, facing the middle.
+            Some(arm) => {
+                let (out_x, out_y) = arm.unit();
+                x * out_y - y * out_x > 0.0 && (length as i32) % (2 * dash) < dash
+            }
+            None => true,
+        }
+    });
+}
+
+// Fills the ring between the given radii round the middle of the intersection, a row at a time.
+fn fill_ring_to_texture(
+    texture_canvas: &mut Canvas<Window>,
+    dimensions: &Dimensions,
+    inner: i32,
+    outer: i32,
+) {
+    let (x, y) = (dimensions.half_width, dimensions.half_height);
+    let span = |radius: i32, dy: i32| ((radius * radius - dy * dy) as f64).sqrt().round() as i32;
+    for dy in -outer..=outer {
+        let far = span(outer, dy);
+        if dy.abs() < inner {
+            let near = span(inner, dy);
+            texture_canvas
+                .draw_line((x - far, y + dy), (x - near, y + dy))
+                .unwrap();
+            texture_canvas
+                .draw_line((x + near, y + dy), (x + far, y + dy))
+                .unwrap();
+        } else {
+            texture_canvas
+                .draw_line((x - far, y + dy), (x + far, y + dy))
+                .unwrap();
+        }
+    }
+}
+
+// Draws a circle of the given radius round the middle of the intersection, a pixel at a time, where `shown` says so, given where the pixel is relative to the middle and how far round the circle it is.
+fn draw_circle_to_texture(
+    texture_canvas: &mut Canvas<Window>,
+    dimensions: &Dimensions,
+    radius: i32,
+    shown: impl Fn((f64, f64), f64) -> bool,
+) {
+    let radius = radius as f64;
+    let steps = (std::f64::consts::TAU * radius).ceil() as i32;
+    for step in 0..steps {
+        let (sin, cos) = (step as f64 / radius).sin_cos();
+        let (x, y) = (radius * cos, radius * sin);
+        if shown((x, y), step as f64) {
+            texture_canvas
+                .draw_point((
+                    dimensions.half_width + x.round() as i32,
+                    dimensions.half_height + y.round() as i32,
+                ))
+                .unwrap();
+        }
+    }
+}
+
+// Two dashed lines across the lanes coming in, at the edge of the intersection, on each arm where cars give way.
 fn draw_give_way_lines_to_texture(texture_canvas: &mut Canvas<Window>, dimensions: &Dimensions) {
     let dash_len = dimensions.lane_width / 4;
     let gap_len = dimensions.lane_width / 4;
     let offset = dimensions.lane_width / 2;
 
-    draw_dashed_line_to_texture(
-        texture_canvas,
-        (
-            dimensions.half_width,
-            dimensions.half_height + 3 * dimensions.lane_width,
-        ),
-        (
-            dimensions.half_width + 3 * dimensions.lane_width,
-            dimensions.half_height + 3 * dimensions.lane_width,
-        ),
-        dash_len,
-        gap_len,
-    );
-    draw_dashed_line_to_texture(
-        texture_canvas,
-        (
-            dimensions.half_width,
-            dimensions.half_height + 3 * dimensions.lane_width + offset,
-        ),
-        (
-            dimensions.half_width + 3 * dimensions.lane_width,
-            dimensions.half_height + 3 * dimensions.lane_width + offset,
-        ),
-        dash_len,
-        gap_len,
-    );
-
-    draw_dashed_line_to_texture(
-        texture_canvas,
-        (
-            dimensions.half_width,
-            dimensions.half_height - 3 * dimensions.lane_width,
-        ),
-        (
-            dimensions.half_width - 3 * dimensions.lane_width,
-            dimensions.half_height - 3 * dimensions.lane_width,
-        ),
-        dash_len,
-        gap_len,
-    );
-    draw_dashed_line_to_texture(
-        texture_canvas,
-        (
-            dimensions.half_width,
-            dimensions.half_height - 3 * dimensions.lane_width - offset,
-        ),
-        (
-            dimensions.half_width - 3 * dimensions.lane_width,
-            dimensions.half_height - 3 * dimensions.lane_width - offset,
-        ),
-        dash_len,
-        gap_len,
-    );
-
-    draw_dashed_line_to_texture(
-        texture_canvas,
-        (
-            dimensions.half_width + 3 * dimensions.lane_width,
-            dimensions.half_height,
-        ),
-        (
-            dimensions.half_width + 3 * dimensions.lane_width,
-            dimensions.half_height - 3 * dimensions.lane_width,
-        ),
-        dash_len,
-        gap_len,
-    );
-    draw_dashed_line_to_texture(
-        texture_canvas,
-        (
-            dimensions.half_width + 3 * dimensions.lane_width + offset,
-            dimensions.half_height,
-        ),
-        (
-            dimensions.half_width + 3 * dimensions.lane_width + offset,
-            dimensions.half_height - 3 * dimensions.lane_width,
-        ),
-        dash_len,
-        gap_len,
-    );
-
-    draw_dashed_line_to_texture(
-        texture_canvas,
-        (
-            dimensions.half_width - 3 * dimensions.lane_width,
-            dimensions.half_height,
-        ),
-        (
-            dimensions.half_width - 3 * dimensions.lane_width,
-            dimensions.half_height + 3 * dimensions.lane_width,
-        ),
-        dash_len,
-        gap_len,
-    );
-    draw_dashed_line_to_texture(
-        texture_canvas,
-        (
-            dimensions.half_width - 3 * dimensions.lane_width - offset,
-            dimensions.half_height,
-        ),
-        (
-            dimensions.half_width - 3 * dimensions.lane_width - offset,
-            dimensions.half_height + 3 * dimensions.lane_width,
-        ),
-        dash_len,
-        gap_len,
-    );
+    for arm in dimensions.arms() {
+        if !dimensions.gives_way(arm) {
+            continue;
+        }
+        for along in [dimensions.reach(arm), dimensions.reach(arm) + offset] {
+            draw_dashed_line_to_texture(
+                texture_canvas,
+                on_arm(dimensions, arm, along, 0),
+                on_arm(dimensions, arm, along, dimensions.half_road_in(arm)),
+                dash_len,
+                gap_len,
+            );
+        }
+    }
+}
+
+// The edge of the intersection wherever there's no road across it: beside a road that's narrower than the ones it crosses, or moved to one side of them at a staggered junction, and across the end of the arm that isn't there at a T-junction, where the road running past carries its bike lane across, if it has one.
+fn draw_box_edges_to_texture(texture_canvas: &mut Canvas<Window>, dimensions: &Dimensions) {
+    let arms = dimensions.arms();
+    for arm in Airt::ALL {
+        let present = arms.contains(&arm);
+        // How far the intersection reaches on either side of the middle of the road along the arm.
+        let width =
+            |side: i32| dimensions.reach(facing(arm, side)) - side * dimensions.stagger(arm);
+        let reach = dimensions.reach(arm);
+        if !present && dimensions.bike_lane > 0 {
+            let edge = reach - dimensions.bike_lane;
+            let across = (-width(-1), width(1));
+            texture_canvas.set_draw_color(Color::RGB(48, 96, 64));
+            texture_canvas
+                .fill_rect(along_arm(dimensions, arm, (edge, reach), across))
+                .unwrap();
+            texture_canvas.set_draw_color(Color::RGB(255, 255, 255));
+            texture_canvas
+                .fill_rect(along_arm(dimensions, arm, (edge, edge), across))
+                .unwrap();
+        }
+        for side in [-1, 1] {
+            let open = if present {
+                side_of(dimensions, arm, side).1
+            } else {
+                0
+            };
+            if width(side) > open {
+                texture_canvas
+                    .draw_line(
+                        on_arm(dimensions, arm, reach, side * open),
+                        on_arm(dimensions, arm, reach, side * width(side)),
+                    )
+                    .unwrap();
+            }
+        }
+    }
+}
+
+// Where the innermost lane coming in along an arm is a turn pocket, it's closed off further out than where the pocket opens up by a ghost island: a solid line along its outside edge, hatched with diagonal stripes, that tapers in to the middle of the road where the pocket opens.
+fn draw_pockets_to_texture(texture_canvas: &mut Canvas<Window>, dimensions: &Dimensions) {
+    let lane_width = dimensions.lane_width;
+    for arm in dimensions.arms() {
+        let Some(opening) = dimensions.pocket_opening(arm) else {
+            continue;
+        };
+        let closed = opening + lane_width;
+        let edge = window_edge(arm, dimensions);
+        draw_line_along_arm(texture_canvas, dimensions, arm, (closed, edge), lane_width);
+        for along in (opening..edge).step_by(lane_width as usize / 2) {
+            texture_canvas
+                .draw_line(
+                    on_arm(dimensions, arm, along, 0),
+                    on_arm(dimensions, arm, along + lane_width, lane_width),
+                )
+                .unwrap();
+        }
+    }
+}
+
+// An arrow painted in the innermost lane coming in along each arm, before the crossing, where cars can make a U-turn: on along the lane, round to the left and back.
+fn draw_u_turn_arrows_to_texture(texture_canvas: &mut Canvas<Window>, dimensions: &Dimensions) {
+    let lane_width = dimensions.lane_width as f64;
+    let radius = lane_width / 4.0;
+    let (stem, hook) = (0.7 * lane_width, 0.2 * lane_width);
+    for arm in dimensions.arms() {
+        if !dimensions.movements().contains(&(arm.opposite(), arm)) {
+            continue;
+        }
+        let tip = dimensions.reach(arm) as f64 + 2.5 * lane_width;
+        let at = |along: f64, across: f64| {
+            on_arm(dimensions, arm, along.round() as i32, across.round() as i32)
+        };
+        let mut points = vec![at(tip + lane_width, stem)];
+        for step in 0..=8 {
+            let (sin, cos) = (step as f64 * std::f64::consts::PI / 8.0).sin_cos();
+            points.push(at(tip - radius * sin, stem - radius * (1.0 - cos)));
+        }
+        points.push(at(tip + lane_width / 2.0, hook));
+        for pair in points.windows(2) {
+            texture_canvas.draw_line(pair[0], pair[1]).unwrap();
+        }
+        for side in [-1.0, 1.0] {
+            texture_canvas
+                .draw_line(
+                    at(tip + lane_width / 2.0, hook),
+                    at(tip + 0.3 * lane_width, hook + side * 0.15 * lane_width),
+                )
+                .unwrap();
+        }
+    }
 }
 
+// Zebra crossings: stripes a quarter of a lane width wide, running the way the cars go, from one edge of the road to the other.
+fn draw_crossings_to_texture(texture_canvas: &mut Canvas<Window>, dimensions: &Dimensions) {
+    let stripe = dimensions.lane_width / 4;
+    for arm in dimensions.arms() {
+        let crossing = pedestrians::crossing(arm, dimensions);
+        let (x, y) = crossing.center;
+        let across_road = crossing.heading.0 != 0.0;
+        let (length, width) = (
+            2 * crossing.half_length.round() as i32,
+            2 * crossing.half_width.round() as i32,
+        );
+        for offset in (stripe / 2..length).step_by(2 * stripe as usize) {
+            let rect = if across_road {
+                sdl2::rect::Rect::new(
+                    x.round() as i32 - length / 2 + offset,
+                    y.round() as i32 - width / 2,
+                    stripe as u32,
+                    width as u32,
+                )
+            } else {
+                sdl2::rect::Rect::new(
+                    x.round() as i32 - width / 2,
+                    y.round() as i32 - length / 2 + offset,
+                    width as u32,
+                    stripe as u32,
+                )
+            };
+            texture_canvas.fill_rect(rect).unwrap();
+        }
+    }
+}
+
+// Dashed lines between the lanes on each side of each road, from the middle of the intersection out.
 fn draw_lane_lines_to_texture(
     texture_canvas: &mut sdl2::render::Canvas<Window>,
     dimensions: &Dimensions,
@@ -264,47 +433,19 @@ fn draw_lane_lines_to_texture(
     let dash_len = dimensions.lane_width / 4;
     let gap_len = dimensions.lane_width / 4;
 
-    for i in 1..3 {
-        draw_dashed_line_to_texture(
-            texture_canvas,
-            (dimensions.half_width - dimensions.lane_width * i, 0),
-            (
-                dimensions.half_width - dimensions.lane_width * i,
-                dimensions.window_height,
-            ),
-            dash_len,
-            gap_len,
-        );
-        draw_dashed_line_to_texture(
-            texture_canvas,
-            (dimensions.half_width + dimensions.lane_width * i, 0),
-            (
-                dimensions.half_width + dimensions.lane_width * i,
-                dimensions.window_height,
-            ),
-            dash_len,
-            gap_len,
-        );
-        draw_dashed_line_to_texture(
-            texture_canvas,
-            (0, dimensions.half_height - dimensions.lane_width * i),
-            (
-                dimensions.window_width,
-                dimensions.half_height - dimensions.lane_width * i,
-            ),
-            dash_len,
-            gap_len,
-        );
-        draw_dashed_line_to_texture(
-            texture_canvas,
-            (0, dimensions.half_height + dimensions.lane_width * i),
-            (
-                dimensions.window_width,
-                dimensions.half_height + dimensions.lane_width * i,
-            ),
-            dash_len,
-            gap_len,
-        );
+    for arm in dimensions.arms() {
+        for side in [-1, 1] {
+            for i in 1..side_of(dimensions, arm, side).0 {
+                let across = side * dimensions.lane_width * i;
+                draw_dashed_line_to_texture(
+                    texture_canvas,
+                    on_arm(dimensions, arm, 0, across),
+                    on_arm(dimensions, arm, window_edge(arm, dimensions), across),
+                    dash_len,
+                    gap_len,
+                );
+            }
+        }
     }
 }
 
@@ -312,18 +453,15 @@ fn draw_center_lines_to_texture(
     texture_canvas: &mut sdl2::render::Canvas<Window>,
     dimensions: &Dimensions,
 ) {
-    texture_canvas
-        .draw_line(
-            (dimensions.half_width, 0),
-            (dimensions.half_width, dimensions.window_height),
-        )
-        .unwrap();
-    texture_canvas
-        .draw_line(
-            (0, dimensions.half_height),
-            (dimensions.window_width, dimensions.half_height),
-        )
-        .unwrap();
+    for arm in dimensions.arms() {
+        draw_line_along_arm(
+            texture_canvas,
+            dimensions,
+            arm,
+            (0, window_edge(arm, dimensions)),
+            0,
+        );
+    }
 }
 
 fn draw_dashed_line_to_texture(
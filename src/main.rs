@@ -1,18 +1,224 @@
 #![windows_subsystem = "windows"] // From the druid docs: "By default, Windows will open a console with your application’s window. If you don’t want the console to be shown, use #![windows_subsystem = "windows"] at the beginning of your crate."
 
 mod cars;
+mod checker;
+mod collision;
+mod compare;
+mod drivers;
+mod json;
 mod lanes;
+mod map;
+mod network;
+mod osm;
+mod pedestrians;
+mod routes;
+mod scenario;
+mod sensing;
 mod sim;
 mod stats;
 mod textures;
 mod trees;
 mod types;
+mod vehicles;
 
-use crate::cars::Traffic;
+use std::process;
+
+use crate::{
+    cars::Traffic,
+    network::Network,
+    types::{Airt, Layout, Road},
+};
 
 fn main() {
-    let mut traffic = Traffic::new();
-    sim::simulate(&mut traffic);
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "--check") {
+        match checker::check(&args[1..]) {
+            Ok(true) => return,
+            Ok(false) => process::exit(1),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(2);
+            }
+        }
+    }
+
+    if args.first().is_some_and(|arg| arg == "--compare") {
+        if let Err(e) = compare::compare(&args[1..]) {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+        return;
+    }
+
+    if let Some(path) = args.first().and_then(|arg| arg.strip_prefix("--play=")) {
+        if let Err(e) = scenario::play(path, &args[1..]) {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+        return;
+    }
+
+    // Left-hand traffic and turn pockets can go with any of the options below, before or after them, and U-turns with a single intersection. Cars crossing a road network never plan a U-turn.
+    let left_hand = args.iter().any(|arg| arg == "--left-hand");
+    let pockets = args.iter().any(|arg| arg == "--pockets");
+    let u_turns = args.iter().any(|arg| arg == "--u-turns");
+    args.retain(|arg| !["--left-hand", "--pockets", "--u-turns"].contains(&arg.as_str()));
+    // So can a seed, which makes a run repeatable, picking the cars sent in from it instead of at random.
+    let seed = match args.iter().find_map(|arg| arg.strip_prefix("--seed=")) {
+        None => None,
+        Some(value) => match value.parse::<u64>() {
+            Ok(seed) => Some(seed),
+            Err(_) => {
+                eprintln!("Expected a whole number for --seed, got {}", value);
+                process::exit(2);
+            }
+        },
+    };
+    args.retain(|arg| !arg.starts_with("--seed="));
+    // So can a scenario, for a single intersection, which plays in the window instead of the keys sending cars in.
+    let scenario = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--scenario="))
+        .map(str::to_string);
+    args.retain(|arg| !arg.starts_with("--scenario="));
+    if scenario.is_some()
+        && args
+            .first()
+            .is_some_and(|arg| arg.starts_with("--grid=") || arg.starts_with("--map="))
+    {
+        eprintln!("A scenario is for a single intersection, not a road network");
+        process::exit(2);
+    }
+    // A major road from side to side, crossed by a minor road with one lane each way, where the traffic is slower and gives way.
+    let mut roads = [Road::default(); 4];
+    if args.first().is_some_and(|arg| arg == "--minor-road") {
+        for arm in [Airt::Up, Airt::Down] {
+            roads[arm as usize] = Road {
+                lanes_in: 1,
+                lanes_out: 1,
+                speed: 60,
+                minor: true,
+            };
+        }
+    }
+    // The options for the roads that the checker takes, e.g. `--lanes=2`, `--up-lanes-in=1` or `--left-speed=60`, can go anywhere too, and go on top of `--minor-road`'s. A grid only takes `--lanes`, and a map gives its own.
+    let mut road_options = Vec::new();
+    for arg in &args {
+        let Some((key, value)) = arg.split_once('=') else {
+            continue;
+        };
+        match checker::road_option(key, value, &mut roads) {
+            Ok(true) => road_options.push(arg.clone()),
+            Ok(false) => {}
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(2);
+            }
+        }
+    }
+    args.retain(|arg| !road_options.contains(arg));
+
+    if let Some(grid) = args.first().and_then(|arg| arg.strip_prefix("--grid=")) {
+        if road_options.iter().any(|arg| !arg.starts_with("--lanes=")) {
+            eprintln!("A grid has the same lanes each way along every road, set with --lanes");
+            process::exit(2);
+        }
+        if let Err(e) = checker::check_roads(&roads, Layout::Crossroads) {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+        match network::parse_grid(grid) {
+            Ok((columns, rows)) => {
+                let mut network = Network::grid(columns, rows, roads[0].lanes_in);
+                if let Some(seed) = seed {
+                    network.seed(seed);
+                }
+                network.pockets = pockets;
+                if left_hand {
+                    network.keep_left();
+                }
+                sim::simulate_network(&mut network);
+                let s = network.format();
+                stats::show(s);
+                return;
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(2);
+            }
+        }
+    }
+
+    if let Some(path) = args.first().and_then(|arg| arg.strip_prefix("--map=")) {
+        if !road_options.is_empty() {
+            eprintln!("A map gives the lanes and speed of each of its roads itself");
+            process::exit(2);
+        }
+        match map::load(path) {
+            Ok((mut network, warnings)) => {
+                for warning in warnings {
+                    eprintln!("{}", warning);
+                }
+                if let Some(seed) = seed {
+                    network.seed(seed);
+                }
+                network.pockets = pockets;
+                if left_hand {
+                    network.keep_left();
+                }
+                sim::simulate_network(&mut network);
+                let s = network.format();
+                stats::show(s);
+                return;
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(2);
+            }
+        }
+    }
+
+    let layout = match args.first().map(String::as_str) {
+        Some("--roundabout") => Layout::Roundabout,
+        Some("--t-junction") => Layout::TJunction(Airt::Up),
+        Some("--staggered") => Layout::Staggered(Airt::Left),
+        _ => Layout::Crossroads,
+    };
+    if let Err(e) = checker::check_roads(&roads, layout) {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
+    let mut traffic = match scenario {
+        None => seed.map_or_else(Traffic::new, Traffic::seeded),
+        Some(path) => {
+            // The scenario is checked against the intersection as it's drawn, and mirrored along with it for left-hand traffic.
+            let arrivals = scenario::load(&path).and_then(|arrivals| {
+                scenario::check(&arrivals, &layout.all_movements(u_turns))
+                    .map_err(|e| format!("{}: {}", path, e))?;
+                Ok(arrivals)
+            });
+            let mut arrivals = match arrivals {
+                Ok(arrivals) => arrivals,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    process::exit(2);
+                }
+            };
+            if left_hand {
+                arrivals = arrivals.iter().map(scenario::Arrival::mirror).collect();
+            }
+            let mut traffic = Traffic::seeded(seed.unwrap_or(0));
+            traffic.play(arrivals);
+            traffic
+        }
+    };
+    let (layout, roads) = if left_hand {
+        (layout.mirror(), types::mirror_roads(roads))
+    } else {
+        (layout, roads)
+    };
+    traffic.left_hand = left_hand;
+    sim::simulate(&mut traffic, layout, roads, pockets, u_turns);
     let s = traffic.format();
     stats::show(s);
 }
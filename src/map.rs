@@ -0,0 +1,639 @@
+// Road networks read from a file, instead of a grid of identical crossroads. A map is a set of intersections at given coordinates and the two-way roads between them, each with its own number of lanes, lane speed and priority, and the turns allowed at each intersection. It's read from JSON in the format described in the README, or from the subset of OpenStreetMap XML that `osm.rs` understands, and fitted to the grid that the network is drawn on: every road has to run roughly north-south or east-west, so the intersections fall into columns and rows. Anything that can't be simulated is an error, with a message that says what and where, rather than being quietly left out. The exceptions are the kinds of road in OpenStreetMap that are in almost every export, such as motorways and slip roads, which are left out with a warning instead.
+
+use std::fs;
+
+use crate::{
+    json::{self, Json},
+    network::{Link, MAX_GRID, Network, Node},
+    osm,
+    types::{Airt, Layout, Road},
+};
+
+// How far, in degrees, a road can run from north-south or east-west and still be taken as running that way.
+const MAX_SKEW: f64 = 30.0;
+
+// An intersection as it's mapped, before it's put on the grid. `y` increases northward, up the screen. `arms` are the ways out of it there are roads along, whether they lead to another intersection or off the map, and `edge_roads` the roads along those that lead off the map, where they differ from `road`. `turns` are the arms that cars coming in along an arm may go out along, for those arms where not every turn is allowed.
+pub struct Site {
+    pub name: String,
+    pub x: f64,
+    pub y: f64,
+    pub roundabout: bool,
+    pub arms: Vec<Airt>,
+    pub road: Road,
+    pub edge_roads: Vec<(Airt, Road)>,
+    pub turns: Vec<(Airt, Vec<Airt>)>,
+}
+
+// A two-way road between two intersections, given by their indices, as it is at each end: it has the same lanes each way and lane speed all along, so the lanes coming in at one end are those going out at the other, but it may be a minor road at one end and not the other.
+pub struct Street {
+    pub from: usize,
+    pub to: usize,
+    pub ends: [Road; 2],
+}
+
+pub struct Map {
+    pub sites: Vec<Site>,
+    pub streets: Vec<Street>,
+    pub warnings: Vec<String>, // About what was left out of the map.
+}
+
+// Reads a map from a file, as JSON if its name ends in `.json`, or as OpenStreetMap XML if it ends in `.osm` or `.xml`, and fits it to the grid. Returns the warnings about it too.
+pub fn load(path: &str) -> Result<(Network, Vec<String>), String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+    let map = if path.ends_with(".json") {
+        from_json(&text)
+    } else if path.ends_with(".osm") || path.ends_with(".xml") {
+        osm::read(&text)
+    } else {
+        Err("Expected a map file ending in .json, .osm or .xml".to_string())
+    };
+    map.and_then(|map| {
+        let warnings = map
+            .warnings
+            .iter()
+            .map(|warning| format!("{}: {}", path, warning));
+        Ok((map.network()?, warnings.collect()))
+    })
+    .map_err(|e| format!("{}: {}", path, e))
+}
+
+impl Map {
+    // Puts the intersections on the grid, walking out from the first one along the roads: the next intersection along a road that runs east is in the next column, and so on. Fails if the roads don't line up, if two intersections would land in the same place, or if the result is bigger than `MAX_GRID` either way.
+    pub fn network(&self) -> Result<Network, String> {
+        if self.sites.is_empty() {
+            return Err("The map has no intersections".to_string());
+        }
+
+        // Which street, if any, leaves each intersection along each arm.
+        let mut used = vec![[None; 4]; self.sites.len()];
+        let mut headings = Vec::new();
+        for (index, street) in self.streets.iter().enumerate() {
+            let (from, to) = (&self.sites[street.from], &self.sites[street.to]);
+            if street.from == street.to {
+                return Err(format!("A road leads from {} back to itself", from.name));
+            }
+            let heading = heading(to.x - from.x, to.y - from.y).ok_or_else(|| {
+                format!(
+                    "The road from {} to {} runs more than {} degrees off north-south or east-west, and only roads at right angles to each other are supported",
+                    from.name, to.name, MAX_SKEW
+                )
+            })?;
+            for (site, arm) in [(street.from, heading), (street.to, heading.opposite())] {
+                let name = &self.sites[site].name;
+                if let Some(other) = used[site][arm as usize] {
+                    let other: &Street = &self.streets[other];
+                    let other = &self.sites[other.from + other.to - site].name;
+                    return Err(format!(
+                        "The roads from {} to {} and to {} both leave it going {}",
+                        name,
+                        self.sites[street.from + street.to - site].name,
+                        other,
+                        compass(arm)
+                    ));
+                }
+                if !self.sites[site].arms.contains(&arm) {
+                    return Err(format!(
+                        "The road from {} to {} leaves {} going {}, where it has no arm",
+                        from.name,
+                        to.name,
+                        name,
+                        compass(arm)
+                    ));
+                }
+                used[site][arm as usize] = Some(index);
+            }
+            headings.push(heading);
+        }
+
+        let mut nodes = Vec::new();
+        for (index, site) in self.sites.iter().enumerate() {
+            nodes.push(self.node(index, site, &used[index])?);
+        }
+        self.place(&mut nodes, &headings)?;
+
+        let mut links = Vec::new();
+        for (street, &heading) in self.streets.iter().zip(&headings) {
+            for (from, to, heading) in [
+                (street.from, street.to, heading),
+                (street.to, street.from, heading.opposite()),
+            ] {
+                let end = &street.ends[if from == street.from { 0 } else { 1 }];
+                links.push(Link {
+                    from,
+                    to,
+                    heading,
+                    lanes: end.lanes_out,
+                });
+            }
+        }
+        Ok(Network::new(nodes, links))
+    }
+
+    // The intersection as it will be simulated, yet to be placed on the grid, given the street along each of its arms.
+    fn node(&self, index: usize, site: &Site, used: &[Option<usize>; 4]) -> Result<Node, String> {
+        let missing = Airt::ALL
+            .into_iter()
+            .filter(|arm| !site.arms.contains(arm))
+            .collect::<Vec<Airt>>();
+        let layout = match (missing.as_slice(), site.roundabout) {
+            ([], true) => Layout::Roundabout,
+            ([], false) => Layout::Crossroads,
+            ([missing], false) => Layout::TJunction(*missing),
+            ([_], true) => {
+                return Err(format!(
+                    "{} is a roundabout with three arms, and only roundabouts with four are supported",
+                    site.name
+                ));
+            }
+            _ => {
+                return Err(format!(
+                    "{} has {} arms, and only crossroads, T-junctions and roundabouts, with three or four, are supported",
+                    site.name,
+                    site.arms.len()
+                ));
+            }
+        };
+
+        let mut roads = [Road::default(); 4];
+        for arm in site.arms.iter().copied() {
+            roads[arm as usize] = match used[arm as usize] {
+                Some(street) => {
+                    let street = &self.streets[street];
+                    street.ends[if street.from == index { 0 } else { 1 }]
+                }
+                None => site
+                    .edge_roads
+                    .iter()
+                    .find(|&&(edge, _)| edge == arm)
+                    .map_or(site.road, |&(_, road)| road),
+            };
+            let road = roads[arm as usize];
+            for (lanes, way) in [(road.lanes_in, "coming in"), (road.lanes_out, "going out")] {
+                if !(1..=5).contains(&lanes) {
+                    return Err(format!(
+                        "The road going {} from {} has {} lanes {}, and from 1 to 5 are supported",
+                        compass(arm),
+                        site.name,
+                        lanes,
+                        way
+                    ));
+                }
+            }
+            if !(25..=150).contains(&road.speed) {
+                return Err(format!(
+                    "The road going {} from {} has a speed of {}, and from 25 to 150 are supported",
+                    compass(arm),
+                    site.name,
+                    road.speed
+                ));
+            }
+        }
+        let first = roads[site.arms[0] as usize].lanes_in;
+        if layout == Layout::Roundabout
+            && site.arms.iter().any(|&arm| {
+                roads[arm as usize].lanes_in != first || roads[arm as usize].lanes_out != first
+            })
+        {
+            return Err(format!(
+                "Every road into the roundabout {} needs the same number of lanes each way",
+                site.name
+            ));
+        }
+
+        let mut movements = layout.movements();
+        for (arm, allowed) in &site.turns {
+            for &out in allowed.iter().chain([arm]) {
+                if !site.arms.contains(&out) {
+                    return Err(format!(
+                        "The turns at {} are given for its {} arm, which it doesn't have",
+                        site.name,
+                        compass(out)
+                    ));
+                }
+            }
+            movements.retain(|&(start, end)| start != arm.opposite() || allowed.contains(&end));
+            if !movements.iter().any(|&(start, _)| start == arm.opposite()) {
+                return Err(format!(
+                    "Cars coming into {} from the {} aren't allowed to go anywhere",
+                    site.name,
+                    compass(*arm)
+                ));
+            }
+        }
+
+        Ok(Node {
+            name: site.name.clone(),
+            column: 0,
+            row: 0,
+            layout,
+            roads,
+            movements,
+        })
+    }
+
+    // Works out each intersection's column and row from the way the roads run between them.
+    fn place(&self, nodes: &mut [Node], headings: &[Airt]) -> Result<(), String> {
+        let mut places: Vec<Option<(i64, i64)>> = vec![None; nodes.len()];
+        places[0] = Some((0, 0));
+        let mut queue = vec![0];
+        while let Some(site) = queue.pop() {
+            let (column, row) = places[site].expect("Unplaced intersection");
+            for (street, &heading) in self.streets.iter().zip(headings) {
+                let (next, heading) = if street.from == site {
+                    (street.to, heading)
+                } else if street.to == site {
+                    (street.from, heading.opposite())
+                } else {
+                    continue;
+                };
+                let (dx, dy) = heading.unit();
+                let place = (column + dx as i64, row + dy as i64);
+                match places[next] {
+                    None => {
+                        if let Some(other) = places.iter().position(|&other| other == Some(place)) {
+                            return Err(format!(
+                                "{} and {} would be in the same place on the grid, going by the roads between them and the other intersections",
+                                self.sites[next].name, self.sites[other].name
+                            ));
+                        }
+                        places[next] = Some(place);
+                        queue.push(next);
+                    }
+                    Some(other) if other != place => {
+                        return Err(format!(
+                            "The roads around {} and {} don't line up into a grid",
+                            self.sites[site].name, self.sites[next].name
+                        ));
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+
+        let mut placed = Vec::new();
+        for (site, place) in places.iter().enumerate() {
+            placed.push(place.ok_or_else(|| {
+                format!(
+                    "{} isn't connected to {} by any roads",
+                    self.sites[site].name, self.sites[0].name
+                )
+            })?);
+        }
+        let left = placed.iter().map(|&(column, _)| column).min().unwrap_or(0);
+        let top = placed.iter().map(|&(_, row)| row).min().unwrap_or(0);
+        let right = placed.iter().map(|&(column, _)| column).max().unwrap_or(0);
+        let bottom = placed.iter().map(|&(_, row)| row).max().unwrap_or(0);
+        if right - left >= MAX_GRID as i64 || bottom - top >= MAX_GRID as i64 {
+            return Err(format!(
+                "The map is {} intersections across and {} down, and at most {} each way are supported",
+                right - left + 1,
+                bottom - top + 1,
+                MAX_GRID
+            ));
+        }
+        for (node, (column, row)) in nodes.iter_mut().zip(placed) {
+            node.column = (column - left) as usize;
+            node.row = (row - top) as usize;
+        }
+        Ok(())
+    }
+}
+
+// The way on screen that a road runs, given how far east and north its far end is, or None if it's too far off all of them.
+pub fn heading(east: f64, north: f64) -> Option<Airt> {
+    let angle = north.atan2(east).to_degrees();
+    [
+        (0.0, Airt::Right),
+        (90.0, Airt::Up),
+        (180.0, Airt::Left),
+        (-180.0, Airt::Left),
+        (-90.0, Airt::Down),
+    ]
+    .into_iter()
+    .find(|&(axis, _)| (angle - axis).abs() <= MAX_SKEW)
+    .map(|(_, heading)| heading)
+}
+
+pub fn compass(arm: Airt) -> &'static str {
+    match arm {
+        Airt::Up => "north",
+        Airt::Down => "south",
+        Airt::Left => "west",
+        Airt::Right => "east",
+    }
+}
+
+pub fn from_compass(name: &str) -> Result<Airt, String> {
+    Airt::ALL
+        .into_iter()
+        .find(|&arm| compass(arm) == name)
+        .ok_or_else(|| {
+            format!(
+                "expected north, south, east or west, got \"{}\"; only roads at right angles are supported",
+                name
+            )
+        })
+}
+
+// The arm a car coming in along the given arm goes out along, if it makes the given turn, as the driver sees it.
+pub fn turn(arm: Airt, turn: &str) -> Result<Airt, String> {
+    let heading = arm.opposite();
+    let right = match heading {
+        Airt::Up => Airt::Right,
+        Airt::Right => Airt::Down,
+        Airt::Down => Airt::Left,
+        Airt::Left => Airt::Up,
+    };
+    match turn {
+        "straight" => Ok(heading),
+        "right" => Ok(right),
+        "left" => Ok(right.opposite()),
+        "u-turn" => Err("U-turns aren't supported".to_string()),
+        _ => Err(format!(
+            "expected a turn of left, straight or right, got \"{}\"",
+            turn
+        )),
+    }
+}
+
+// Reads a map in the JSON format described in the README: an object with a list of `nodes` and a list of `roads`.
+pub fn from_json(text: &str) -> Result<Map, String> {
+    let top = json::parse(text)?;
+    let top = fields(&top, "The map", &["nodes", "roads"])?;
+    let list = |key: &str| match get(top, key) {
+        None => Ok(Vec::new()),
+        Some(Json::Array(items)) => Ok(items.clone()),
+        Some(other) => Err(format!("Expected a list of {}, got {}", key, other.kind())),
+    };
+
+    let mut sites = Vec::new();
+    for (index, node) in list("nodes")?.iter().enumerate() {
+        let what = format!("Node {}", index + 1);
+        let node = fields(
+            node,
+            &what,
+            &[
+                "id",
+                "x",
+                "y",
+                "layout",
+                "arms",
+                "turns",
+                "lanes",
+                "lanes_in",
+                "lanes_out",
+                "speed",
+                "minor",
+            ],
+        )?;
+        let name = match get(node, "id") {
+            Some(Json::String(id)) => id.clone(),
+            Some(Json::Number(id)) => id.to_string(),
+            Some(other) => {
+                return Err(format!(
+                    "{} needs an id that's a string or a number, not {}",
+                    what,
+                    other.kind()
+                ));
+            }
+            None => return Err(format!("{} needs an id", what)),
+        };
+        if sites.iter().any(|site: &Site| site.name == name) {
+            return Err(format!("There are two nodes called {}", name));
+        }
+        let what = format!("Node {}", name);
+        let roundabout = match get(node, "layout") {
+            None => false,
+            Some(Json::String(layout)) if layout == "crossroads" => false,
+            Some(Json::String(layout)) if layout == "roundabout" => true,
+            Some(Json::String(layout)) if layout == "t-junction" => {
+                return Err(format!(
+                    "{}: a T-junction is given by the three arms it has, not its layout",
+                    what
+                ));
+            }
+            Some(other) => {
+                return Err(format!(
+                    "{}: expected a layout of \"crossroads\" or \"roundabout\", got {}",
+                    what,
+                    describe(other)
+                ));
+            }
+        };
+        let arms = match get(node, "arms") {
+            None => Airt::ALL.to_vec(),
+            Some(Json::Array(arms)) => {
+                let mut list = Vec::new();
+                for arm in arms {
+                    let arm = from_compass(&string(arm, &what)?)
+                        .map_err(|e| format!("{}: {}", what, e))?;
+                    if list.contains(&arm) {
+                        return Err(format!("{} has two arms going {}", what, compass(arm)));
+                    }
+                    list.push(arm);
+                }
+                list
+            }
+            Some(other) => {
+                return Err(format!(
+                    "{}: expected a list of arms, got {}",
+                    what,
+                    other.kind()
+                ));
+            }
+        };
+        let mut turns = Vec::new();
+        if let Some(given) = get(node, "turns") {
+            let Json::Object(given) = given else {
+                return Err(format!(
+                    "{}: expected the turns as an object, from each arm to a list of turns, got {}",
+                    what,
+                    given.kind()
+                ));
+            };
+            for (arm, allowed) in given {
+                let arm = from_compass(arm).map_err(|e| format!("{}: {}", what, e))?;
+                let Json::Array(allowed) = allowed else {
+                    return Err(format!(
+                        "{}: expected a list of turns from the {}, got {}",
+                        what,
+                        compass(arm),
+                        allowed.kind()
+                    ));
+                };
+                let mut out = Vec::new();
+                for allowed in allowed {
+                    out.push(
+                        turn(arm, &string(allowed, &what)?)
+                            .map_err(|e| format!("{}: {}", what, e))?,
+                    );
+                }
+                turns.push((arm, out));
+            }
+        }
+        let (x, y) = match (get(node, "x"), get(node, "y")) {
+            (Some(x), Some(y)) => (number(x, &what)?, number(y, &what)?),
+            _ => return Err(format!("{} needs both an x and a y", what)),
+        };
+        sites.push(Site {
+            road: road(node, &what, ["lanes_in", "lanes_out"])?,
+            name,
+            x,
+            y,
+            roundabout,
+            arms,
+            edge_roads: Vec::new(),
+            turns,
+        });
+    }
+
+    let mut streets = Vec::new();
+    for (index, street) in list("roads")?.iter().enumerate() {
+        let what = format!("Road {}", index + 1);
+        let street = fields(
+            street,
+            &what,
+            &[
+                "from",
+                "to",
+                "lanes",
+                "lanes_forward",
+                "lanes_backward",
+                "speed",
+                "minor",
+            ],
+        )?;
+        let mut ends = [0, 0];
+        for (end, key) in ends.iter_mut().zip(["from", "to"]) {
+            let id = match get(street, key) {
+                Some(Json::String(id)) => id.clone(),
+                Some(Json::Number(id)) => id.to_string(),
+                Some(other) => {
+                    return Err(format!(
+                        "{}: expected a node id for \"{}\", got {}",
+                        what,
+                        key,
+                        other.kind()
+                    ));
+                }
+                None => return Err(format!("{} needs a \"{}\"", what, key)),
+            };
+            *end = sites
+                .iter()
+                .position(|site| site.name == id)
+                .ok_or_else(|| format!("{}: there's no node called {}", what, id))?;
+        }
+        // As it is at the end it goes from, where the lanes going forward, toward the other end, go out.
+        let road = road(street, &what, ["lanes_backward", "lanes_forward"])?;
+        streets.push(Street {
+            from: ends[0],
+            to: ends[1],
+            ends: [road, road.reversed()],
+        });
+    }
+
+    Ok(Map {
+        sites,
+        streets,
+        warnings: Vec::new(),
+    })
+}
+
+// The lanes, speed and priority given in an object, with the defaults for any that aren't. `lanes` is the number each way, and the given keys override it for the lanes coming in and going out.
+fn road(object: &[(String, Json)], what: &str, ways: [&str; 2]) -> Result<Road, String> {
+    let mut road = Road::default();
+    if let Some(lanes) = get(object, "lanes") {
+        road.lanes_in = whole(lanes, what)?;
+        road.lanes_out = road.lanes_in;
+    }
+    if let Some(lanes) = get(object, ways[0]) {
+        road.lanes_in = whole(lanes, what)?;
+    }
+    if let Some(lanes) = get(object, ways[1]) {
+        road.lanes_out = whole(lanes, what)?;
+    }
+    if let Some(speed) = get(object, "speed") {
+        road.speed = whole(speed, what)?;
+    }
+    match get(object, "minor") {
+        None => {}
+        Some(Json::Bool(minor)) => road.minor = *minor,
+        Some(other) => {
+            return Err(format!(
+                "{}: expected true or false for \"minor\", got {}",
+                what,
+                other.kind()
+            ));
+        }
+    }
+    Ok(road)
+}
+
+// The fields of an object, as long as they're all ones we know.
+pub fn fields<'a>(
+    value: &'a Json,
+    what: &str,
+    known: &[&str],
+) -> Result<&'a [(String, Json)], String> {
+    let Json::Object(fields) = value else {
+        return Err(format!(
+            "{} should be an object, not {}",
+            what,
+            value.kind()
+        ));
+    };
+    if let Some((key, _)) = fields
+        .iter()
+        .find(|(key, _)| !known.contains(&key.as_str()))
+    {
+        return Err(format!(
+            "{}: \"{}\" isn't supported; the supported fields are {}",
+            what,
+            key,
+            known.join(", ")
+        ));
+    }
+    Ok(fields)
+}
+
+pub fn get<'a>(fields: &'a [(String, Json)], key: &str) -> Option<&'a Json> {
+    fields
+        .iter()
+        .find(|(other, _)| other == key)
+        .map(|(_, value)| value)
+}
+
+pub fn describe(value: &Json) -> String {
+    match value {
+        Json::String(string) => format!("\"{}\"", string),
+        Json::Number(number) => number.to_string(),
+        other => other.kind().to_string(),
+    }
+}
+
+pub fn string(value: &Json, what: &str) -> Result<String, String> {
+    match value {
+        Json::String(string) => Ok(string.clone()),
+        other => Err(format!("{}: expected a string, got {}", what, other.kind())),
+    }
+}
+
+pub fn number(value: &Json, what: &str) -> Result<f64, String> {
+    match value {
+        Json::Number(number) => Ok(*number),
+        other => Err(format!("{}: expected a number, got {}", what, other.kind())),
+    }
+}
+
+fn whole(value: &Json, what: &str) -> Result<i32, String> {
+    match value {
+        Json::Number(number) if number.fract() == 0.0 && number.abs() < i32::MAX as f64 => {
+            Ok(*number as i32)
+        }
+        other => Err(format!(
+            "{}: expected a whole number, got {}",
+            what,
+            describe(other)
+        )),
+    }
+}
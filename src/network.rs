@@ -0,0 +1,489 @@
+// A road network: a grid of intersections, each simulated as before in its own `Traffic`, joined by the roads between them. A car that drives out of one intersection toward a neighbouring one is handed over to it, and comes in along the road it turned into, in the lane it came out in. It drives across the network from where it comes in on the edge of the grid to where it leaves, following a plan of which way to go at each intersection it comes to.
+
+use std::{collections::VecDeque, time::Duration};
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::{
+    cars::{Car, TICK, Traffic},
+    routes::Route,
+    types::{self, Airt, Dimensions, Layout, Road},
+};
+
+// The largest grid, in each direction.
+pub const MAX_GRID: usize = 5;
+
+// An intersection, at its place in the grid, with how it's laid out, the road along each of its arms, in the order of `Airt::ALL`, and the movements that cars are allowed to make through it, of those its layout has roads for.
+pub struct Node {
+    pub name: String,
+    pub column: usize,
+    pub row: usize,
+    pub layout: Layout,
+    pub roads: [Road; 4],
+    pub movements: Vec<(Airt, Airt)>,
+}
+
+// A road from one intersection to its neighbour, one way, and how many lanes it has that way.
+pub struct Link {
+    pub from: usize,
+    pub to: usize,
+    pub heading: Airt,
+    pub lanes: i32,
+}
+
+// A car's journey across the network, carried along with it from one intersection to the next. `started` is the tick it came into the network, and `exit` the node where it's heading out of the network, and which way.
+#[derive(Clone)]
+pub struct Trip {
+    pub started: u64,
+    pub stops: u32, // How many times it has come to a standstill.
+    pub exit: Option<(usize, Airt)>,
+}
+
+pub struct Network {
+    pub nodes: Vec<Node>,
+    pub links: Vec<Link>,
+    pub traffic: Vec<Traffic>, // Of each node.
+    pub ticks: u64,
+    pub trips: Vec<(Duration, u32)>, // How long each finished trip took, and how many stops it made.
+    pub left_hand: bool, // Whether traffic keeps left, in which case each intersection is simulated and drawn mirrored, as a single one is.
+    pub pockets: bool, // Whether the roads into each intersection have turn pockets, as a single one can.
+    waiting: Vec<(usize, Car, Airt)>, // Cars that have driven out of an intersection, the link they're on and the way they'll go at the end of it, waiting to get into the next intersection because their lane there is full up to where they'd come in.
+    rng: StdRng,
+}
+
+impl Network {
+    // A grid of the given number of crossroads across and down, with a two-way road with the given number of lanes each way between each pair of neighbours.
+    pub fn grid(columns: usize, rows: usize, lanes: i32) -> Self {
+        let road = Road {
+            lanes_in: lanes,
+            lanes_out: lanes,
+            ..Road::default()
+        };
+        let nodes = (0..rows)
+            .flat_map(|row| {
+                (0..columns).map(move |column| Node {
+                    name: format!("{},{}", column + 1, row + 1),
+                    column,
+                    row,
+                    layout: Layout::Crossroads,
+                    roads: [road; 4],
+                    movements: Layout::Crossroads.movements(),
+                })
+            })
+            .collect::<Vec<Node>>();
+        let mut links = Vec::new();
+        for (from, node) in nodes.iter().enumerate() {
+            for heading in Airt::ALL {
+                let (dx, dy) = heading.unit();
+                let column = node.column as i64 + dx as i64;
+                let row = node.row as i64 + dy as i64;
+                if (0..columns as i64).contains(&column) && (0..rows as i64).contains(&row) {
+                    links.push(Link {
+                        from,
+                        to: row as usize * columns + column as usize,
+                        heading,
+                        lanes,
+                    });
+                }
+            }
+        }
+        Network::new(nodes, links)
+    }
+
+    pub fn new(nodes: Vec<Node>, links: Vec<Link>) -> Self {
+        Network {
+            traffic: nodes
+                .iter()
+                .map(|_| network_traffic(Traffic::new()))
+                .collect(),
+            nodes,
+            links,
+            ticks: 0,
+            trips: Vec::new(),
+            left_hand: false,
+            pockets: false,
+            waiting: Vec::new(),
+            rng: StdRng::from_os_rng(),
+        }
+    }
+
+    // Makes a run repeatable: the trips across the network, and the vehicles each intersection sends on them, are picked from the given seed instead of at random.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.traffic = (0..self.nodes.len())
+            .map(|_| network_traffic(Traffic::seeded(self.rng.random())))
+            .collect();
+    }
+
+    // Makes traffic keep left. Each intersection stays where it is on the grid, but is simulated as the mirror image of the one that's drawn, with right-hand traffic, so the roads between them leave each one the other way across.
+    pub fn keep_left(&mut self) {
+        self.left_hand = true;
+        for node in &mut self.nodes {
+            node.layout = node.layout.mirror();
+            node.roads = types::mirror_roads(node.roads);
+            for (start, end) in &mut node.movements {
+                (*start, *end) = (start.mirror(), end.mirror());
+            }
+        }
+        for link in &mut self.links {
+            link.heading = link.heading.mirror();
+        }
+    }
+
+    // The dimensions of each intersection, laid out as it is, with the movements it doesn't allow banned, for a window of the given size.
+    pub fn dimensions(
+        &self,
+        window_width: i32,
+        window_height: i32,
+        lane_width: i32,
+    ) -> Vec<Dimensions> {
+        self.nodes
+            .iter()
+            .map(|node| {
+                let mut dimensions = Dimensions::new(window_width, window_height, lane_width);
+                dimensions.layout = node.layout;
+                dimensions.roads = node.roads;
+                dimensions.pockets = self.pockets;
+                dimensions.banned = node
+                    .layout
+                    .movements()
+                    .into_iter()
+                    .filter(|movement| !node.movements.contains(movement))
+                    .collect();
+                dimensions.routes = Route::table(&dimensions);
+                dimensions
+            })
+            .collect()
+    }
+
+    fn link(&self, from: usize, heading: Airt) -> Option<usize> {
+        self.links
+            .iter()
+            .position(|link| link.from == from && link.heading == heading)
+    }
+
+    // Sends a car across the network, from a random place on the edge of the grid to another one.
+    pub fn push_random(&mut self, dimensions: &[Dimensions]) {
+        let ends = self.edges();
+        let (from, initial_direction) = ends[self.rng.random_range(0..ends.len())];
+        let (to, final_direction) = ends[self.rng.random_range(0..ends.len())];
+        // The ways in and out at the edge face opposite ways: in toward the middle, out away from it.
+        let final_direction = final_direction.opposite();
+        let Some(plan) = self.plan(from, initial_direction, to, final_direction) else {
+            return;
+        };
+        let trip = Trip {
+            started: self.ticks,
+            stops: 0,
+            exit: Some((to, final_direction)),
+        };
+        self.traffic[from].push_trip(initial_direction, plan[0], trip, &dimensions[from]);
+    }
+
+    // The places on the edge of the network where a road leads in, and the way it leads in: the arms of each intersection that don't lead to another one.
+    fn edges(&self) -> Vec<(usize, Airt)> {
+        let mut edges = Vec::new();
+        for (node, Node { layout, .. }) in self.nodes.iter().enumerate() {
+            for heading in Airt::ALL {
+                if layout.arms().contains(&heading.opposite())
+                    && self.link(node, heading.opposite()).is_none()
+                {
+                    edges.push((node, heading));
+                }
+            }
+        }
+        edges
+    }
+
+    // The shortest plan for getting from one place to another, as the way to go out of each intersection on the way, making only the movements each one allows. A breadth-first search over the roads, where each step is the way a car is heading as it comes into an intersection.
+    fn plan(&self, from: usize, heading: Airt, to: usize, exit: Airt) -> Option<Vec<Airt>> {
+        let mut previous: Vec<Option<(usize, Airt)>> = vec![None; self.nodes.len() * 4];
+        let state = |node: usize, heading: Airt| node * 4 + heading as usize;
+        let mut queue = VecDeque::from([(from, heading)]);
+        let mut seen = vec![false; self.nodes.len() * 4];
+        seen[state(from, heading)] = true;
+
+        while let Some((node, heading)) = queue.pop_front() {
+            if node == to && self.nodes[node].movements.contains(&(heading, exit)) {
+                let mut plan = vec![exit];
+                let mut at = (node, heading);
+                while let Some(before) = previous[state(at.0, at.1)] {
+                    plan.push(at.1);
+                    at = before;
+                }
+                plan.reverse();
+                return Some(plan);
+            }
+            for link in self.links.iter().filter(|link| link.from == node) {
+                let next = (link.to, link.heading);
+                if self.nodes[node]
+                    .movements
+                    .contains(&(heading, link.heading))
+                    && !seen[state(next.0, next.1)]
+                {
+                    seen[state(next.0, next.1)] = true;
+                    previous[state(next.0, next.1)] = Some((node, heading));
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    // Advances every intersection by one tick, given the dimensions of each, then hands each car that drove out of one over to the next intersection on its way, or counts its trip as finished if it drove out of the network. The car's plan is worked out afresh each time, since it may not have gone the way it meant to, if it missed its lane. If it can't get to its exit from there, it goes straight on, or the first way it's allowed to if it can't, and tries again at the next intersection. Returns false if nothing moved anywhere.
+    pub fn update(&mut self, dimensions: &[Dimensions]) -> bool {
+        self.ticks += 1;
+        let mut moved = false;
+        for (traffic, dimensions) in self.traffic.iter_mut().zip(dimensions) {
+            moved |= traffic.update(dimensions);
+        }
+
+        for from in 0..self.nodes.len() {
+            for car in self.traffic[from].take_departures() {
+                match self.link(from, car.heading_out()) {
+                    Some(link) => {
+                        let heading = car.heading_out();
+                        let next = self.links[link].to;
+                        let next = car
+                            .trip()
+                            .exit
+                            .and_then(|(to, exit)| self.plan(next, heading, to, exit))
+                            .map_or_else(|| self.nodes[next].way_on(heading), |plan| plan[0]);
+                        self.waiting.push((link, car, next));
+                    }
+                    None => {
+                        let trip = car.trip();
+                        self.trips
+                            .push((TICK * (self.ticks - trip.started) as u32, trip.stops));
+                    }
+                }
+            }
+        }
+
+        // In the order they left, so a queue for a full lane stays in order.
+        let mut still_waiting = Vec::new();
+        for (link, car, next) in std::mem::take(&mut self.waiting) {
+            let blocked =
+                still_waiting
+                    .iter()
+                    .any(|(other_link, other, _): &(usize, Car, Airt)| {
+                        *other_link == link && other.same_lane_out(&car)
+                    });
+            let Link { to, lanes, .. } = self.links[link];
+            if !blocked && self.traffic[to].push_arrival(&car, next, lanes, &dimensions[to]) {
+                moved = true;
+            } else {
+                still_waiting.push((link, car, next));
+            }
+        }
+        self.waiting = still_waiting;
+
+        moved
+    }
+
+    pub fn format(&self) -> String {
+        let trips = if self.trips.is_empty() {
+            "Trips: 0\nTrip time: N/A\nStops per trip: N/A".to_string()
+        } else {
+            let count = self.trips.len() as f64;
+            let total = self.trips.iter().map(|&(time, _)| time).sum::<Duration>();
+            let longest = self.trips.iter().map(|&(time, _)| time).max().unwrap();
+            let stops = self.trips.iter().map(|&(_, stops)| stops).sum::<u32>();
+            format!(
+                "Trips: {}\nTrip time: mean {:.2}s, max {:.2}s\nStops per trip: {:.2}",
+                self.trips.len(),
+                total.as_secs_f64() / count,
+                longest.as_secs_f64(),
+                stops as f64 / count
+            )
+        };
+        let nodes = self
+            .nodes
+            .iter()
+            .zip(&self.traffic)
+            .map(|(node, traffic)| {
+                format!(
+                    "Intersection {}: {} passed, {} crashes, {} near misses",
+                    node.name, traffic.cars_passed, traffic.crashes, traffic.near_misses
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+        format!(
+            "{}\nOn the road: {}\nWaiting to get into an intersection: {}\n{}",
+            trips,
+            self.traffic
+                .iter()
+                .map(|traffic| traffic.cars.len())
+                .sum::<usize>(),
+            self.waiting.len()
+                + self
+                    .traffic
+                    .iter()
+                    .map(|traffic| traffic.held())
+                    .sum::<usize>(),
+            nodes
+        )
+    }
+}
+
+impl Node {
+    // The way a car coming in heading the given way goes on if it has no plan: straight on, if it's allowed to, or else the first way it is.
+    fn way_on(&self, heading: Airt) -> Airt {
+        if self.movements.contains(&(heading, heading)) {
+            return heading;
+        }
+        self.movements
+            .iter()
+            .find(|&&(start, _)| start == heading)
+            .map_or(heading, |&(_, end)| end)
+    }
+}
+
+fn network_traffic(mut traffic: Traffic) -> Traffic {
+    traffic.handing_over = true;
+    traffic
+}
+
+// Reads a grid size such as `3x3`, as columns by rows.
+pub fn parse_grid(grid: &str) -> Result<(usize, usize), String> {
+    let error = || {
+        format!(
+            "Expected a grid size like 3x3, from 1 to {}, got {}",
+            MAX_GRID, grid
+        )
+    };
+    let (columns, rows) = grid.split_once('x').ok_or_else(error)?;
+    let columns = columns.parse::<usize>().map_err(|_| error())?;
+    let rows = rows.parse::<usize>().map_err(|_| error())?;
+    if !(1..=MAX_GRID).contains(&columns) || !(1..=MAX_GRID).contains(&rows) {
+        return Err(error());
+    }
+    Ok((columns, rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vehicles::Class;
+
+    // A single crossroads where cars coming in from the north, heading down the screen, aren't allowed to turn left, toward the east.
+    fn no_left_turn() -> Network {
+        let movements = Layout::Crossroads
+            .movements()
+            .into_iter()
+            .filter(|&movement| movement != (Airt::Down, Airt::Right))
+            .collect();
+        let node = Node {
+            name: "1,1".to_string(),
+            column: 0,
+            row: 0,
+            layout: Layout::Crossroads,
+            roads: [Road::default(); 4],
+            movements,
+        };
+        Network::new(vec![node], Vec::new())
+    }
+
+    #[test]
+    fn never_makes_a_banned_turn() {
+        let network = no_left_turn();
+        let dimensions = network.dimensions(800, 800, 32);
+        assert!(
+            !dimensions[0]
+                .movements()
+                .contains(&(Airt::Down, Airt::Right))
+        );
+        let mut traffic = Traffic::seeded(0);
+        traffic.handing_over = true;
+        traffic.random_lanes = true;
+        // Cars sent in at random, and cars that want to turn left handed over from a neighbouring intersection in each lane, including the one that's only for turning left where it's allowed. They come out of it heading south, from the lane they turned left, went straight on or turned right from.
+        let elsewhere = Dimensions::new(800, 800, 32);
+        let handed_over = [Airt::Left, Airt::Down, Airt::Right]
+            .map(|start| Car::spawn(start, Airt::Down, Class::Car, 0, 0, 0, &elsewhere));
+        let mut sent = 0;
+        for tick in 0..3000 {
+            if tick % 40 == 0 {
+                traffic.push(Airt::Down, &dimensions[0]);
+                sent += 1;
+            }
+            if tick % 120 == 0 {
+                let car = &handed_over[tick / 120 % 3];
+                if traffic.push_arrival(car, Airt::Right, 3, &dimensions[0]) {
+                    sent += 1;
+                }
+            }
+            traffic.update(&dimensions[0]);
+        }
+        while !traffic.is_empty() && traffic.ticks < 10000 {
+            traffic.update(&dimensions[0]);
+        }
+        let departures = traffic.take_departures();
+        assert_eq!(departures.len(), sent);
+        assert!(
+            departures
+                .iter()
+                .all(|car| car.heading_out() != Airt::Right)
+        );
+    }
+
+    // Follows a plan from where it starts, checking each intersection on the way allows the movement it makes and has a road on to the next one, to where it leaves the network.
+    fn follow(network: &Network, from: usize, heading: Airt, plan: &[Airt]) -> (usize, Airt) {
+        let (mut node, mut heading) = (from, heading);
+        for (i, &next) in plan.iter().enumerate() {
+            assert!(network.nodes[node].movements.contains(&(heading, next)));
+            if i + 1 < plan.len() {
+                node = network.links[network.link(node, next).unwrap()].to;
+            }
+            heading = next;
+        }
+        (node, heading)
+    }
+
+    #[test]
+    fn plans_the_shortest_way_with_the_turns_allowed() {
+        // From the top left corner to the bottom right one, across a 3x3 grid, going in and out heading right, takes five intersections, and any of the shortest ways will do.
+        let mut network = Network::grid(3, 3, 2);
+        let plan = network.plan(0, Airt::Right, 8, Airt::Right).unwrap();
+        assert_eq!(plan.len(), 5);
+        assert_eq!(follow(&network, 0, Airt::Right, &plan), (8, Airt::Right));
+        assert_eq!(
+            network.plan(0, Airt::Right, 0, Airt::Up),
+            Some(vec![Airt::Up])
+        );
+
+        // Without left turns, it goes round the block to the right instead, and can't get to a way out that takes one.
+        for node in &mut network.nodes {
+            node.movements.retain(|&(start, end)| {
+                let ((in_x, in_y), (out_x, out_y)) = (start.unit(), end.unit());
+                in_x * out_y - in_y * out_x >= 0.0
+            });
+        }
+        let plan = network.plan(0, Airt::Right, 0, Airt::Up).unwrap();
+        assert_eq!(
+            plan,
+            [Airt::Right, Airt::Down, Airt::Left, Airt::Up, Airt::Up]
+        );
+        assert_eq!(follow(&network, 0, Airt::Right, &plan), (0, Airt::Up));
+        assert_eq!(network.plan(0, Airt::Right, 2, Airt::Up), None);
+    }
+
+    #[test]
+    fn plays_out_the_same_from_the_same_seed() {
+        let run = |seed| {
+            let mut network = Network::grid(2, 2, 2);
+            network.seed(seed);
+            let dimensions = network.dimensions(800, 800, 32);
+            for tick in 0..3000 {
+                if tick % 30 == 0 {
+                    network.push_random(&dimensions);
+                }
+                network.update(&dimensions);
+            }
+            assert!(!network.trips.is_empty());
+            network.format()
+        };
+        assert_eq!(run(1), run(1));
+        assert_ne!(run(1), run(2));
+    }
+}
@@ -0,0 +1,841 @@
+// Maps from OpenStreetMap, in its XML format, as exported from openstreetmap.org or JOSM. Only what's needed to find the junctions and the roads between them is read: nodes, with their coordinates, ways tagged as roads, with their lanes and speed limits, and turn restrictions. A junction is a node where three or more stretches of road meet, and the roads between junctions are followed through any nodes where only two meet, so a road split into several ways is still one road. A road that ends without reaching another junction leads off the map. A link road is taken to be of the same kind as the road it links to, unless it's one-way, as slip roads are. Those, and roads for cars other than those listed in `RANKS`, such as motorways, are left out with a warning. One-way roads, roundabouts drawn as rings of ways, and anything else that can't be simulated are errors, while footpaths and other ways that cars don't use are left out without one.
+
+use std::{collections::HashMap, f64::consts::PI};
+
+use quick_xml::{
+    events::{BytesStart, Event},
+    reader::Reader,
+};
+
+use crate::{
+    map::{self, Map, Site, Street},
+    types::{Airt, Road},
+};
+
+// The kinds of road that are simulated, from the most important down. At a junction, the roads of a lower kind than the highest there are minor roads, and give way.
+const RANKS: [&[&str]; 6] = [
+    &["trunk"],
+    &["primary"],
+    &["secondary"],
+    &["tertiary"],
+    &["unclassified", "residential"],
+    &["living_street", "service"],
+];
+
+// Ways that aren't roads for cars, or not yet, which are left out.
+const IGNORED: [&str; 14] = [
+    "footway",
+    "cycleway",
+    "path",
+    "steps",
+    "pedestrian",
+    "bridleway",
+    "track",
+    "corridor",
+    "platform",
+    "elevator",
+    "proposed",
+    "construction",
+    "abandoned",
+    "bus_stop",
+];
+
+// The speed limit, in km/h, that the usual lane speeds stand for.
+const USUAL_SPEED: f64 = 50.0;
+
+// An element of an XML document, with its attributes and the elements inside it. Text between elements is skipped.
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(other, _)| other == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // The value of the `tag` child with the given key, as OpenStreetMap tags elements.
+    fn tag(&self, key: &str) -> Option<&str> {
+        self.children
+            .iter()
+            .find(|child| child.name == "tag" && child.attribute("k") == Some(key))
+            .and_then(|child| child.attribute("v"))
+    }
+
+    fn id(&self) -> &str {
+        self.attribute("id").unwrap_or("?")
+    }
+}
+
+// A road for cars, with its rank in `RANKS` and its lanes and speed, as seen from its first node: the lanes going out are those forward, in the order of its nodes, and the lanes coming in those backward.
+struct Way {
+    id: String,
+    rank: usize,
+    road: Road,
+}
+
+// From a junction, along the stretches of road that lead out of it, to the next junction, or to the end of the road. `way` is the way of the first stretch, which turn restrictions refer to, `forward` whether that stretch leads out in the order of the way's nodes, and `end` the node it ends at.
+struct Branch {
+    way: usize,
+    forward: bool,
+    end: usize,
+    arm: Airt,
+}
+
+pub fn read(text: &str) -> Result<Map, String> {
+    let root = parse(text)?;
+    if root.name != "osm" {
+        return Err(format!(
+            "Expected OpenStreetMap XML, starting with <osm>, got <{}>",
+            root.name
+        ));
+    }
+
+    // The nodes' coordinates, with longitude scaled so that distances east and north are alike, near enough, across a small map, and whether each is a roundabout. Ways refer to nodes by id, so each id's node is looked up once.
+    let mut ids = Vec::new();
+    let mut index = HashMap::new();
+    let mut coordinates = Vec::new();
+    let mut roundabouts = Vec::new();
+    for node in root.children.iter().filter(|child| child.name == "node") {
+        let coordinate = |key: &str| {
+            node.attribute(key)
+                .and_then(|value| value.parse::<f64>().ok())
+                .ok_or_else(|| format!("Node {} needs a {}", node.id(), key))
+        };
+        index.entry(node.id()).or_insert(ids.len());
+        ids.push(node.id());
+        coordinates.push((coordinate("lon")?, coordinate("lat")?));
+        roundabouts.push(node.tag("highway") == Some("mini_roundabout"));
+    }
+    let latitude =
+        coordinates.iter().map(|&(_, lat)| lat).sum::<f64>() / coordinates.len().max(1) as f64;
+    let scale = (latitude * PI / 180.0).cos();
+
+    // Each node's neighbours along the roads, the way each stretch belongs to, and whether it leads to the neighbour in the order of the way's nodes.
+    let mut ways = Vec::new();
+    let mut left_out = Vec::new();
+    let mut neighbours: Vec<Vec<(usize, usize, bool)>> = vec![Vec::new(); ids.len()];
+    for element in root.children.iter().filter(|child| child.name == "way") {
+        let Some(way) = way(element, &mut left_out)? else {
+            continue;
+        };
+        let mut previous: Option<usize> = None;
+        for nd in element.children.iter().filter(|child| child.name == "nd") {
+            let reference = nd.attribute("ref").unwrap_or("?");
+            let &node = index.get(reference).ok_or_else(|| {
+                format!(
+                    "Way {} refers to node {}, which isn't in the file",
+                    way.id, reference
+                )
+            })?;
+            if let Some(previous) = previous.filter(|&previous| previous != node) {
+                neighbours[previous].push((node, ways.len(), true));
+                neighbours[node].push((previous, ways.len(), false));
+            }
+            previous = Some(node);
+        }
+        ways.push(way);
+    }
+
+    let junctions = (0..ids.len())
+        .filter(|&node| neighbours[node].len() >= 3)
+        .collect::<Vec<usize>>();
+    if junctions.is_empty() {
+        return Err("There are no junctions, where three or more roads meet".to_string());
+    }
+    // The site at each node that's a junction.
+    let mut site_at = vec![None; ids.len()];
+    for (site, &junction) in junctions.iter().enumerate() {
+        site_at[junction] = Some(site);
+    }
+
+    let mut sites = Vec::new();
+    let mut branches = Vec::new();
+    for &junction in &junctions {
+        let mut from_here = Vec::new();
+        for &(first, way, forward) in &neighbours[junction] {
+            let end = follow(junction, first, &neighbours, &ids)?;
+            let (x, y) = coordinates[junction];
+            let (end_x, end_y) = coordinates[end];
+            let arm = map::heading((end_x - x) * scale, end_y - y).ok_or_else(|| {
+                format!(
+                    "The road from node {} toward node {} runs too far off north-south or east-west, and only roads at right angles to each other are supported",
+                    ids[junction], ids[end]
+                )
+            })?;
+            if let Some(other) = from_here.iter().find(|other: &&Branch| other.arm == arm) {
+                return Err(format!(
+                    "Node {} has two roads going {}, on ways {} and {}",
+                    ids[junction],
+                    map::compass(arm),
+                    ways[other.way].id,
+                    ways[way].id
+                ));
+            }
+            from_here.push(Branch {
+                way,
+                forward,
+                end,
+                arm,
+            });
+        }
+
+        sites.push(Site {
+            name: format!("node {}", ids[junction]),
+            x: coordinates[junction].0 * scale,
+            y: coordinates[junction].1,
+            roundabout: roundabouts[junction],
+            arms: from_here.iter().map(|branch| branch.arm).collect(),
+            road: Road::default(),
+            edge_roads: from_here
+                .iter()
+                .filter(|branch| site_at[branch.end].is_none())
+                .map(|branch| (branch.arm, road(branch, &from_here, &ways)))
+                .collect(),
+            turns: Vec::new(),
+        });
+        branches.push(from_here);
+    }
+
+    // Each road between two junctions, once, from the first of them, with how it is at either end.
+    let mut streets = Vec::new();
+    for (from, from_here) in branches.iter().enumerate() {
+        for branch in from_here {
+            let Some(to) = site_at[branch.end] else {
+                continue;
+            };
+            if to < from {
+                continue;
+            }
+            let back = branches[to]
+                .iter()
+                .find(|back| back.arm == branch.arm.opposite() && back.end == junctions[from])
+                .ok_or_else(|| {
+                    format!(
+                        "The road between nodes {} and {} bends too far, leaving one going {} and the other not going {}",
+                        ids[junctions[from]],
+                        ids[junctions[to]],
+                        map::compass(branch.arm),
+                        map::compass(branch.arm.opposite())
+                    )
+                })?;
+            let ends = [
+                road(branch, from_here, &ways),
+                road(back, &branches[to], &ways),
+            ];
+            let [here, there] = ends;
+            if (here.lanes_in, here.lanes_out, here.speed)
+                != (there.lanes_out, there.lanes_in, there.speed)
+            {
+                return Err(format!(
+                    "The road between nodes {} and {} changes its lanes or speed limit on the way, which isn't supported",
+                    ids[junctions[from]], ids[junctions[to]]
+                ));
+            }
+            streets.push(Street { from, to, ends });
+        }
+    }
+
+    for relation in root
+        .children
+        .iter()
+        .filter(|child| child.name == "relation")
+    {
+        restrict(relation, &mut sites, &branches, &site_at, &ways, &index)?;
+    }
+
+    Ok(Map {
+        sites,
+        streets,
+        warnings: warnings(&left_out),
+    })
+}
+
+// A warning for each reason ways were left out, given the reason for each way and its id, listing the ways it applies to.
+fn warnings(left_out: &[(String, String)]) -> Vec<String> {
+    let mut reasons: Vec<&str> = Vec::new();
+    for (reason, _) in left_out {
+        if !reasons.contains(&reason.as_str()) {
+            reasons.push(reason);
+        }
+    }
+    reasons
+        .into_iter()
+        .map(|reason| {
+            let ids = left_out
+                .iter()
+                .filter(|(other, _)| other == reason)
+                .map(|(_, id)| id.as_str())
+                .collect::<Vec<&str>>();
+            match ids.as_slice() {
+                [id] => format!("{}, so way {} is left out", reason, id),
+                [rest @ .., last] => format!(
+                    "{}, so ways {} and {} are left out",
+                    reason,
+                    rest.join(", "),
+                    last
+                ),
+                [] => unreachable!("No ways for the reason"),
+            }
+        })
+        .collect()
+}
+
+// The way, if it's a road for cars that's simulated, with how many lanes it has each way and its speed limit as a percentage of `USUAL_SPEED`. If it's one that's left out with a warning, the reason is added to those for the others, along with its id.
+fn way(element: &Element, left_out: &mut Vec<(String, String)>) -> Result<Option<Way>, String> {
+    let id = element.id().to_string();
+    let Some(highway) = element.tag("highway") else {
+        return Ok(None);
+    };
+    if IGNORED.contains(&highway) || element.tag("area") == Some("yes") {
+        return Ok(None);
+    }
+    let kind = highway.strip_suffix("_link").unwrap_or(highway);
+    let Some(rank) = RANKS.iter().position(|names| names.contains(&kind)) else {
+        left_out.push((
+            format!("Roads of type highway={} aren't supported", highway),
+            id,
+        ));
+        return Ok(None);
+    };
+    if let Some(junction @ ("roundabout" | "circular")) = element.tag("junction") {
+        return Err(format!(
+            "Way {} is part of a roundabout drawn as a ring of ways (junction={}), and only roundabouts at a single node, tagged highway=mini_roundabout, are supported",
+            id, junction
+        ));
+    }
+    if let Some(oneway @ ("yes" | "true" | "1" | "-1" | "reversible")) = element.tag("oneway") {
+        if kind != highway {
+            left_out.push((
+                format!(
+                    "One-way roads of type highway={}, such as slip roads, aren't supported",
+                    highway
+                ),
+                id,
+            ));
+            return Ok(None);
+        }
+        return Err(format!(
+            "Way {} is one-way (oneway={}), and only two-way roads are supported",
+            id, oneway
+        ));
+    }
+
+    // The lanes each way, as given, or whatever `lanes` leaves once the other way and any lane in the middle shared by both have theirs, or half of `lanes`, or one.
+    let lanes = |key: &str| {
+        element
+            .tag(key)
+            .map(|lanes| {
+                lanes.parse::<i32>().map_err(|_| {
+                    format!(
+                        "Way {} has {}={}, which isn't a whole number",
+                        id, key, lanes
+                    )
+                })
+            })
+            .transpose()
+    };
+    let both_ways = lanes("lanes:both_ways")?.unwrap_or(0);
+    let shared = lanes("lanes")?.map(|total| total - both_ways);
+    let (forward, backward) = match (lanes("lanes:forward")?, lanes("lanes:backward")?, shared) {
+        (Some(forward), Some(backward), _) => (forward, backward),
+        (Some(forward), None, Some(shared)) => (forward, shared - forward),
+        (None, Some(backward), Some(shared)) => (shared - backward, backward),
+        (forward, backward, None) => (forward.unwrap_or(1), backward.unwrap_or(1)),
+        (_, _, Some(shared)) if shared % 2 != 0 => {
+            return Err(format!(
+                "Way {} has {} lanes, which can't be shared equally between its two directions, and doesn't say how many go each way with lanes:forward and lanes:backward",
+                id, shared
+            ));
+        }
+        (_, _, Some(shared)) => (shared / 2, shared / 2),
+    };
+    if forward < 1 || backward < 1 {
+        return Err(format!(
+            "Way {} has {} lanes forward and {} backward, and only roads with at least one lane each way are supported",
+            id, forward, backward
+        ));
+    }
+    let mut road = Road {
+        lanes_in: backward,
+        lanes_out: forward,
+        ..Road::default()
+    };
+    if let Some(maxspeed) = element.tag("maxspeed") {
+        let (number, factor) = match maxspeed.strip_suffix(" mph") {
+            Some(number) => (number, 1.609),
+            None => (maxspeed, 1.0),
+        };
+        let speed = number.parse::<f64>().map_err(|_| {
+            format!(
+                "Way {} has maxspeed={}, and only numbers, in km/h or with \" mph\", are supported",
+                id, maxspeed
+            )
+        })? * factor;
+        road.speed = (speed * 100.0 / USUAL_SPEED).round() as i32;
+        if !(25..=150).contains(&road.speed) {
+            return Err(format!(
+                "Way {} has maxspeed={}, and only speed limits from {} to {} km/h are supported",
+                id,
+                maxspeed,
+                USUAL_SPEED / 4.0,
+                USUAL_SPEED * 1.5
+            ));
+        }
+    }
+    Ok(Some(Way { id, rank, road }))
+}
+
+// The road along a branch out of a junction, given all the branches out of it, with the lanes coming in and going out that way: the roads of the highest rank there are major, and the rest minor.
+fn road(branch: &Branch, from_here: &[Branch], ways: &[Way]) -> Road {
+    let top = from_here
+        .iter()
+        .map(|other| ways[other.way].rank)
+        .min()
+        .unwrap_or(0);
+    let road = ways[branch.way].road;
+    Road {
+        minor: ways[branch.way].rank > top,
+        ..if branch.forward {
+            road
+        } else {
+            road.reversed()
+        }
+    }
+}
+
+// Follows the road out of a junction through the given neighbour, past any nodes where only two stretches meet, to the next junction or the end of the road, and returns where it stops.
+fn follow(
+    junction: usize,
+    first: usize,
+    neighbours: &[Vec<(usize, usize, bool)>],
+    ids: &[&str],
+) -> Result<usize, String> {
+    let (mut previous, mut node) = (junction, first);
+    while neighbours[node].len() == 2 {
+        let next = neighbours[node]
+            .iter()
+            .map(|&(next, _, _)| next)
+            .find(|&next| next != previous)
+            .unwrap_or(previous);
+        (previous, node) = (node, next);
+        if node == junction {
+            return Err(format!(
+                "A road from node {} loops back to it, which isn't supported",
+                ids[junction]
+            ));
+        }
+    }
+    Ok(node)
+}
+
+// Applies a turn restriction, from one way, at a junction, to another. Restrictions on U-turns are already met, since there are none, and those that only apply to other vehicles are left out.
+fn restrict(
+    relation: &Element,
+    sites: &mut [Site],
+    branches: &[Vec<Branch>],
+    site_at: &[Option<usize>],
+    ways: &[Way],
+    index: &HashMap<&str, usize>,
+) -> Result<(), String> {
+    if relation.tag("type") != Some("restriction") {
+        return Ok(());
+    }
+    let Some(restriction) = relation
+        .tag("restriction")
+        .or_else(|| relation.tag("restriction:motorcar"))
+    else {
+        return Ok(());
+    };
+    let id = relation.id();
+    let member = |role: &str| {
+        relation
+            .children
+            .iter()
+            .find(|child| child.name == "member" && child.attribute("role") == Some(role))
+            .ok_or_else(|| format!("Turn restriction {} has no \"{}\" member", id, role))
+    };
+    let via = member("via")?;
+    if via.attribute("type") != Some("node") {
+        return Err(format!(
+            "Turn restriction {} goes via a way, and only restrictions at a node are supported",
+            id
+        ));
+    }
+    let via = via.attribute("ref").unwrap_or("?");
+    let site = index
+        .get(via)
+        .and_then(|&node| site_at[node])
+        .ok_or_else(|| {
+            format!(
+                "Turn restriction {} is at node {}, which isn't a junction",
+                id, via
+            )
+        })?;
+    let arm = |role: &str| -> Result<Airt, String> {
+        let way = member(role)?.attribute("ref").unwrap_or("?");
+        let arms = branches[site]
+            .iter()
+            .filter(|branch| ways[branch.way].id == way)
+            .map(|branch| branch.arm)
+            .collect::<Vec<Airt>>();
+        match arms.as_slice() {
+            [arm] => Ok(*arm),
+            [] => Err(format!(
+                "Turn restriction {} is from or to way {}, which doesn't meet node {}",
+                id, way, via
+            )),
+            _ => Err(format!(
+                "Turn restriction {} is from or to way {}, which goes through node {} rather than ending there",
+                id, way, via
+            )),
+        }
+    };
+    let (from, to) = (arm("from")?, arm("to")?);
+    if from == to {
+        return Ok(());
+    }
+
+    let site = &mut sites[site];
+    let others = site
+        .arms
+        .iter()
+        .copied()
+        .filter(|&arm| arm != from)
+        .collect::<Vec<Airt>>();
+    let allowed = match site.turns.iter().position(|&(arm, _)| arm == from) {
+        Some(index) => &mut site.turns[index].1,
+        None => {
+            site.turns.push((from, others));
+            &mut site.turns.last_mut().expect("No turns").1
+        }
+    };
+    match restriction {
+        "no_left_turn" | "no_right_turn" | "no_straight_on" => allowed.retain(|&arm| arm != to),
+        "only_left_turn" | "only_right_turn" | "only_straight_on" => {
+            allowed.retain(|&arm| arm == to)
+        }
+        "no_u_turn" | "only_u_turn" => {}
+        _ => {
+            return Err(format!(
+                "Turn restriction {} is restriction={}, which isn't supported",
+                id, restriction
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Reads the elements of an XML document into a tree, and returns the root. quick-xml does the parsing, and everything but the elements and their attributes, such as text, comments and declarations, is skipped.
+fn parse(text: &str) -> Result<Element, String> {
+    let line = |at: u64| text[..(at as usize).min(text.len())].matches('\n').count() + 1;
+    let mut reader = Reader::from_str(text);
+    let mut stack: Vec<Element> = Vec::new();
+    let mut root = None;
+    loop {
+        let at = reader.buffer_position();
+        let (element, closed) = match reader.read_event() {
+            Ok(Event::Start(start)) => (element(&start, line(at))?, false),
+            Ok(Event::Empty(start)) => (element(&start, line(at))?, true),
+            Ok(Event::End(_)) => (stack.pop().expect("quick-xml matches end tags"), true),
+            Ok(Event::Eof) => break,
+            Ok(_) => continue,
+            Err(e) => {
+                return Err(format!(
+                    "Invalid XML at line {}: {}",
+                    line(reader.error_position()),
+                    e
+                ));
+            }
+        };
+        if !closed {
+            stack.push(element);
+            continue;
+        }
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(element),
+            None if root.is_none() => root = Some(element),
+            None => {
+                return Err(format!(
+                    "Invalid XML at line {}: <{}> comes after the end of the root element",
+                    line(at),
+                    element.name
+                ));
+            }
+        }
+    }
+    if let Some(element) = stack.last() {
+        return Err(format!("Invalid XML: <{}> is never closed", element.name));
+    }
+    root.ok_or_else(|| "The file has no XML elements".to_string())
+}
+
+// A start tag's name and attributes, on the given line, with the entities in the attributes' values replaced.
+fn element(start: &BytesStart, line: usize) -> Result<Element, String> {
+    let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+    let error =
+        |e: &dyn std::fmt::Display| format!("Invalid XML at line {}, in <{}>: {}", line, name, e);
+    let mut attributes = Vec::new();
+    for attribute in start.attributes() {
+        let attribute = attribute.map_err(|e| error(&e))?;
+        let value = attribute.unescape_value().map_err(|e| error(&e))?;
+        attributes.push((
+            String::from_utf8_lossy(attribute.key.as_ref()).into_owned(),
+            value.into_owned(),
+        ));
+    }
+    Ok(Element {
+        name,
+        attributes,
+        children: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A junction at node 1, with a road north to south through it on way 10, and one west to east on way 11, with the given tags on the first.
+    fn crossroads(tags: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6">
+  <node id="1" lat="51.5" lon="0.0"/>
+  <node id="2" lat="51.501" lon="0.0"/>
+  <node id="3" lat="51.499" lon="0.0"/>
+  <node id="4" lat="51.5" lon="-0.0015"/>
+  <node id="5" lat="51.5" lon="0.0015"/>
+  <way id="10">
+    <nd ref="2"/><nd ref="1"/><nd ref="3"/>
+    <tag k="highway" v="primary"/>{}
+  </way>
+  <way id="11">
+    <nd ref="4"/><nd ref="1"/><nd ref="5"/>
+    <tag k="highway" v="residential"/>
+  </way>
+</osm>"#,
+            tags
+        )
+    }
+
+    #[test]
+    fn reads_a_junction() {
+        let map = read(&crossroads(
+            r#"<tag k="lanes" v="4"/><tag k="maxspeed" v="30 mph"/>"#,
+        ))
+        .unwrap();
+        assert_eq!(map.sites.len(), 1);
+        assert!(map.streets.is_empty());
+        let site = &map.sites[0];
+        assert_eq!(site.name, "node 1");
+        assert_eq!(site.arms.len(), 4);
+        let road = |arm: Airt| {
+            site.edge_roads
+                .iter()
+                .find(|&&(edge, _)| edge == arm)
+                .map(|&(_, road)| road)
+                .unwrap()
+        };
+        // The primary road has two lanes each way at 30 mph, and the residential one is minor, with one.
+        assert_eq!(road(Airt::Up).lanes_in, 2);
+        assert_eq!(road(Airt::Down).lanes_out, 2);
+        assert_eq!(road(Airt::Up).speed, 97);
+        assert!(!road(Airt::Up).minor);
+        assert_eq!(road(Airt::Left).lanes_in, 1);
+        assert!(road(Airt::Right).minor);
+    }
+
+    #[test]
+    fn reads_entities_and_skips_comments() {
+        let text = crossroads(r#"<!-- a comment --><tag k="name" v="Fish &amp; Chips &#x41;"/>"#);
+        let root = parse(&text).unwrap();
+        let way = root
+            .children
+            .iter()
+            .find(|child| child.id() == "10")
+            .unwrap();
+        assert_eq!(way.tag("name"), Some("Fish & Chips A"));
+
+        let text = crossroads(r#"<![CDATA[<x>]]><tag k="note" v="a > b, 'c'"/>"#);
+        let root = parse(&text).unwrap();
+        let way = root
+            .children
+            .iter()
+            .find(|child| child.id() == "10")
+            .unwrap();
+        assert_eq!(way.tag("note"), Some("a > b, 'c'"));
+    }
+
+    #[test]
+    fn rejects_malformed_xml() {
+        for (text, error) in [
+            ("", "The file has no XML elements"),
+            ("<osm>", "Invalid XML: <osm> is never closed"),
+            (
+                "<osm>\n</way>",
+                "Invalid XML at line 2: ill-formed document",
+            ),
+            ("</osm>", "Invalid XML at line 1: ill-formed document"),
+            ("<osm/><osm/>", "Invalid XML at line 1: <osm> comes after"),
+            (
+                "<osm><node id=1/></osm>",
+                "Invalid XML at line 1, in <node>: position 8: attribute value must be enclosed",
+            ),
+            (
+                "<osm>\n<node id=\"1/></osm>",
+                "Invalid XML at line 2: syntax error",
+            ),
+            ("<osm><node id/></osm>", "Invalid XML at line 1, in <node>"),
+            (
+                "<osm><node id='1' id='2'/></osm>",
+                "Invalid XML at line 1, in <node>: position 12: duplicated attribute",
+            ),
+            (
+                "<osm><node id='&nbsp;'/></osm>",
+                "Invalid XML at line 1, in <node>: at 1..5: unrecognized entity `nbsp`",
+            ),
+            ("<osm><!-- open", "Invalid XML at line 1: syntax error"),
+            ("<osm><node", "Invalid XML at line 1: syntax error"),
+        ] {
+            let e = parse(text).err().unwrap();
+            assert!(e.starts_with(error), "{:?} gave {:?}", text, e);
+        }
+        assert_eq!(
+            read("<map/>").err().unwrap(),
+            "Expected OpenStreetMap XML, starting with <osm>, got <map>"
+        );
+    }
+
+    #[test]
+    fn rejects_missing_attributes_and_references() {
+        let text = crossroads("").replace(r#"<node id="5" lat="51.5" lon="0.0015"/>"#, "");
+        assert_eq!(
+            read(&text).err().unwrap(),
+            "Way 11 refers to node 5, which isn't in the file"
+        );
+        let text = crossroads("").replace(r#"lat="51.499" "#, "");
+        assert_eq!(read(&text).err().unwrap(), "Node 3 needs a lat");
+        let text = crossroads("").replace(
+            "</osm>",
+            r#"<relation id="20"><tag k="type" v="restriction"/><tag k="restriction" v="no_left_turn"/><member type="way" ref="10" role="from"/></relation></osm>"#,
+        );
+        assert_eq!(
+            read(&text).err().unwrap(),
+            "Turn restriction 20 has no \"via\" member"
+        );
+    }
+
+    #[test]
+    fn rejects_roads_it_cant_simulate() {
+        for (tags, error) in [
+            (r#"<tag k="oneway" v="yes"/>"#, "Way 10 is one-way"),
+            (
+                r#"<tag k="lanes" v="3"/>"#,
+                "Way 10 has 3 lanes, which can't be shared equally",
+            ),
+            (
+                r#"<tag k="lanes" v="two"/>"#,
+                "Way 10 has lanes=two, which isn't a whole number",
+            ),
+            (
+                r#"<tag k="lanes" v="3"/><tag k="lanes:forward" v="3"/>"#,
+                "Way 10 has 3 lanes forward and 0 backward",
+            ),
+            (
+                r#"<tag k="lanes:forward" v="many"/>"#,
+                "Way 10 has lanes:forward=many, which isn't a whole number",
+            ),
+            (
+                r#"<tag k="maxspeed" v="fast"/>"#,
+                "Way 10 has maxspeed=fast, and only numbers",
+            ),
+            (
+                r#"<tag k="maxspeed" v="200"/>"#,
+                "Way 10 has maxspeed=200, and only speed limits",
+            ),
+            (
+                r#"<tag k="junction" v="roundabout"/>"#,
+                "Way 10 is part of a roundabout",
+            ),
+        ] {
+            let e = read(&crossroads(tags)).err().unwrap();
+            assert!(e.starts_with(error), "{:?} gave {:?}", tags, e);
+        }
+        let text = crossroads("").replace(r#"<nd ref="4"/><nd ref="1"/><nd ref="5"/>"#, "");
+        assert_eq!(
+            read(&text).err().unwrap(),
+            "There are no junctions, where three or more roads meet"
+        );
+    }
+
+    #[test]
+    fn reads_the_lanes_each_way() {
+        // Way 10 runs from north to south, so the lanes forward come into the junction from the north and go out to the south, and those backward the other way round.
+        for (tags, forward, backward) in [
+            ("", 1, 1),
+            (r#"<tag k="lanes" v="2"/>"#, 1, 1),
+            (
+                r#"<tag k="lanes:forward" v="2"/><tag k="lanes:backward" v="3"/>"#,
+                2,
+                3,
+            ),
+            (r#"<tag k="lanes:forward" v="2"/>"#, 2, 1),
+            (
+                r#"<tag k="lanes" v="3"/><tag k="lanes:backward" v="2"/>"#,
+                1,
+                2,
+            ),
+            (
+                r#"<tag k="lanes" v="5"/><tag k="lanes:both_ways" v="1"/><tag k="lanes:forward" v="3"/>"#,
+                3,
+                1,
+            ),
+            (
+                r#"<tag k="lanes" v="5"/><tag k="lanes:both_ways" v="1"/>"#,
+                2,
+                2,
+            ),
+        ] {
+            let map = read(&crossroads(tags)).unwrap();
+            let road = |arm: Airt| {
+                map.sites[0]
+                    .edge_roads
+                    .iter()
+                    .find(|&&(edge, _)| edge == arm)
+                    .map(|&(_, road)| (road.lanes_in, road.lanes_out))
+                    .unwrap()
+            };
+            assert_eq!(road(Airt::Up), (forward, backward), "{}", tags);
+            assert_eq!(road(Airt::Down), (backward, forward), "{}", tags);
+        }
+    }
+
+    #[test]
+    fn leaves_out_roads_it_doesnt_simulate() {
+        // The road east is a two-way link to the primary road, and where it ends, there's a motorway and a one-way slip road onto it.
+        let text = crossroads("")
+            .replace(r#"v="residential""#, r#"v="primary_link""#)
+            .replace(
+                "</osm>",
+                r#"<node id="6" lat="51.5" lon="0.003"/>
+  <way id="12"><nd ref="5"/><nd ref="6"/><tag k="highway" v="motorway"/></way>
+  <way id="13"><nd ref="5"/><nd ref="6"/><tag k="highway" v="trunk_link"/><tag k="oneway" v="yes"/></way>
+  <way id="14"><nd ref="6"/><nd ref="5"/><tag k="highway" v="motorway"/></way>
+</osm>"#,
+            );
+        let map = read(&text).unwrap();
+        assert_eq!(map.sites.len(), 1);
+        assert!(map.sites[0].edge_roads.iter().all(|(_, road)| !road.minor));
+        assert_eq!(
+            map.warnings,
+            [
+                "Roads of type highway=motorway aren't supported, so ways 12 and 14 are left out",
+                "One-way roads of type highway=trunk_link, such as slip roads, aren't supported, so way 13 is left out",
+            ]
+        );
+    }
+}
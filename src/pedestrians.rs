@@ -0,0 +1,162 @@
+// People on foot, who cross each arm of the intersection at a zebra crossing a little way back from it. They wait on the sidewalk until no car is on the crossing or too close to stop before it, then walk straight across without stopping, and cars have to let them. Distances are in lane widths.
+
+use rand::{Rng, rngs::StdRng};
+use sdl2::{pixels::Color, rect::Rect};
+
+use crate::{
+    collision::Rectangle,
+    types::{Airt, Dimensions},
+};
+
+// How far the crossing is from the intersection, and how wide it is. The gap leaves room for a short vehicle that's come out of the intersection to stop for someone on the crossing without blocking the intersection.
+const CROSSING_OFFSET: f64 = 1.0;
+const CROSSING_WIDTH: f64 = 1.0;
+
+const CURB_DISTANCE: f64 = 0.5; // How far back from the edge of the road a pedestrian waits, and where they leave the crossing on the far side.
+const WALKING_SPEED: f64 = 1.0 / 24.0; // Per tick.
+const SIZE: f64 = 0.375; // Of a pedestrian's body, which is square.
+
+// The chance on each tick that a pedestrian turns up at one of the crossings in the simulation. `W` sends one along whenever you like.
+pub const RATE: f64 = 1.0 / 240.0;
+
+// The stretch of road a pedestrian has yet to cross, and whether they're still waiting to step out onto it.
+#[derive(Clone)]
+pub struct Zone {
+    pub area: Rectangle,
+    pub waiting: bool,
+}
+
+#[derive(Clone)]
+pub struct Pedestrian {
+    pub arm: Airt, // The side of the intersection their crossing is on.
+    side: f64, // 1.0 if they cross in the direction of the crossing's heading, -1.0 if against it.
+    lateral: f64, // How far to one side of the middle of the crossing they walk, in pixels.
+    walked: f64, // How far they've come from where they waited, in pixels.
+    pub birthday: u64,
+    pub waiting: bool,
+    pub hit: bool, // Whether a car has run into them.
+}
+
+// The part of the road that the crossing on the given arm covers. It's heading across the road, from the side where cars come in to the side where they go out, and it's off the middle of the road toward the wider of them.
+pub fn crossing(arm: Airt, dimensions: &Dimensions) -> Rectangle {
+    let lane_width = dimensions.lane_width as f64;
+    let (half_in, half_out) = (
+        dimensions.half_road_in(arm) as f64,
+        dimensions.half_road_out(arm) as f64,
+    );
+    let (out_x, out_y) = arm.unit();
+    let distance =
+        dimensions.reach(arm) as f64 + (CROSSING_OFFSET + CROSSING_WIDTH / 2.0) * lane_width;
+    let off = (half_out - half_in) / 2.0 - dimensions.stagger(arm) as f64;
+    Rectangle {
+        center: (
+            dimensions.half_width as f64 + out_x * distance - out_y * off,
+            dimensions.half_height as f64 + out_y * distance + out_x * off,
+        ),
+        heading: (-out_y, out_x),
+        half_length: (half_in + half_out) / 2.0,
+        half_width: CROSSING_WIDTH * lane_width / 2.0,
+    }
+}
+
+impl Pedestrian {
+    // Someone waiting on either side of a random crossing.
+    pub fn spawn(rng: &mut StdRng, birthday: u64, dimensions: &Dimensions) -> Self {
+        let lane_width = dimensions.lane_width as f64;
+        let lateral = (CROSSING_WIDTH - SIZE) * lane_width / 2.0;
+        let arms = dimensions.arms();
+        Pedestrian {
+            arm: arms[rng.random_range(0..arms.len())],
+            side: if rng.random_bool(0.5) { 1.0 } else { -1.0 },
+            lateral: rng.random_range(-lateral..=lateral),
+            walked: 0.0,
+            birthday,
+            waiting: true,
+            hit: false,
+        }
+    }
+
+    // How far along the crossing they are from its middle, in the direction of its heading.
+    fn along(&self, dimensions: &Dimensions) -> f64 {
+        let start = crossing(self.arm, dimensions).half_length
+            + CURB_DISTANCE * dimensions.lane_width as f64;
+        self.side * (self.walked - start)
+    }
+
+    pub fn body(&self, dimensions: &Dimensions) -> Rectangle {
+        let crossing = crossing(self.arm, dimensions);
+        let (x, y) = crossing.center;
+        let (along_x, along_y) = crossing.heading;
+        let (across_x, across_y) = self.arm.unit();
+        let along = self.along(dimensions);
+        Rectangle {
+            center: (
+                x + along * along_x + self.lateral * across_x,
+                y + along * along_y + self.lateral * across_y,
+            ),
+            heading: crossing.heading,
+            half_length: SIZE * dimensions.lane_width as f64 / 2.0,
+            half_width: SIZE * dimensions.lane_width as f64 / 2.0,
+        }
+    }
+
+    // The whole crossing while they wait to step out, and then the rest of it ahead of them, across its full width. None once they're off the road.
+    pub fn zone(&self, dimensions: &Dimensions) -> Option<Zone> {
+        let crossing = crossing(self.arm, dimensions);
+        let half_length = crossing.half_length;
+        let far = self.side * half_length;
+        let near = if self.waiting {
+            -far
+        } else {
+            (self.along(dimensions) - self.side * SIZE * dimensions.lane_width as f64 / 2.0)
+                .clamp(-half_length, half_length)
+        };
+        if (far - near) * self.side <= 0.0 {
+            return None;
+        }
+        let middle = (near + far) / 2.0;
+        let (x, y) = crossing.center;
+        let (along_x, along_y) = crossing.heading;
+        Some(Zone {
+            area: Rectangle {
+                center: (x + middle * along_x, y + middle * along_y),
+                half_length: (far - near).abs() / 2.0,
+                ..crossing
+            },
+            waiting: self.waiting,
+        })
+    }
+
+    pub fn walk(&mut self, dimensions: &Dimensions) {
+        self.walked += WALKING_SPEED * dimensions.lane_width as f64;
+    }
+
+    // Whether they've reached the sidewalk on the far side.
+    pub fn has_crossed(&self, dimensions: &Dimensions) -> bool {
+        self.side * self.along(dimensions)
+            >= crossing(self.arm, dimensions).half_length
+                + CURB_DISTANCE * dimensions.lane_width as f64
+    }
+
+    pub fn draw(
+        &self,
+        canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
+        dimensions: &Dimensions,
+    ) {
+        let body = self.body(dimensions);
+        let size = 2.0 * body.half_length;
+        canvas.set_draw_color(if self.waiting {
+            Color::RGB(200, 40, 160)
+        } else {
+            Color::RGB(40, 40, 200)
+        });
+        canvas
+            .fill_rect(Rect::new(
+                (body.center.0 - body.half_length).round() as i32,
+                (body.center.1 - body.half_width).round() as i32,
+                size.round() as u32,
+                size.round() as u32,
+            ))
+            .expect("Failed to draw pedestrian");
+    }
+}
@@ -0,0 +1,451 @@
+// The routes through the intersection, as data. Each one is the line that the top left corner of a car's square follows, from where it comes in at the edge of the window, in its lane, to where it goes out, made up of straight lines and circular arcs. They're built once from the `Dimensions`, and a car finds where it is on its route from how far it's travelled along it.
+
+use std::f64::consts::{FRAC_PI_2, PI};
+
+use crate::types::{Airt, Dimensions, Layout};
+
+// The radii of the arcs that cars follow through the intersection, in lane widths, for left and right turns from the driver's point of view. Right turns are as tight as they can be without a car starting to turn before it reaches the intersection, and cyclists in the bike lane, closer to the edge, turn right tighter still. Left turns are wider, but a wider arc would cut further across the middle of the intersection, where the left-turning cars from all four sides meet.
+const LEFT_TURN_RADIUS: f64 = 1.5;
+const RIGHT_TURN_RADIUS: f64 = 1.0;
+
+// The radius of the turn back round for a U-turn, in lane widths, unless the intersection is too small for it. It's as wide as a left turn. A half circle that wide would take a car further across than the lanes it turns between are apart, so it swings out to its right first, as a long vehicle has to.
+const U_TURN_RADIUS: f64 = 1.5;
+
+#[derive(Clone, Copy)]
+enum Segment {
+    Line {
+        from: (f64, f64),
+        heading: (f64, f64), // Unit vector.
+        length: f64,
+    },
+    // Starting off the given way and turning through the given angle in radians, clockwise on screen, i.e. to the right, if it's positive.
+    Arc {
+        from: (f64, f64),
+        heading: (f64, f64),
+        radius: f64,
+        angle: f64,
+    },
+}
+
+impl Segment {
+    fn length(&self) -> f64 {
+        match *self {
+            Segment::Line { length, .. } => length,
+            Segment::Arc { radius, angle, .. } => radius * angle.abs(),
+        }
+    }
+
+    // Where the segment is the given distance along it, and which way it's going there.
+    fn at(&self, distance: f64) -> ((f64, f64), (f64, f64)) {
+        match *self {
+            Segment::Line { from, heading, .. } => (
+                (from.0 + distance * heading.0, from.1 + distance * heading.1),
+                heading,
+            ),
+            Segment::Arc {
+                from,
+                heading,
+                radius,
+                angle,
+            } => {
+                // The center is off to the side it turns toward, and the point on the arc swings round it as the heading turns.
+                let side = angle.signum();
+                let right = |(x, y): (f64, f64)| (-y, x);
+                let (sin, cos) = (side * distance / radius).sin_cos();
+                let turned = (
+                    heading.0 * cos - heading.1 * sin,
+                    heading.0 * sin + heading.1 * cos,
+                );
+                let (start, now) = (right(heading), right(turned));
+                (
+                    (
+                        from.0 + side * radius * (start.0 - now.0),
+                        from.1 + side * radius * (start.1 - now.1),
+                    ),
+                    turned,
+                )
+            }
+        }
+    }
+}
+
+// One way through the intersection, from a given lane on a given side, or the bike lane if `lane` is None, together with the speed of that lane.
+pub struct Route {
+    pub start: Airt,
+    pub end: Airt,
+    pub lane: Option<i32>,
+    pub speed: i32,
+    segments: Vec<Segment>,
+}
+
+impl Route {
+    // Every route for the given dimensions: each of the movements the layout has roads for, from each lane of the road it comes in along, and from the bike lane if there is one.
+    pub fn table(dimensions: &Dimensions) -> Vec<Route> {
+        dimensions
+            .movements()
+            .into_iter()
+            .flat_map(|(start, end)| {
+                (0..dimensions.lanes_in(start.opposite()))
+                    .map(Some)
+                    .chain((dimensions.bike_lane > 0).then_some(None))
+                    .map(move |lane| Route::new(start, end, lane, dimensions))
+            })
+            .collect()
+    }
+
+    // At a crossroads, straight on to the turn, round it on a circular arc, then straight on again. The car comes out as far to the right of the middle of the road it turns into as it was of the one it came from, or in the outermost lane, if that road has fewer going out. Going straight on into a road with fewer lanes, it moves over to the right lane on the way across, on a pair of arcs that bend one way and then back. A U-turn comes back out the same distance to the right of the middle of the road as it went in.
+    fn new(start: Airt, end: Airt, lane: Option<i32>, dimensions: &Dimensions) -> Route {
+        let lane_width = dimensions.lane_width as f64;
+        let half_lane = lane_width / 2.0;
+        let (in_x, in_y) = start.unit();
+        let (out_x, out_y) = end.unit();
+
+        // How far to the right of the middle of the road along the given arm the middle of the lane is, as the driver sees it, given how many lanes that side of the road has.
+        let offset_on = |lanes: i32| match lane {
+            Some(lane) => (lane.min(lanes - 1) as f64 + 0.5) * lane_width,
+            None => lanes as f64 * lane_width + dimensions.bike_lane as f64 / 2.0,
+        };
+        // At a staggered junction, the lanes are further to one side, with the middle of their road.
+        let offset = offset_on(dimensions.lanes_in(start.opposite()))
+            + dimensions.stagger(start.opposite()) as f64;
+        let offset_out = offset_on(dimensions.lanes_out(end)) - dimensions.stagger(end) as f64;
+        let center = (
+            dimensions.half_width as f64 - half_lane,
+            dimensions.half_height as f64 - half_lane,
+        );
+        // From the middle of the intersection to the edge of the window the given way, less half a lane, so the car's square is just inside it.
+        let reach = |x: f64| {
+            if x != 0.0 {
+                dimensions.half_width as f64 - half_lane
+            } else {
+                dimensions.half_height as f64 - half_lane
+            }
+        };
+        let entry = (
+            center.0 - offset * in_y - reach(in_x) * in_x,
+            center.1 + offset * in_x - reach(in_x) * in_y,
+        );
+        let exit = (
+            center.0 - offset_out * out_y + reach(out_x) * out_x,
+            center.1 + offset_out * out_x + reach(out_x) * out_y,
+        );
+        let line = |from: (f64, f64), heading: (f64, f64), to: (f64, f64)| Segment::Line {
+            from,
+            heading,
+            length: (to.0 - from.0) * heading.0 + (to.1 - from.1) * heading.1,
+        };
+
+        // On screen, where y increases downward, a left turn is counterclockwise.
+        let turn = in_x * out_y - in_y * out_x;
+        let segments = if dimensions.layout == Layout::Roundabout {
+            // At a roundabout, straight on to the outside of the ring, then right onto it, along an arc that meets the lane's circle round the island head on. Counterclockwise round that circle, then off it again the same way, and straight on out. The curves on and off are mirror images, so the way round is whatever's left of the turn.
+            let island = dimensions.island() as f64;
+            let outside = dimensions.reach(start.opposite()) as f64;
+            let ring = island + offset;
+            let along = (outside * outside - offset * offset).sqrt();
+            let radius = (outside * outside - ring * ring) / (2.0 * island);
+            let angle = along.atan2(offset + radius);
+            let joining = (
+                center.0 - offset * in_y - along * in_x,
+                center.1 + offset * in_x - along * in_y,
+            );
+            let turned = if start == end {
+                0.0
+            } else if start == end.opposite() {
+                -PI
+            } else {
+                turn.signum() * FRAC_PI_2
+            };
+            let mut segments = vec![line(entry, (in_x, in_y), joining)];
+            for (radius, angle) in [
+                (radius, angle),
+                (ring, turned - 2.0 * angle),
+                (radius, angle),
+            ] {
+                let last = segments.last().expect("Empty route");
+                let (from, heading) = last.at(last.length());
+                segments.push(Segment::Arc {
+                    from,
+                    heading,
+                    radius,
+                    angle,
+                });
+            }
+            let last = segments.last().expect("Empty route");
+            let (leaving, _) = last.at(last.length());
+            segments.push(line(leaving, (out_x, out_y), exit));
+            segments
+        } else if start == end && offset == offset_out {
+            vec![line(entry, (in_x, in_y), exit)]
+        } else if start == end {
+            // Across the intersection, from one edge to the other, bending toward the lane it's going out in and back.
+            let near = dimensions.reach(start.opposite()) as f64;
+            let length = near + dimensions.reach(end) as f64;
+            let shift = offset_out - offset;
+            let angle = 2.0 * (shift / length).atan();
+            let radius = length / (2.0 * angle.sin().abs());
+            let before = (
+                center.0 - offset * in_y - near * in_x,
+                center.1 + offset * in_x - near * in_y,
+            );
+            let mut segments = vec![line(entry, (in_x, in_y), before)];
+            for angle in [angle, -angle] {
+                let last = segments.last().expect("Empty route");
+                let (from, heading) = last.at(last.length());
+                segments.push(Segment::Arc {
+                    from,
+                    heading,
+                    radius,
+                    angle,
+                });
+            }
+            let last = segments.last().expect("Empty route");
+            let (after, _) = last.at(last.length());
+            segments.push(line(after, (out_x, out_y), exit));
+            segments
+        } else if start == end.opposite() {
+            // Into the intersection, then back round to the left onto the other side of the road, on three arcs of the same radius: out to the right, round to the left and back in to the right, in the shape of a keyhole. The further it swings out, the more room it has to turn, and with room enough it turns back on a half circle.
+            let near = dimensions.reach(start.opposite()) as f64;
+            let room = near + dimensions.reach(start) as f64 - half_lane;
+            let across = offset + offset_out;
+            let swing = |radius: f64| ((across / (2.0 * radius) + 1.0) / 2.0).acos();
+            let mut radius = (U_TURN_RADIUS * lane_width).max(across / 2.0);
+            while radius > across / 2.0 && radius * (1.0 + 2.0 * swing(radius).sin()) > room {
+                radius = (radius * 0.9).max(across / 2.0);
+            }
+            let swing = swing(radius);
+            let before = (
+                center.0 - offset * in_y - near * in_x,
+                center.1 + offset * in_x - near * in_y,
+            );
+            let mut segments = vec![line(entry, (in_x, in_y), before)];
+            for angle in [swing, -(PI + 2.0 * swing), swing] {
+                let last = segments.last().expect("Empty route");
+                let (from, heading) = last.at(last.length());
+                segments.push(Segment::Arc {
+                    from,
+                    heading,
+                    radius,
+                    angle,
+                });
+            }
+            let last = segments.last().expect("Empty route");
+            let (after, _) = last.at(last.length());
+            segments.push(line(after, (out_x, out_y), exit));
+            segments
+        } else {
+            // The corner where the straight stretches would meet, cut off by the arc.
+            let corner = (
+                center.0 - offset * in_y - offset_out * out_y,
+                center.1 + offset * in_x + offset_out * out_x,
+            );
+            let radius = if turn < 0.0 {
+                LEFT_TURN_RADIUS * lane_width
+            } else {
+                (RIGHT_TURN_RADIUS * lane_width)
+                    .min(dimensions.reach(start.opposite()) as f64 - offset_out + half_lane)
+            };
+            let before = (corner.0 - radius * in_x, corner.1 - radius * in_y);
+            let after = (corner.0 + radius * out_x, corner.1 + radius * out_y);
+            vec![
+                line(entry, (in_x, in_y), before),
+                Segment::Arc {
+                    from: before,
+                    heading: (in_x, in_y),
+                    radius,
+                    angle: turn.signum() * FRAC_PI_2,
+                },
+                line(after, (out_x, out_y), exit),
+            ]
+        };
+
+        Route {
+            start,
+            end,
+            lane,
+            speed: dimensions.lane_speed(start, end),
+            segments,
+        }
+    }
+
+    // How far it is from one end of the route to the other.
+    pub fn length(&self) -> f64 {
+        self.segments.iter().map(Segment::length).sum()
+    }
+
+    // How far along the route a car has come once it's on the ring of a roundabout, at the end of the curve onto it.
+    pub fn joined(&self) -> f64 {
+        self.segments.iter().take(2).map(Segment::length).sum()
+    }
+
+    // Where a car is on the route after travelling the given distance along it, and which way it's going. Past the end, it carries straight on.
+    pub fn at(&self, travelled: f64) -> ((f64, f64), (f64, f64)) {
+        let mut left = travelled;
+        for segment in &self.segments {
+            if left <= segment.length() {
+                return segment.at(left);
+            }
+            left -= segment.length();
+        }
+        let last = self.segments.last().expect("Empty route");
+        last.at(last.length() + left)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Road;
+
+    // Every layout, with U-turns, each way round for those that have one, and, but for the roundabout, with roads of different widths, coming in and going out, and without bike lanes.
+    fn every_layout() -> Vec<Dimensions> {
+        let mut layouts = vec![Layout::Crossroads, Layout::Roundabout];
+        layouts.extend(Airt::ALL.map(Layout::TJunction));
+        layouts.extend([Airt::Left, Airt::Right].map(Layout::Staggered));
+        let mut every = Vec::new();
+        for layout in layouts {
+            for (uneven, bike_lane) in [(false, true), (true, true), (true, false)] {
+                if uneven && layout == Layout::Roundabout {
+                    continue;
+                }
+                let mut dimensions = Dimensions::new(1000, 800, 32);
+                dimensions.layout = layout;
+                dimensions.u_turns = true;
+                if uneven {
+                    dimensions.roads =
+                        [(1, 2), (3, 3), (2, 4), (5, 1)].map(|(lanes_in, lanes_out)| Road {
+                            lanes_in,
+                            lanes_out,
+                            ..Road::default()
+                        });
+                }
+                if !bike_lane {
+                    dimensions.bike_lane = 0;
+                }
+                dimensions.routes = Route::table(&dimensions);
+                every.push(dimensions);
+            }
+        }
+        every
+    }
+
+    fn close(a: (f64, f64), b: (f64, f64)) -> bool {
+        (a.0 - b.0).hypot(a.1 - b.1) < 1e-6
+    }
+
+    // How far to the right of the middle of the road along the given arm, as a driver going the given way sees it, the middle of a car's square is, where its top left corner is at the given point.
+    fn across(at: (f64, f64), heading: Airt, arm: Airt, dimensions: &Dimensions) -> f64 {
+        let half_lane = dimensions.lane_width as f64 / 2.0;
+        let (x, y) = heading.unit();
+        let (dx, dy) = (
+            at.0 + half_lane - dimensions.half_width as f64,
+            at.1 + half_lane - dimensions.half_height as f64,
+        );
+        // The stagger is to the right of cars coming in along the arm.
+        let stagger = if heading == arm {
+            -dimensions.stagger(arm)
+        } else {
+            dimensions.stagger(arm)
+        };
+        -y * dx + x * dy - stagger as f64
+    }
+
+    // Whether a car's square at the given point is in the given lane, or the bike lane if that's None, on the side of the road with the given number of lanes.
+    fn in_lane(across: f64, lane: Option<i32>, lanes: i32, dimensions: &Dimensions) -> bool {
+        let lane_width = dimensions.lane_width as f64;
+        let (near, far) = match lane {
+            Some(lane) => (lane as f64 * lane_width, (lane + 1) as f64 * lane_width),
+            None => (
+                lanes as f64 * lane_width,
+                lanes as f64 * lane_width + dimensions.bike_lane as f64,
+            ),
+        };
+        near < across && across < far
+    }
+
+    // Whether a car's square at the given point is just inside the edge of the window that's the given way from the middle.
+    fn at_edge(at: (f64, f64), way: Airt, dimensions: &Dimensions) -> bool {
+        let lane_width = dimensions.lane_width as f64;
+        match way {
+            Airt::Up => at.1.abs() < 1e-6,
+            Airt::Down => (at.1 + lane_width - dimensions.window_height as f64).abs() < 1e-6,
+            Airt::Left => at.0.abs() < 1e-6,
+            Airt::Right => (at.0 + lane_width - dimensions.window_width as f64).abs() < 1e-6,
+        }
+    }
+
+    #[test]
+    fn starts_and_ends_at_the_edge_in_the_right_lane() {
+        for dimensions in every_layout() {
+            assert!(!dimensions.routes.is_empty());
+            for route in &dimensions.routes {
+                let what = format!(
+                    "{:?} {:?} -> {:?} from {:?}",
+                    dimensions.layout, route.start, route.end, route.lane
+                );
+                let (arm_in, arm_out) = (route.start.opposite(), route.end);
+                let lanes_in = dimensions.lanes_in(arm_in);
+                let lanes_out = dimensions.lanes_out(arm_out);
+                assert!(route.lane.is_none_or(|lane| lane < lanes_in), "{}", what);
+
+                let (entry, heading) = route.at(0.0);
+                assert!(at_edge(entry, arm_in, &dimensions), "{}", what);
+                assert!(close(heading, route.start.unit()), "{}", what);
+                let across_in = across(entry, route.start, arm_in, &dimensions);
+                assert!(
+                    in_lane(across_in, route.lane, lanes_in, &dimensions),
+                    "{}: {} across",
+                    what,
+                    across_in
+                );
+
+                let (exit, heading) = route.at(route.length());
+                assert!(at_edge(exit, arm_out, &dimensions), "{}", what);
+                assert!(close(heading, route.end.unit()), "{}", what);
+                let lane_out = route.lane.map(|lane| lane.min(lanes_out - 1));
+                let across_out = across(exit, route.end, arm_out, &dimensions);
+                assert!(
+                    in_lane(across_out, lane_out, lanes_out, &dimensions),
+                    "{}: {} across",
+                    what,
+                    across_out
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn joins_up_without_gaps_or_kinks() {
+        for dimensions in every_layout() {
+            for route in &dimensions.routes {
+                for pair in route.segments.windows(2) {
+                    let (end, heading) = pair[0].at(pair[0].length());
+                    let (start, next_heading) = pair[1].at(0.0);
+                    assert!(
+                        close(end, start) && close(heading, next_heading),
+                        "{:?} {:?} -> {:?} from {:?}",
+                        dimensions.layout,
+                        route.start,
+                        route.end,
+                        route.lane
+                    );
+                }
+                assert!(route.segments.iter().all(|segment| segment.length() >= 0.0));
+            }
+        }
+    }
+
+    #[test]
+    fn carries_straight_on_past_the_end() {
+        for dimensions in every_layout() {
+            for route in &dimensions.routes {
+                let ((x, y), heading) = route.at(route.length());
+                for beyond in [0.5, 10.0, 100.0] {
+                    let (at, past) = route.at(route.length() + beyond);
+                    assert!(close(at, (x + beyond * heading.0, y + beyond * heading.1)));
+                    assert!(close(past, heading));
+                }
+            }
+        }
+    }
+}
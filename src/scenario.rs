@@ -0,0 +1,341 @@
+// Scenarios: lists of cars to send in at given times, so that a run can be repeated exactly, as a demo or to see whether a change makes a known situation better or worse. A scenario file has one car per line, in JSON, as an object with the `time` in seconds from the start that it comes into view and the arm it comes in `from`, as a compass direction, with north at the top of the window. It can also say which way it `turn`s, as "left", "straight", "right" or "u-turn", what `vehicle` it is, e.g. "truck", and what `driver` is behind the wheel, e.g. "cautious". Anything left out is picked at random, as when a car is sent in with the keyboard, from the same seed every time. Blank lines are skipped, and the lines needn't be in order of time.
+
+use std::fs;
+
+use crate::{
+    cars::TICK,
+    checker::{self, Settings},
+    drivers::Profile,
+    json,
+    map::{self, compass},
+    types::Airt,
+    vehicles::Class,
+};
+
+#[derive(Clone, Copy)]
+pub struct Arrival {
+    pub tick: u64,
+    pub start: Airt,       // The way it's heading as it comes in.
+    pub end: Option<Airt>, // The way it goes out, or None for a random turn.
+    pub class: Option<Class>,
+    pub profile: Option<Profile>,
+    line: usize, // Of the scenario file, for errors.
+}
+
+impl Arrival {
+    // The same arrival in the mirror image of the intersection, as for left-hand traffic.
+    pub fn mirror(&self) -> Arrival {
+        Arrival {
+            start: self.start.mirror(),
+            end: self.end.map(|end| end.mirror()),
+            ..*self
+        }
+    }
+}
+
+pub fn load(path: &str) -> Result<Vec<Arrival>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+    read(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+// The arrivals in a scenario, in order of time, and in the order they're listed if they're at the same time.
+pub fn read(text: &str) -> Result<Vec<Arrival>, String> {
+    let mut arrivals = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        if !line.trim().is_empty() {
+            arrivals.push(arrival(line, index + 1)?);
+        }
+    }
+    if arrivals.is_empty() {
+        return Err("The scenario has no cars in it".to_string());
+    }
+    arrivals.sort_by_key(|arrival| arrival.tick);
+    Ok(arrivals)
+}
+
+fn arrival(text: &str, line: usize) -> Result<Arrival, String> {
+    let what = format!("Line {}", line);
+    let value = json::parse_at(text, line)?;
+    let fields = map::fields(
+        &value,
+        &what,
+        &["time", "from", "turn", "vehicle", "driver"],
+    )?;
+    let time = match map::get(fields, "time") {
+        Some(time) => map::number(time, &what)?,
+        None => return Err(format!("{} needs a time", what)),
+    };
+    if time < 0.0 {
+        return Err(format!("{}: the time can't be negative", what));
+    }
+    let arm = match map::get(fields, "from") {
+        Some(from) => {
+            map::from_compass(&map::string(from, &what)?).map_err(|e| format!("{}: {}", what, e))?
+        }
+        None => return Err(format!("{} needs a road to come in from", what)),
+    };
+    let end = match map::get(fields, "turn") {
+        None => None,
+        Some(turn) => match map::string(turn, &what)?.as_str() {
+            "u-turn" => Some(arm),
+            turn => Some(map::turn(arm, turn).map_err(|_| {
+                format!(
+                    "{}: expected a turn of left, straight, right or u-turn, got \"{}\"",
+                    what, turn
+                )
+            })?),
+        },
+    };
+    let class = match map::get(fields, "vehicle") {
+        None => None,
+        Some(vehicle) => {
+            let vehicle = map::string(vehicle, &what)?;
+            let class = Class::ALL
+                .into_iter()
+                .find(|class| format!("{:?}", class).to_lowercase() == vehicle);
+            let names = Class::ALL.map(|class| format!("{:?}", class).to_lowercase());
+            Some(class.ok_or_else(|| {
+                format!(
+                    "{}: expected a vehicle of {}, got \"{}\"",
+                    what,
+                    names.join(", "),
+                    vehicle
+                )
+            })?)
+        }
+    };
+    let profile = match map::get(fields, "driver") {
+        None => None,
+        Some(driver) => {
+            let driver = map::string(driver, &what)?;
+            let profile = Profile::ALL
+                .into_iter()
+                .find(|profile| profile.behaviour().name == driver);
+            let names = Profile::ALL.map(|profile| profile.behaviour().name);
+            Some(profile.ok_or_else(|| {
+                format!(
+                    "{}: expected a driver who's {}, got \"{}\"",
+                    what,
+                    names.join(", "),
+                    driver
+                )
+            })?)
+        }
+    };
+
+    Ok(Arrival {
+        tick: (time / TICK.as_secs_f64()).round() as u64,
+        start: arm.opposite(),
+        end,
+        class,
+        profile,
+        line,
+    })
+}
+
+// Fails if a car in the scenario comes in along a road that isn't there, or turns where there's no road to turn into, given the movements the intersection has roads for, as seen on screen.
+pub fn check(arrivals: &[Arrival], movements: &[(Airt, Airt)]) -> Result<(), String> {
+    for arrival in arrivals {
+        let arm = arrival.start.opposite();
+        if !movements.iter().any(|&(start, _)| start == arrival.start) {
+            return Err(format!(
+                "Line {}: there's no road from the {}",
+                arrival.line,
+                compass(arm)
+            ));
+        }
+        if let Some(end) = arrival
+            .end
+            .filter(|&end| !movements.contains(&(arrival.start, end)))
+        {
+            return Err(if end == arm {
+                format!(
+                    "Line {}: there's no U-turn from the {}",
+                    arrival.line,
+                    compass(arm)
+                )
+            } else {
+                format!(
+                    "Line {}: there's no road to the {} from the {}",
+                    arrival.line,
+                    compass(end),
+                    compass(arm)
+                )
+            });
+        }
+    }
+    Ok(())
+}
+
+// Plays back the scenario in the given file headless, with the options of the checker that follow it, e.g. `--roundabout=1` or `--local=1`, and prints the stats once the last car has left. Fails if it's run for `--max-ticks` past the last arrival with cars still to come or on the road, after printing the stats so far.
+pub fn play(path: &str, args: &[String]) -> Result<(), String> {
+    let mut settings = Settings::from_args(args)?;
+    // Whatever a scenario leaves out is picked at random, unlike in the checker.
+    settings.random_lanes = true;
+    settings.random_vehicles = true;
+    let dimensions = checker::dimensions(&settings);
+    let mut arrivals = load(path)?;
+    // The settings are already mirrored for left-hand traffic, and the scenario isn't yet.
+    let seen = |way: Airt| {
+        if settings.left_hand {
+            way.mirror()
+        } else {
+            way
+        }
+    };
+    let movements = dimensions
+        .movements()
+        .into_iter()
+        .map(|(start, end)| (seen(start), seen(end)))
+        .collect::<Vec<(Airt, Airt)>>();
+    check(&arrivals, &movements).map_err(|e| format!("{}: {}", path, e))?;
+    if settings.left_hand {
+        arrivals = arrivals.iter().map(Arrival::mirror).collect();
+    }
+
+    let count = arrivals.len();
+    let last = arrivals.last().map_or(0, |arrival| arrival.tick);
+    let mut traffic = checker::traffic(&settings);
+    traffic.play(arrivals);
+    while !traffic.finished() && traffic.ticks < last + settings.max_ticks {
+        traffic.update(&dimensions);
+    }
+    println!(
+        "{} cars from {}.\n\n{}\nTime to clear: {:.2}s\nLeft on the road: {}",
+        count,
+        path,
+        traffic.format(),
+        (TICK * traffic.ticks as u32).as_secs_f64(),
+        traffic.cars.len() + traffic.held()
+    );
+    if !traffic.finished() {
+        return Err(format!(
+            "{}: cars were still on the road {} ticks after the last one came in",
+            path, settings.max_ticks
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Layout;
+
+    #[test]
+    fn reads_cars_in_order_of_time() {
+        let text = r#"{"time": 2.4, "from": "west"}
+
+{"time": 0.8, "from": "north", "turn": "left", "vehicle": "truck", "driver": "cautious"}
+{"time": 2.4, "from": "south", "turn": "u-turn"}
+"#;
+        let arrivals = read(text).unwrap();
+        let ticks = arrivals
+            .iter()
+            .map(|arrival| arrival.tick)
+            .collect::<Vec<u64>>();
+        let tick = |time: f64| (time / TICK.as_secs_f64()).round() as u64;
+        assert_eq!(ticks, [tick(0.8), tick(2.4), tick(2.4)]);
+        // A car from the north heads down the screen, and turns left to go out east.
+        let first = arrivals[0];
+        assert_eq!((first.start, first.end), (Airt::Down, Some(Airt::Right)));
+        assert_eq!(first.class, Some(Class::Truck));
+        assert_eq!(first.profile, Some(Profile::Cautious));
+        assert_eq!(first.line, 3);
+        // Cars at the same time stay in the order they're listed, with anything left out to be picked at random.
+        assert_eq!((arrivals[1].start, arrivals[1].end), (Airt::Right, None));
+        assert_eq!(arrivals[1].class, None);
+        assert_eq!(arrivals[1].profile, None);
+        assert_eq!(
+            (arrivals[2].start, arrivals[2].end),
+            (Airt::Up, Some(Airt::Down))
+        );
+    }
+
+    #[test]
+    fn rejects_what_it_cant_play() {
+        for (text, error) in [
+            ("", "The scenario has no cars in it"),
+            ("\n  \n", "The scenario has no cars in it"),
+            (r#"{"from": "north"}"#, "Line 1 needs a time"),
+            (r#"{"time": 1}"#, "Line 1 needs a road to come in from"),
+            (
+                r#"{"time": -1, "from": "north"}"#,
+                "Line 1: the time can't be negative",
+            ),
+            (
+                r#"{"time": "1", "from": "north"}"#,
+                "Line 1: expected a number, got a string",
+            ),
+            (
+                r#"{"time": 1, "from": 3}"#,
+                "Line 1: expected a string, got a number",
+            ),
+            (
+                r#"{"time": 1, "from": "northeast"}"#,
+                "Line 1: expected north, south, east or west, got \"northeast\"",
+            ),
+            (
+                r#"{"time": 1, "from": "north", "turn": "back"}"#,
+                "Line 1: expected a turn of left, straight, right or u-turn, got \"back\"",
+            ),
+            (
+                r#"{"time": 1, "from": "north", "vehicle": "tank"}"#,
+                "Line 1: expected a vehicle of car, van, truck",
+            ),
+            (
+                r#"{"time": 1, "from": "north", "driver": "sleepy"}"#,
+                "Line 1: expected a driver who's cautious, normal, aggressive, got \"sleepy\"",
+            ),
+            (
+                r#"{"time": 1, "from": "north", "speed": 50}"#,
+                "Line 1: \"speed\" isn't supported; the supported fields are time, from, turn, vehicle, driver",
+            ),
+            ("[1, 2]", "Line 1 should be an object, not a list"),
+            (
+                "{\"time\": 1, \"from\": \"north\"}\n{\"time\": 2,",
+                "Invalid JSON at line 2, column 11: EOF while parsing a value",
+            ),
+        ] {
+            let e = read(text).err().unwrap();
+            assert!(e.starts_with(error), "{:?} gave {:?}", text, e);
+        }
+    }
+
+    #[test]
+    fn checks_the_roads_are_there() {
+        // A T-junction with no road to the north.
+        let movements = Layout::TJunction(Airt::Up).all_movements(false);
+        let checked = |line: &str| check(&read(line).unwrap(), &movements);
+        assert!(checked(r#"{"time": 1, "from": "west", "turn": "right"}"#).is_ok());
+        assert!(checked(r#"{"time": 1, "from": "south"}"#).is_ok());
+        for (line, error) in [
+            (
+                r#"{"time": 1, "from": "north"}"#,
+                "Line 1: there's no road from the north",
+            ),
+            (
+                r#"{"time": 1, "from": "west", "turn": "left"}"#,
+                "Line 1: there's no road to the north from the west",
+            ),
+            (
+                r#"{"time": 1, "from": "south", "turn": "straight"}"#,
+                "Line 1: there's no road to the north from the south",
+            ),
+            (
+                r#"{"time": 1, "from": "south", "turn": "u-turn"}"#,
+                "Line 1: there's no U-turn from the south",
+            ),
+        ] {
+            assert_eq!(checked(line).err().unwrap(), error);
+        }
+        let movements = Layout::Crossroads.all_movements(true);
+        assert!(
+            check(
+                &read(r#"{"time": 1, "from": "south", "turn": "u-turn"}"#).unwrap(),
+                &movements
+            )
+            .is_ok()
+        );
+    }
+}
@@ -0,0 +1,415 @@
+// Decentralised control based on local sensing. A car in this mode can't ask the rest of the traffic where everyone is about to move, and it can't refuse a move that would cause a crash. All it knows is its own route and speed, and what it can see of the cars within its sensing radius: where they are, which way they're facing, how fast they're going, and which way they're signaling to turn, as well as any pedestrians crossing the road ahead. From that it chooses how hard to accelerate or brake, and then it moves, whatever happens.
+
+use crate::{
+    collision::{self, Rectangle},
+    types::{self, Dimensions},
+};
+
+const SENSING_RADIUS: f64 = 6.0; // In lane widths, measured between the centers of two cars.
+pub const HORIZON: usize = 45; // How many ticks ahead a car looks for conflicts.
+const SAFETY_MARGIN: f64 = 0.25; // Extra clearance, in lane widths, that a car tries to keep from one that's turning alongside it.
+
+pub struct Neighbour {
+    pub x: f64,
+    pub y: f64,
+    pub heading: (f64, f64), // Unit vector in the direction it's facing.
+    pub body: Rectangle,
+    pub speed: f64,
+    pub path: Vec<Rectangle>, // Where its body would be on each of the next few ticks if it kept to its current speed, judging by its turn signal.
+    pub route: Vec<Rectangle>, // The stretch of road ahead of it, judging by its turn signal, whatever its speed.
+    pub emergency: bool,       // Whether its lights are flashing.
+    pub cyclist: bool,         // Whether it's a bicycle going straight on in the bike lane.
+    pub crosses_bike_lane: bool, // Whether it's signaling to turn right across the bike lane beside it, and hasn't turned yet.
+    pub circulating: bool,       // Whether it's on the ring of a roundabout.
+    pub yields: bool,            // Whether it's coming up to the intersection on a minor road.
+}
+
+// What a car knows about itself.
+pub struct Own<'a> {
+    pub x: f64,
+    pub y: f64,
+    pub heading: (f64, f64), // Unit vector in the direction of travel.
+    pub body: Rectangle,
+    pub speed: f64,
+    pub cruise: f64,
+    pub acceleration: f64,      // The most its speed can go up by on one tick.
+    pub path: &'a [Rectangle], // Where the car's body would be on each of the next few ticks if it went at cruising speed.
+    pub route: &'a [Rectangle], // As for a `Neighbour`.
+    pub emergency: bool,
+    pub headway: f64, // The distance the driver likes to keep behind the car in front.
+    pub gap: f64, // The clearance, in lane widths, the driver wants from a car that has priority before they'll cross its path.
+    pub complies: bool, // Whether the driver gives way when they should.
+    pub cyclist: bool, // As for a `Neighbour`.
+    pub crosses_bike_lane: bool,
+    pub circulating: bool,
+    pub yields: bool,
+}
+
+pub struct Decision {
+    pub acceleration: f64,
+    pub yielding: bool,
+    pub held: bool, // Held back by a pedestrian in particular.
+}
+
+pub fn can_sense(a: (f64, f64), b: (f64, f64), dimensions: &Dimensions) -> bool {
+    let radius = SENSING_RADIUS * dimensions.lane_width as f64;
+    (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2) <= radius * radius
+}
+
+// `pedestrians` are the parts of the crossings ahead that the car has to stay off until the pedestrians on them are across.
+pub fn decide(
+    own: &Own,
+    neighbours: &[&Neighbour],
+    pedestrians: &[&Rectangle],
+    dimensions: &Dimensions,
+) -> Decision {
+    let safety_margin = dimensions.lane_width as f64 * SAFETY_MARGIN;
+    let dynamics = &dimensions.dynamics;
+
+    // The distance the car can still travel before it gets in the way of a car it has to give way to, and how much of that the driver is happy to use, keeping their headway behind a car in front.
+    let mut room: Option<f64> = None;
+    let mut keeping: Option<f64> = None;
+    // Likewise, the room before it gets in the way of a car crossing its path that has priority, which is what giving way is.
+    let mut giving_way: Option<f64> = None;
+
+    for &neighbour in neighbours {
+        let current = &neighbour.body;
+        if near(&own.body, current, 0.0) {
+            continue; // Too late: they've crashed. Both drivers carry on and untangle themselves rather than stay locked together.
+        }
+
+        // There's no need to keep a margin around a stopped car, or two cars waiting side by side could keep each other waiting forever. Nor around cars going the same way or the opposite way, which are either in the same lane, where braking in good time keeps them apart, or in the next lane.
+        let stopped = neighbour.speed < f64::EPSILON;
+        let following = dot(own.heading, neighbour.heading) > 0.9;
+        let margin = if stopped || dot(own.heading, neighbour.heading).abs() > 0.9 {
+            0.0
+        } else {
+            dimensions.lane_width as f64 * own.gap
+        };
+
+        // A car turning right across the bike lane lets a cyclist coming up behind it in the bike lane go first, unless it's already in their way, and the cyclist only keeps clear of where the car is going at its current speed.
+        let cyclist_behind = own.crosses_bike_lane
+            && neighbour.cyclist
+            && dot(
+                neighbour.heading,
+                (own.x - neighbour.x, own.y - neighbour.y),
+            ) > 0.0;
+        let car_ahead = own.cyclist
+            && neighbour.crosses_bike_lane
+            && dot(own.heading, (neighbour.x - own.x, neighbour.y - own.y)) > 0.0;
+
+        // The first tick on which the car would get in the way, if it went at cruising speed.
+        let mut gives_way = false;
+        let conflict = if cyclist_behind {
+            if neighbour
+                .route
+                .iter()
+                .any(|spot| near(&own.body, spot, 0.0))
+            {
+                continue;
+            }
+            own.path.iter().position(|spot| {
+                near(spot, current, 0.0)
+                    || neighbour.route.iter().any(|other| near(spot, other, 0.0))
+            })
+        } else if car_ahead {
+            own.path
+                .iter()
+                .zip(&neighbour.path)
+                .position(|(spot, other)| near(spot, other, 0.0))
+        } else if following {
+            // Going the same way: the car behind keeps its distance from the car in front. On the ring of a roundabout, where a car leaving from an inner lane comes in at an angle across an outer one, each can be a little ahead of the other as it sees it, and the one that's further ahead goes first.
+            let ahead = dot(own.heading, (neighbour.x - own.x, neighbour.y - own.y));
+            if ahead <= 0.0
+                || own.circulating
+                    && neighbour.circulating
+                    && dot(
+                        neighbour.heading,
+                        (own.x - neighbour.x, own.y - neighbour.y),
+                    ) > ahead
+            {
+                continue;
+            }
+            // A car that has started to turn by then swings out as it goes, so it gets the full clearance even from a car in the next lane.
+            let turning = |spot: &Rectangle| {
+                cross(
+                    neighbour.heading,
+                    (
+                        spot.center.0 - current.center.0,
+                        spot.center.1 - current.center.1,
+                    ),
+                )
+                .abs()
+                    > 1.0
+            };
+            // A long vehicle in the next lane swings out over this one as it turns, and the car has to let it finish. Its route is only known a tick at a time, and its back swings out further than its middle moves between ticks, so the car keeps the full clearance from it.
+            let alongside =
+                cross(own.heading, (neighbour.x - own.x, neighbour.y - own.y)).abs() > 1.0;
+            own.path
+                .iter()
+                .zip(&neighbour.path)
+                .position(|(spot, other)| {
+                    near(
+                        spot,
+                        other,
+                        if turning(other) {
+                            safety_margin
+                        } else {
+                            margin
+                        },
+                    ) || alongside
+                        && neighbour
+                            .route
+                            .iter()
+                            .any(|other| near(spot, other, safety_margin))
+                })
+        } else if has_priority(own, neighbour, dimensions) && !pushes_in(own, neighbour, dimensions)
+        {
+            // Crossing paths with a car that has priority, even if it's stopped for now: stay off the road ahead of it until it's passed.
+            gives_way = true;
+            own.path.iter().position(|spot| {
+                near(spot, current, margin)
+                    || neighbour
+                        .route
+                        .iter()
+                        .any(|other| near(spot, other, margin))
+            })
+        } else if stopped {
+            // A stopped car can't be expected to get out of the way, even if it ought to give way.
+            own.path.iter().position(|spot| near(spot, current, 0.0))
+        } else {
+            continue;
+        };
+
+        if let Some(k) = conflict {
+            let distance = k as f64 * own.cruise;
+            let kept = if following {
+                (distance - own.headway).max(0.0)
+            } else {
+                distance
+            };
+            room = Some(room.map_or(distance, |room| room.min(distance)));
+            keeping = Some(keeping.map_or(kept, |keeping| keeping.min(kept)));
+            if gives_way {
+                giving_way = Some(giving_way.map_or(distance, |room| room.min(distance)));
+            }
+        }
+    }
+
+    // The fastest the car can go and still stop comfortably before it runs out of room. If it's already going faster than that, it brakes as hard as it has to, up to the limit of its brakes. Keeping its headway never calls for braking harder than is comfortable, though.
+    let braking = dynamics.comfortable_braking;
+    let target = |room: f64, keeping: f64| {
+        (2.0 * braking * keeping)
+            .sqrt()
+            .max((2.0 * braking * room).sqrt().min(own.speed - braking))
+            .min(own.cruise)
+    };
+    let for_cars = match (room, keeping) {
+        (Some(room), Some(keeping)) => target(room, keeping),
+        _ => own.cruise,
+    };
+    let for_pedestrians = own
+        .path
+        .iter()
+        .position(|spot| pedestrians.iter().any(|area| near(spot, area, 0.0)))
+        .map_or(own.cruise, |k| {
+            let distance = k as f64 * own.cruise;
+            target(distance, distance)
+        });
+
+    let for_giving_way = giving_way.map_or(own.cruise, |room| target(room, room));
+
+    let target = for_cars.min(for_pedestrians);
+    Decision {
+        acceleration: (target - own.speed).clamp(-dynamics.emergency_braking, own.acceleration),
+        // Only giving way counts, not keeping behind the car in front, or stopping for a pedestrian.
+        yielding: for_giving_way < own.cruise && for_giving_way <= target,
+        held: for_pedestrians < for_cars.min(own.speed + own.acceleration),
+    }
+}
+
+// Every car applies the same rule to what it sees, so of two cars crossing paths, exactly one gives way. A car that's already standing on the road ahead of the other goes first, since the other can't get past until it's moved on, e.g. a long vehicle that has swung across the next lane. Otherwise an emergency vehicle goes first, and then, at a roundabout, a car that's already on the ring, and elsewhere, a car on the major road, or already in the intersection, over one coming up on a minor road. Failing that, a car that can no longer stop comfortably before it reaches the center of the intersection goes first, and failing that it's the one that is closer to the center, or further past it.
+fn has_priority(own: &Own, neighbour: &Neighbour, dimensions: &Dimensions) -> bool {
+    let own_in_the_way = neighbour
+        .route
+        .iter()
+        .any(|spot| near(&own.body, spot, 0.0));
+    let neighbour_in_the_way = own
+        .route
+        .iter()
+        .any(|spot| near(&neighbour.body, spot, 0.0));
+    if own_in_the_way != neighbour_in_the_way {
+        return neighbour_in_the_way;
+    }
+
+    if own.emergency != neighbour.emergency {
+        return neighbour.emergency;
+    }
+
+    if own.circulating != neighbour.circulating {
+        return neighbour.circulating;
+    }
+
+    if own.yields != neighbour.yields {
+        return own.yields;
+    }
+
+    let center = (
+        dimensions.half_width as f64 - dimensions.lane_width as f64 / 2.0,
+        dimensions.half_height as f64 - dimensions.lane_width as f64 / 2.0,
+    );
+    let own_progress = dot((center.0 - own.x, center.1 - own.y), own.heading);
+    let neighbour_progress = dot(
+        (center.0 - neighbour.x, center.1 - neighbour.y),
+        neighbour.heading,
+    );
+
+    let committed = |progress: f64, speed: f64| {
+        progress > 0.0 && dimensions.dynamics.stopping_distance(speed) > progress
+    };
+    let own_committed = committed(own_progress, own.speed);
+    let neighbour_committed = committed(neighbour_progress, neighbour.speed);
+    if own_committed != neighbour_committed {
+        return neighbour_committed;
+    }
+
+    if (own_progress - neighbour_progress).abs() > f64::EPSILON {
+        neighbour_progress < own_progress
+    } else {
+        (neighbour.x, neighbour.y) < (own.x, own.y)
+    }
+}
+
+// A driver who doesn't comply doesn't give way to a car that ought to go first, as long as they reckon they'd be on its road before it got too close to stop for them. Once they're in its way, the rule about cars in the way means it's the other car that waits. Nobody pushes in ahead of an emergency vehicle.
+fn pushes_in(own: &Own, neighbour: &Neighbour, dimensions: &Dimensions) -> bool {
+    if own.complies || neighbour.emergency {
+        return false;
+    }
+    let Some(ours) = own
+        .path
+        .iter()
+        .position(|spot| neighbour.route.iter().any(|other| near(spot, other, 0.0)))
+    else {
+        return false;
+    };
+    let Some(theirs) = neighbour
+        .route
+        .iter()
+        .position(|spot| own.path.iter().any(|other| near(spot, other, 0.0)))
+    else {
+        return false;
+    };
+
+    // How many ticks it would take to get there from the current speed, and how far the other car could go meanwhile. A driver who can't get going doesn't try.
+    let distance = (ours + 1) as f64 * own.cruise;
+    let Some(ticks) = types::ticks_to_cover(distance, own.speed, own.acceleration, own.cruise)
+    else {
+        return false;
+    };
+    let their_room =
+        (theirs + 1) as f64 * dimensions.speed.fast as f64 - ticks as f64 * neighbour.speed;
+    their_room > dimensions.dynamics.stopping_distance(neighbour.speed)
+}
+
+fn dot(a: (f64, f64), b: (f64, f64)) -> f64 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+fn cross(a: (f64, f64), b: (f64, f64)) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+// Whether two bodies would come within the given distance of each other.
+fn near(a: &Rectangle, b: &Rectangle, distance: f64) -> bool {
+    collision::within(a, b, distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CRUISE: f64 = 4.0;
+
+    // A car's body at the given place, facing the given way, and where it would be on each of the next ticks at the given speed.
+    fn body(at: (f64, f64), heading: (f64, f64)) -> Rectangle {
+        Rectangle {
+            center: at,
+            heading,
+            half_length: 8.0,
+            half_width: 6.0,
+        }
+    }
+
+    fn path(at: (f64, f64), heading: (f64, f64), speed: f64) -> Vec<Rectangle> {
+        (1..=HORIZON)
+            .map(|k| {
+                let k = k as f64 * speed;
+                body((at.0 + k * heading.0, at.1 + k * heading.1), heading)
+            })
+            .collect()
+    }
+
+    fn neighbour(at: (f64, f64), heading: (f64, f64), speed: f64) -> Neighbour {
+        Neighbour {
+            x: at.0,
+            y: at.1,
+            heading,
+            body: body(at, heading),
+            speed,
+            path: path(at, heading, speed),
+            route: path(at, heading, CRUISE),
+            emergency: false,
+            cyclist: false,
+            crosses_bike_lane: false,
+            circulating: false,
+            yields: false,
+        }
+    }
+
+    // A car heading right along the middle of the window, at its cruising speed.
+    fn decide_for(neighbours: &[&Neighbour], pedestrians: &[&Rectangle]) -> Decision {
+        let dimensions = Dimensions::new(800, 800, 32);
+        let (at, heading) = ((100.0, 400.0), (1.0, 0.0));
+        let path = path(at, heading, CRUISE);
+        let own = Own {
+            x: at.0,
+            y: at.1,
+            heading,
+            body: body(at, heading),
+            speed: CRUISE,
+            cruise: CRUISE,
+            acceleration: 0.1,
+            path: &path,
+            route: &path,
+            emergency: false,
+            headway: 16.0,
+            gap: 0.5,
+            complies: true,
+            cyclist: false,
+            crosses_bike_lane: false,
+            circulating: false,
+            yields: false,
+        };
+        decide(&own, neighbours, pedestrians, &dimensions)
+    }
+
+    #[test]
+    fn only_giving_way_counts_as_yielding() {
+        let ahead = neighbour((130.0, 400.0), (1.0, 0.0), 0.5);
+        let decision = decide_for(&[&ahead], &[]);
+        assert!(decision.acceleration < 0.0);
+        assert!(!decision.yielding);
+
+        let crossing = body((125.0, 400.0), (0.0, -1.0));
+        let decision = decide_for(&[], &[&crossing]);
+        assert!(decision.acceleration < 0.0);
+        assert!(!decision.yielding);
+        assert!(decision.held);
+
+        let mut ambulance = neighbour((130.0, 430.0), (0.0, -1.0), CRUISE);
+        ambulance.emergency = true;
+        let decision = decide_for(&[&ambulance], &[]);
+        assert!(decision.acceleration < 0.0);
+        assert!(decision.yielding);
+    }
+}
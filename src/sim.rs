@@ -1,28 +1,45 @@
 use std::{
-    thread,
+    f32::consts::SQRT_2,
+    slice, thread,
     time::{Duration, Instant},
 };
 
 use sdl2::{
-    event::Event,
+    Sdl,
+    event::{Event, WindowEvent},
     keyboard::Keycode,
     pixels::Color,
+    rect::Rect,
     render::{Canvas, Texture, TextureCreator},
     video::{FullscreenType, Window, WindowContext},
-    Sdl,
 };
 
 use crate::{
-    cars::Traffic,
+    cars::{TICK, Traffic},
+    network::{MAX_GRID, Network},
+    routes::Route,
     textures, trees,
-    types::{Airt, Dimensions, Speed},
+    types::{Airt, Control, Dimensions, Layout, Road},
 };
 
-pub fn simulate(traffic: &mut Traffic) {
+pub const KEYPRESS_INTERVAL: Duration = Duration::from_millis(360); // Change to a lower value to see gridlock.
+
+pub fn simulate(
+    traffic: &mut Traffic,
+    layout: Layout,
+    roads: [Road; 4],
+    pockets: bool,
+    u_turns: bool,
+) {
     let (sdl_context, mut canvas, mut dimensions) = setup();
+    dimensions.layout = layout;
+    dimensions.roads = roads;
+    dimensions.pockets = pockets;
+    dimensions.u_turns = u_turns;
+    dimensions.routes = Route::table(&dimensions);
+    set_minimum_size(&mut canvas, slice::from_ref(&dimensions));
     let texture_creator = canvas.texture_creator();
-    let (background_texture, lanes_texture, car_textures, tree_textures) =
-        textures::create_textures(&texture_creator, &dimensions, &mut canvas);
+    let tree_textures = textures::create_tree_textures(&texture_creator);
 
     run(
         &sdl_context,
@@ -30,13 +47,155 @@ pub fn simulate(traffic: &mut Traffic) {
         &mut dimensions,
         traffic,
         &texture_creator,
-        &background_texture,
-        &lanes_texture,
-        &car_textures,
         &tree_textures,
     );
 }
 
+// Shows a road network a window's worth at a time, with each intersection drawn as the single one is. The arrow keys scroll around it, and + and - zoom in and out. R sends a car across it from a random place on its edge to another, and P and L switch platooning and the kind of control for every intersection at once. Each intersection takes up as much room as the window, so resizing the window resizes them all.
+pub fn simulate_network(network: &mut Network) {
+    let (sdl_context, mut canvas, mut dimensions) = setup();
+    let mut node_dimensions = network.dimensions(
+        dimensions.window_width,
+        dimensions.window_height,
+        dimensions.lane_width,
+    );
+    set_minimum_size(&mut canvas, &node_dimensions);
+    let texture_creator = canvas.texture_creator();
+    let (mut background_texture, mut lanes_textures, which, mut car_textures) =
+        textures::create_network_textures(&texture_creator, &node_dimensions, &mut canvas);
+    let tree_textures = textures::create_tree_textures(&texture_creator);
+
+    let mut event_pump = sdl_context.event_pump().unwrap();
+    let mut last_keypress_time = Instant::now();
+    let mut start_time = Instant::now();
+    let mut mirror = network
+        .left_hand
+        .then(|| textures::create_mirror_texture(&texture_creator, &dimensions));
+    let (mut scroll_x, mut scroll_y, mut zoom) = (0, 0, 1.0_f32);
+    let mut step = dimensions.window_width.min(dimensions.window_height) / 4;
+
+    'running: loop {
+        let now = Instant::now();
+        if now.duration_since(start_time) < TICK {
+            continue;
+        }
+        start_time = now;
+
+        network.update(&node_dimensions);
+
+        canvas.set_draw_color(Color::RGB(240, 240, 240));
+        canvas.clear();
+        canvas.set_scale(zoom, zoom).unwrap();
+        for (index, (node, traffic)) in network.nodes.iter().zip(&network.traffic).enumerate() {
+            canvas.set_viewport(Rect::new(
+                node.column as i32 * dimensions.window_width - scroll_x,
+                node.row as i32 * dimensions.window_height - scroll_y,
+                dimensions.window_width as u32,
+                dimensions.window_height as u32,
+            ));
+            let draw = |canvas: &mut Canvas<Window>| {
+                canvas.copy(&tree_textures[0].0, None, None).unwrap();
+                canvas.copy(&background_texture, None, None).unwrap();
+                canvas
+                    .copy(&lanes_textures[which[index]], None, None)
+                    .unwrap();
+                traffic.draw(canvas, &node_dimensions[index], &car_textures);
+                trees::plant(canvas, &tree_textures, &dimensions);
+            };
+            match mirror.as_mut() {
+                Some(mirror) => draw_mirrored(&mut canvas, mirror, draw),
+                None => draw(&mut canvas),
+            }
+        }
+        canvas.set_viewport(None);
+        canvas.set_scale(1.0, 1.0).unwrap();
+        canvas.present();
+
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => {
+                    break 'running;
+                }
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(width, height),
+                    ..
+                } => {
+                    let grown = resize(&mut canvas, &mut dimensions, width, height);
+                    for (traffic, dimensions) in
+                        network.traffic.iter_mut().zip(&mut node_dimensions)
+                    {
+                        dimensions.resize(width, height);
+                        traffic.recenter(grown, dimensions);
+                    }
+                    (background_texture, lanes_textures, _, car_textures) =
+                        textures::create_network_textures(
+                            &texture_creator,
+                            &node_dimensions,
+                            &mut canvas,
+                        );
+                    if mirror.is_some() {
+                        mirror = Some(textures::create_mirror_texture(
+                            &texture_creator,
+                            &dimensions,
+                        ));
+                    }
+                    step = dimensions.window_width.min(dimensions.window_height) / 4;
+                }
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } => {
+                    // Scrolling and zooming aren't held back by the keypress interval.
+                    match keycode {
+                        Keycode::Up => scroll_y -= step,
+                        Keycode::Down => scroll_y += step,
+                        Keycode::Left => scroll_x -= step,
+                        Keycode::Right => scroll_x += step,
+                        Keycode::Equals | Keycode::Plus | Keycode::KpPlus => {
+                            zoom = (zoom * 1.25).min(2.0)
+                        }
+                        Keycode::Minus | Keycode::KpMinus => {
+                            zoom = (zoom / 1.25).max(1.0 / MAX_GRID as f32)
+                        }
+                        _ => {}
+                    }
+
+                    let now = Instant::now();
+                    if now.duration_since(last_keypress_time) <= KEYPRESS_INTERVAL {
+                        continue;
+                    }
+                    match keycode {
+                        Keycode::R => network.push_random(&node_dimensions),
+                        Keycode::P => {
+                            for traffic in &mut network.traffic {
+                                traffic.platooning = !traffic.platooning;
+                            }
+                        }
+                        Keycode::L => {
+                            for traffic in &mut network.traffic {
+                                traffic.control = match traffic.control {
+                                    Control::Global => Control::Local,
+                                    Control::Local => Control::Global,
+                                };
+                            }
+                        }
+                        _ => continue,
+                    }
+                    last_keypress_time = now;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // To ward against closing the stats window if you press escape for too long.
+    thread::sleep(Duration::from_millis(128));
+}
+
 fn setup() -> (sdl2::Sdl, Canvas<Window>, Dimensions) {
     sdl2::hint::set("SDL_NO_SIGNAL_HANDLERS", "1");
     let sdl_context = sdl2::init().unwrap();
@@ -45,33 +204,18 @@ fn setup() -> (sdl2::Sdl, Canvas<Window>, Dimensions) {
     let (ddpi, hdpi, vdpi) = video_subsystem.display_dpi(0).unwrap();
 
     let display_mode = video_subsystem.current_display_mode(0).unwrap();
-    // let screen_width = display_mode.w;
+    let screen_width = display_mode.w;
     let screen_height = display_mode.h;
 
-    // let window_width = screen_width as f32 * 0.6 * hdpi / 133.0;
-    let window_width = screen_height as f32 * 0.8 * hdpi / 133.0; // Make the window square.
+    let window_width = screen_width as f32 * 0.8 * hdpi / 133.0;
     let window_height = screen_height as f32 * 0.8 * vdpi / 139.0;
 
-    let inches = f32::sqrt(window_width * window_width + window_height * window_height);
+    // The lanes are sized from the shorter side, as they were when the window was always square, so a wide window shows more of the roads rather than wider ones.
+    let inches = window_width.min(window_height) * SQRT_2;
 
     let lane_width = (16.0 * inches * ddpi / (1024.3201 * 134.4)) as i32;
-    let speed = Speed {
-        fast: lane_width * 3 / 8 as i32,
-        default: lane_width / 4 as i32,
-        slow: lane_width / 8 as i32,
-    };
-
-    let window_width = window_width as i32;
-    let window_height = window_height as i32;
 
-    let dimensions = Dimensions {
-        window_width,
-        window_height,
-        half_width: window_width / 2,
-        half_height: window_height / 2,
-        lane_width,
-        speed,
-    };
+    let dimensions = Dimensions::new(window_width as i32, window_height as i32, lane_width);
 
     let window = video_subsystem
         .window(
@@ -80,6 +224,7 @@ fn setup() -> (sdl2::Sdl, Canvas<Window>, Dimensions) {
             dimensions.window_height as u32,
         )
         .position_centered()
+        .resizable()
         .build()
         .unwrap();
 
@@ -94,41 +239,87 @@ fn setup() -> (sdl2::Sdl, Canvas<Window>, Dimensions) {
     (sdl_context, canvas, dimensions)
 }
 
+// Stops the window from being made so small that the roads no longer reach out of the intersections, and past where any turn pockets open up, before its edges.
+fn set_minimum_size(canvas: &mut Canvas<Window>, dimensions: &[Dimensions]) {
+    let reach = dimensions
+        .iter()
+        .flat_map(|dimensions| {
+            Airt::ALL.map(|arm| {
+                dimensions
+                    .pocket_opening(arm)
+                    .unwrap_or(dimensions.reach(arm))
+            })
+        })
+        .max()
+        .unwrap_or(0);
+    let side = 2 * (reach + 4 * dimensions[0].lane_width) as u32;
+    canvas.window_mut().set_minimum_size(side, side).unwrap();
+}
+
+// Fits the canvas and the dimensions to the window's new size, and returns how much further out its edges are from the middle, across and down.
+fn resize(
+    canvas: &mut Canvas<Window>,
+    dimensions: &mut Dimensions,
+    width: i32,
+    height: i32,
+) -> (i32, i32) {
+    let (half_width, half_height) = (dimensions.half_width, dimensions.half_height);
+    dimensions.resize(width, height);
+    canvas
+        .set_logical_size(width as u32, height as u32)
+        .unwrap();
+    (
+        dimensions.half_width - half_width,
+        dimensions.half_height - half_height,
+    )
+}
+
 fn run(
     sdl_context: &Sdl,
     canvas: &mut Canvas<sdl2::video::Window>,
     dimensions: &mut Dimensions,
     traffic: &mut Traffic,
     texture_creator: &TextureCreator<WindowContext>,
-    background_texture: &sdl2::render::Texture,
-    lanes_texture: &Texture,
-    car_textures: &[sdl2::render::Texture; 4],
     tree_textures: &Vec<(Texture, [f64; 2])>,
 ) {
+    let (mut background_texture, mut lanes_texture, mut car_textures) =
+        textures::create_road_textures(texture_creator, dimensions, canvas);
+    let mut mirror = traffic
+        .left_hand
+        .then(|| textures::create_mirror_texture(texture_creator, dimensions));
+    // The arrow keys send cars the way they point on screen.
+    let left_hand = traffic.left_hand;
+    let seen = |way: Airt| if left_hand { way.mirror() } else { way };
     let mut event_pump = sdl_context.event_pump().unwrap();
     let mut last_keypress_time = Instant::now();
-    let keypress_interval = Duration::from_millis(360); // Change to a lower value to see gridlock.
     let mut start_time = Instant::now();
     let mut is_fullscreen = false;
 
     'running: loop {
         let now = Instant::now();
         let elapsed = now.duration_since(start_time);
-        if elapsed < Duration::from_millis(16) {
+        if elapsed < TICK {
             continue;
         }
         start_time = now;
 
         traffic.update(&dimensions);
+        if traffic.finished() {
+            break 'running;
+        }
+        let scenery = Scenery {
+            background: &background_texture,
+            lanes: &lanes_texture,
+            cars: &car_textures,
+            trees: tree_textures,
+        };
         render(
             canvas,
             &dimensions,
             &traffic,
-            background_texture,
-            car_textures,
-            lanes_texture,
+            &scenery,
             texture_creator,
-            tree_textures,
+            mirror.as_mut(),
         );
 
         for event in event_pump.poll_iter() {
@@ -140,30 +331,68 @@ fn run(
                 } => {
                     break 'running;
                 }
+                // Going fullscreen and back resizes the window too.
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(width, height),
+                    ..
+                } => {
+                    let grown = resize(canvas, dimensions, width, height);
+                    traffic.recenter(grown, dimensions);
+                    (background_texture, lanes_texture, car_textures) =
+                        textures::create_road_textures(texture_creator, dimensions, canvas);
+                    if mirror.is_some() {
+                        mirror = Some(textures::create_mirror_texture(texture_creator, dimensions));
+                    }
+                }
                 Event::KeyDown {
                     keycode: Some(keycode),
                     ..
                 } => {
                     let now = Instant::now();
-                    if now.duration_since(last_keypress_time) <= keypress_interval {
+                    if now.duration_since(last_keypress_time) <= KEYPRESS_INTERVAL {
                         continue;
                     }
                     match keycode {
+                        Keycode::Up
+                        | Keycode::Down
+                        | Keycode::Left
+                        | Keycode::Right
+                        | Keycode::R
+                        | Keycode::E
+                            if traffic.playing() => {}
                         Keycode::Up => {
-                            traffic.push(Airt::Up, &dimensions);
+                            traffic.push(seen(Airt::Up), &dimensions);
                         }
                         Keycode::Down => {
-                            traffic.push(Airt::Down, &dimensions);
+                            traffic.push(seen(Airt::Down), &dimensions);
                         }
                         Keycode::Left => {
-                            traffic.push(Airt::Left, &dimensions);
+                            traffic.push(seen(Airt::Left), &dimensions);
                         }
                         Keycode::Right => {
-                            traffic.push(Airt::Right, &dimensions);
+                            traffic.push(seen(Airt::Right), &dimensions);
                         }
                         Keycode::R => {
                             traffic.push_random(&dimensions);
                         }
+                        Keycode::E => {
+                            traffic.push_emergency(dimensions);
+                        }
+                        Keycode::W => {
+                            traffic.push_pedestrian(dimensions);
+                        }
+                        Keycode::P => {
+                            traffic.platooning = !traffic.platooning;
+                        }
+                        Keycode::O => {
+                            traffic.overriding = !traffic.overriding;
+                        }
+                        Keycode::L => {
+                            traffic.control = match traffic.control {
+                                Control::Global => Control::Local,
+                                Control::Local => Control::Global,
+                            };
+                        }
 
                         Keycode::F => {
                             let window = canvas.window_mut();
@@ -189,34 +418,59 @@ fn run(
     thread::sleep(Duration::from_millis(128));
 }
 
+// The textures a single intersection is drawn with, besides the mirror that left-hand traffic is drawn into.
+struct Scenery<'a, 'b> {
+    background: &'a Texture<'b>,
+    lanes: &'a Texture<'b>,
+    cars: &'a [[Texture<'b>; 4]],
+    trees: &'a Vec<(Texture<'b>, [f64; 2])>,
+}
+
 fn render(
     canvas: &mut Canvas<sdl2::video::Window>,
     dimensions: &Dimensions,
     traffic: &Traffic,
-    background_texture: &sdl2::render::Texture,
-    car_textures: &[sdl2::render::Texture; 4],
-    lanes_texture: &Texture,
+    scenery: &Scenery,
     texture_creator: &TextureCreator<WindowContext>,
-    tree_textures: &Vec<(Texture, [f64; 2])>,
+    mirror: Option<&mut Texture>,
 ) {
-    canvas.set_draw_color(Color::RGB(240, 240, 240));
-    canvas.clear();
-
-    canvas.copy(&tree_textures[0].0, None, None).unwrap();
-    canvas.copy(background_texture, None, None).unwrap();
-    canvas.copy(lanes_texture, None, None).unwrap();
-
-    traffic.draw(canvas, &dimensions, car_textures);
-
-    trees::plant(canvas, tree_textures, dimensions);
-
     let snow = textures::create_speckled_texture(
         texture_creator,
         dimensions.window_width as u32,
         dimensions.window_height as u32,
         canvas,
     );
-    canvas.copy(&snow, None, None).unwrap();
+
+    let draw = |canvas: &mut Canvas<Window>| {
+        canvas.set_draw_color(Color::RGB(240, 240, 240));
+        canvas.clear();
+
+        canvas.copy(&scenery.trees[0].0, None, None).unwrap();
+        canvas.copy(scenery.background, None, None).unwrap();
+        canvas.copy(scenery.lanes, None, None).unwrap();
+
+        traffic.draw(canvas, &dimensions, scenery.cars);
+
+        trees::plant(canvas, scenery.trees, dimensions);
+
+        canvas.copy(&snow, None, None).unwrap();
+    };
+    match mirror {
+        Some(mirror) => draw_mirrored(canvas, mirror, draw),
+        None => draw(canvas),
+    }
 
     canvas.present();
 }
+
+// Draws a scene into the mirror texture, then copies it to the canvas, or the part of it that the viewport is set to, flipped from left to right, as left-hand traffic is drawn.
+fn draw_mirrored(
+    canvas: &mut Canvas<Window>,
+    mirror: &mut Texture,
+    draw: impl FnOnce(&mut Canvas<Window>),
+) {
+    canvas.with_texture_canvas(mirror, draw).unwrap();
+    canvas
+        .copy_ex(mirror, None, None, 0.0, None, true, false)
+        .unwrap();
+}
@@ -8,18 +8,14 @@ use sdl2::{
     video::WindowContext,
 };
 
-use crate::{lanes, types::Dimensions};
+use crate::{lanes, types::Dimensions, vehicles::Class};
 
-pub fn create_textures<'a>(
+// The textures that depend on the size of the window and the layout of the roads, which are made afresh whenever the window is resized.
+pub fn create_road_textures<'a>(
     texture_creator: &'a TextureCreator<WindowContext>,
     dimensions: &Dimensions,
     canvas: &mut Canvas<sdl2::video::Window>,
-) -> (
-    Texture<'a>,
-    Texture<'a>,
-    [Texture<'a>; 4],
-    Vec<(Texture<'a>, [f64; 2])>,
-) {
+) -> (Texture<'a>, Texture<'a>, Vec<[Texture<'a>; 4]>) {
     let background_texture = create_speckled_texture(
         &texture_creator,
         dimensions.window_width as u32,
@@ -28,7 +24,61 @@ pub fn create_textures<'a>(
     );
     let lanes_texture = lanes::draw(canvas, &dimensions, &texture_creator);
     let car_textures = create_car_textures(&texture_creator, &dimensions);
+    (background_texture, lanes_texture, car_textures)
+}
+
+// The same for a road network, given the dimensions of each intersection: one background and one set of cars for them all, and a lanes texture for each way they're laid out, together with which of those each intersection uses, so that those laid out alike share one.
+pub fn create_network_textures<'a>(
+    texture_creator: &'a TextureCreator<WindowContext>,
+    dimensions: &[Dimensions],
+    canvas: &mut Canvas<sdl2::video::Window>,
+) -> (
+    Texture<'a>,
+    Vec<Texture<'a>>,
+    Vec<usize>,
+    Vec<[Texture<'a>; 4]>,
+) {
+    let background_texture = create_speckled_texture(
+        texture_creator,
+        dimensions[0].window_width as u32,
+        dimensions[0].window_height as u32,
+        canvas,
+    );
+    let mut lanes_textures = Vec::new();
+    let mut alike: Vec<&Dimensions> = Vec::new();
+    let mut which = Vec::new();
+    for node in dimensions {
+        let same = |other: &&Dimensions| other.layout == node.layout && other.roads == node.roads;
+        match alike.iter().position(same) {
+            Some(index) => which.push(index),
+            None => {
+                which.push(alike.len());
+                alike.push(node);
+                lanes_textures.push(lanes::draw(canvas, node, texture_creator));
+            }
+        }
+    }
+    let car_textures = create_car_textures(texture_creator, &dimensions[0]);
+    (background_texture, lanes_textures, which, car_textures)
+}
 
+// A texture the size of the window to draw a scene into before it's copied to the window mirrored, for left-hand traffic.
+pub fn create_mirror_texture<'a>(
+    texture_creator: &'a TextureCreator<WindowContext>,
+    dimensions: &Dimensions,
+) -> Texture<'a> {
+    texture_creator
+        .create_texture_target(
+            Some(PixelFormatEnum::RGBA8888),
+            dimensions.window_width as u32,
+            dimensions.window_height as u32,
+        )
+        .expect("Failed to create texture target")
+}
+
+pub fn create_tree_textures<'a>(
+    texture_creator: &'a TextureCreator<WindowContext>,
+) -> Vec<(Texture<'a>, [f64; 2])> {
     let paths = [
         "images/trees.jpg",
         "images/left_trees.png",
@@ -41,13 +91,7 @@ pub fn create_textures<'a>(
     for path in paths {
         tree_textures.push(create_texture_from_image(&texture_creator, path));
     }
-
-    (
-        background_texture,
-        lanes_texture,
-        car_textures,
-        tree_textures,
-    )
+    tree_textures
 }
 
 fn create_texture_from_image<'a>(
@@ -114,69 +158,177 @@ pub fn create_speckled_texture<'a>(
 fn create_car_textures<'a>(
     texture_creator: &'a TextureCreator<WindowContext>,
     dimensions: &Dimensions,
+) -> Vec<[sdl2::render::Texture<'a>; 4]> {
+    Class::ALL
+        .iter()
+        .map(|&class| create_class_textures(texture_creator, dimensions, class))
+        .collect()
+}
+
+fn create_class_textures<'a>(
+    texture_creator: &'a TextureCreator<WindowContext>,
+    dimensions: &Dimensions,
+    class: Class,
 ) -> [sdl2::render::Texture<'a>; 4] {
+    if class.spec().emergency {
+        // Always white. Instead of a color for each side it comes from, it has a texture for each step of the flashing of its light bar: red, off, blue, off.
+        return [Some(0), None, Some(1), None].map(|lit| {
+            let mut car_surface = create_car_surface(dimensions, class, Color::RGB(255, 255, 255));
+            draw_light_bar(&mut car_surface, dimensions, class, lit);
+            texture_creator
+                .create_texture_from_surface(&car_surface)
+                .expect("Failed to create car texture")
+        });
+    }
+
     [
-        create_car_texture(texture_creator, dimensions, Color::RGB(255, 0, 0)),
-        create_car_texture(texture_creator, dimensions, Color::RGB(0, 255, 0)),
-        create_car_texture(texture_creator, dimensions, Color::RGB(0, 0, 255)),
-        create_car_texture(texture_creator, dimensions, Color::RGB(255, 255, 0)),
+        create_car_texture(texture_creator, dimensions, class, Color::RGB(255, 0, 0)),
+        create_car_texture(texture_creator, dimensions, class, Color::RGB(0, 255, 0)),
+        create_car_texture(texture_creator, dimensions, class, Color::RGB(0, 0, 255)),
+        create_car_texture(texture_creator, dimensions, class, Color::RGB(255, 255, 0)),
     ]
 }
 
 fn create_car_texture<'a>(
     texture_creator: &'a TextureCreator<WindowContext>,
     dimensions: &Dimensions,
+    class: Class,
     body_color: Color,
 ) -> sdl2::render::Texture<'a> {
+    texture_creator
+        .create_texture_from_surface(create_car_surface(dimensions, class, body_color))
+        .expect("Failed to create car texture")
+}
+
+// A lane width across and a sixteenth longer than the body at each end, with the body in the middle, facing up.
+fn create_car_surface(
+    dimensions: &Dimensions,
+    class: Class,
+    body_color: Color,
+) -> Surface<'static> {
     let lane_width = dimensions.lane_width as u32;
+    let spec = class.spec();
 
     let scale = lane_width as f32 / 16.0;
 
     let s = |val: u32| (val as f32 * scale).round() as u32;
     let si = |val: i32| (val as f32 * scale).round() as i32;
 
-    let mut car_surface = sdl2::surface::Surface::new(
-        lane_width,
-        lane_width,
-        sdl2::pixels::PixelFormatEnum::RGBA8888,
-    )
-    .expect("Failed to create car surface");
+    let height = s(spec.length + 2);
+    let mut car_surface =
+        sdl2::surface::Surface::new(lane_width, height, sdl2::pixels::PixelFormatEnum::RGBA8888)
+            .expect("Failed to create car surface");
 
     car_surface
-        .fill_rect(
-            Rect::new(0, 0, lane_width, lane_width),
-            Color::RGBA(0, 0, 0, 0),
-        )
+        .fill_rect(Rect::new(0, 0, lane_width, height), Color::RGBA(0, 0, 0, 0))
         .unwrap();
 
+    let left = (16 - spec.width as i32) / 2;
+    let right = left + spec.width as i32;
+    let back = 1 + spec.length as i32;
+    let window_width = spec.width - 2;
+
     // Draw body.
     car_surface
-        .fill_rect(Rect::new(si(4), si(1), s(8), s(14)), body_color)
+        .fill_rect(
+            Rect::new(si(left), si(1), s(spec.width), s(spec.length)),
+            body_color,
+        )
         .unwrap();
 
     // Draw windows.
-    car_surface
-        .fill_rect(Rect::new(si(5), si(6), s(6), s(2)), Color::RGB(0, 0, 0))
-        .unwrap();
-    car_surface
-        .fill_rect(Rect::new(si(5), si(9), s(6), s(2)), Color::RGB(0, 0, 0))
-        .unwrap();
+    let black = Color::RGB(0, 0, 0);
+    match class {
+        Class::Car => {
+            car_surface
+                .fill_rect(Rect::new(si(left + 1), si(6), s(window_width), s(2)), black)
+                .unwrap();
+            car_surface
+                .fill_rect(Rect::new(si(left + 1), si(9), s(window_width), s(2)), black)
+                .unwrap();
+        }
+        Class::Van | Class::Ambulance => {
+            car_surface
+                .fill_rect(Rect::new(si(left + 1), si(4), s(window_width), s(2)), black)
+                .unwrap();
+        }
+        Class::Truck => {
+            // The cab's windscreen, and a gray trailer behind it.
+            car_surface
+                .fill_rect(Rect::new(si(left + 1), si(4), s(window_width), s(2)), black)
+                .unwrap();
+            car_surface
+                .fill_rect(
+                    Rect::new(si(left), si(9), s(spec.width), s(spec.length - 8)),
+                    Color::RGB(160, 160, 160),
+                )
+                .unwrap();
+        }
+        Class::Bus => {
+            car_surface
+                .fill_rect(Rect::new(si(left + 1), si(2), s(window_width), s(2)), black)
+                .unwrap();
+            car_surface
+                .fill_rect(
+                    Rect::new(si(left + 1), si(back - 2), s(window_width), s(1)),
+                    black,
+                )
+                .unwrap();
+        }
+        Class::Motorcycle | Class::Bicycle => {
+            // The rider's helmet.
+            car_surface
+                .fill_rect(Rect::new(si(left), si(3), s(spec.width), s(3)), black)
+                .unwrap();
+        }
+    }
 
     // Draw headlights.
+    let headlights = if matches!(class, Class::Motorcycle | Class::Bicycle) {
+        vec![left + 1]
+    } else {
+        vec![left + 1, right - 3]
+    };
+    for x in headlights {
+        car_surface
+            .fill_rect(
+                Rect::new(si(x), si(1), s(2).max(1), s(1).max(1)),
+                Color::RGB(255, 255, 255),
+            )
+            .unwrap();
+    }
+
     car_surface
-        .fill_rect(
-            Rect::new(si(5), si(1), s(2).max(1), s(1).max(1)),
-            Color::RGB(255, 255, 255),
-        )
-        .unwrap();
-    car_surface
-        .fill_rect(
-            Rect::new(si(9), si(1), s(2).max(1), s(1).max(1)),
-            Color::RGB(255, 255, 255),
-        )
-        .unwrap();
+}
 
-    texture_creator
-        .create_texture_from_surface(&car_surface)
-        .expect("Failed to create car texture")
+// Across the roof behind the windscreen: red on the left, blue on the right, with the given one lit, if any.
+fn draw_light_bar(
+    car_surface: &mut Surface,
+    dimensions: &Dimensions,
+    class: Class,
+    lit: Option<usize>,
+) {
+    let spec = class.spec();
+    let scale = dimensions.lane_width as f32 / 16.0;
+    let s = |val: u32| (val as f32 * scale).round() as u32;
+    let si = |val: i32| (val as f32 * scale).round() as i32;
+
+    let left = (16 - spec.width as i32) / 2 + 1;
+    let half = (spec.width - 2) / 2;
+    let lights = [
+        (left, Color::RGB(255, 0, 0), Color::RGB(96, 0, 0)),
+        (
+            left + half as i32,
+            Color::RGB(0, 64, 255),
+            Color::RGB(0, 0, 96),
+        ),
+    ];
+    for (i, (x, on, off)) in lights.into_iter().enumerate() {
+        car_surface
+            .fill_rect(
+                Rect::new(si(x), si(7), s(half), s(2)),
+                if lit == Some(i) { on } else { off },
+            )
+            .unwrap();
+    }
 }
@@ -1,18 +1,395 @@
+use crate::routes::Route;
+
 pub struct Dimensions {
     pub window_width: i32,
     pub window_height: i32,
     pub half_width: i32,
     pub half_height: i32,
     pub lane_width: i32,
+    pub roads: [Road; 4], // The road along each arm, in the order of `Airt::ALL`.
+    pub bike_lane: i32, // The width of the bike lane on the outside of each side of each road, or 0 if there are none.
+    pub layout: Layout,
+    pub pockets: bool, // Whether the innermost lane coming in along each arm is a short turn pocket, where the road has a lane to spare.
+    pub u_turns: bool, // Whether cars can turn back the way they came, from the innermost lane.
+    pub banned: Vec<(Airt, Airt)>, // Movements the layout has roads for that cars aren't allowed to make, as at an intersection on a map with turn restrictions.
     pub speed: Speed,
+    pub dynamics: Dynamics,
+    pub following: Following,
+    pub desired_speeds: Vec<DesiredSpeed>,
+    pub routes: Vec<Route>,
 }
 
+impl Dimensions {
+    pub fn new(window_width: i32, window_height: i32, lane_width: i32) -> Self {
+        let speed = Speed {
+            fast: lane_width * 3 / 8,
+            default: lane_width / 4,
+            slow: lane_width / 8,
+        };
+        let dynamics = Dynamics::new(
+            speed.fast,
+            ACCELERATION_TICKS,
+            COMFORTABLE_BRAKING_TICKS,
+            EMERGENCY_BRAKING_TICKS,
+        );
+        let desired_speeds = DesiredSpeed::table(&speed, SPEED_SPREAD);
+        let following = Following::new(lane_width, MIN_GAP, ACCELERATION_EXPONENT);
+
+        let mut dimensions = Dimensions {
+            window_width,
+            window_height,
+            half_width: window_width / 2,
+            half_height: window_height / 2,
+            lane_width,
+            roads: [Road::default(); 4],
+            bike_lane: lane_width * BIKE_LANE_WIDTH as i32 / 16,
+            layout: Layout::Crossroads,
+            pockets: false,
+            u_turns: false,
+            banned: Vec::new(),
+            speed,
+            dynamics,
+            following,
+            desired_speeds,
+            routes: Vec::new(),
+        };
+        dimensions.routes = Route::table(&dimensions);
+        dimensions
+    }
+
+    // Fits the dimensions to a window of the given size, with the intersection in the middle of it, and rebuilds the routes, which come in and go out at its edges. The lanes stay as wide as they were.
+    pub fn resize(&mut self, window_width: i32, window_height: i32) {
+        self.window_width = window_width;
+        self.window_height = window_height;
+        self.half_width = window_width / 2;
+        self.half_height = window_height / 2;
+        self.routes = Route::table(self);
+    }
+
+    // The road along the given arm.
+    pub fn road(&self, arm: Airt) -> Road {
+        self.roads[arm as usize]
+    }
+
+    // How many lanes the road along the given arm has for cars coming into the intersection along it, and for cars going out, not counting the bike lanes.
+    pub fn lanes_in(&self, arm: Airt) -> i32 {
+        self.road(arm).lanes_in
+    }
+
+    pub fn lanes_out(&self, arm: Airt) -> i32 {
+        self.road(arm).lanes_out
+    }
+
+    // How far the edge of the road along the given arm is from its middle, on the side where cars come in along it, to their right, and on the side where they go out.
+    pub fn half_road_in(&self, arm: Airt) -> i32 {
+        self.lanes_in(arm) * self.lane_width + self.bike_lane
+    }
+
+    pub fn half_road_out(&self, arm: Airt) -> i32 {
+        self.lanes_out(arm) * self.lane_width + self.bike_lane
+    }
+
+    // How far the edge of the road along the given arm is from its middle on the side that faces the given way, across it.
+    pub fn half_road_toward(&self, arm: Airt, toward: Airt) -> i32 {
+        let ((out_x, out_y), (x, y)) = (arm.unit(), toward.unit());
+        if x * out_y - y * out_x > 0.0 {
+            self.half_road_in(arm)
+        } else {
+            self.half_road_out(arm)
+        }
+    }
+
+    // Whether the road along the given arm is a minor road, where cars coming in give way to those on a major road. If every road is minor, none of them is.
+    pub fn minor(&self, arm: Airt) -> bool {
+        self.road(arm).minor && self.arms().into_iter().any(|other| !self.road(other).minor)
+    }
+
+    // Whether cars coming in along the given arm give way: if it's a minor road, or if all the roads are equal and everyone gives way by the same rules.
+    pub fn gives_way(&self, arm: Airt) -> bool {
+        self.minor(arm) || self.arms().into_iter().all(|other| !self.minor(other))
+    }
+
+    // The radius of the island in the middle of a roundabout.
+    pub fn island(&self) -> i32 {
+        (ISLAND_RADIUS * self.lane_width as f64) as i32
+    }
+
+    // How far the intersection reaches from its center along the given arm: to the far edge, on this side, of the wider of the roads that cross it, or to the outside of the ring of a roundabout, which is as wide as the widest side of any of the roads.
+    pub fn reach(&self, arm: Airt) -> i32 {
+        let (out_x, out_y) = arm.unit();
+        match self.layout {
+            Layout::Crossroads | Layout::TJunction(_) | Layout::Staggered(_) => self
+                .arms()
+                .into_iter()
+                .filter(|other| {
+                    let (x, y) = other.unit();
+                    x * out_x + y * out_y == 0.0
+                })
+                .map(|other| {
+                    // A staggered road reaches further the way it's moved.
+                    let (x, y) = other.unit();
+                    self.half_road_toward(other, arm)
+                        + self.stagger(other) * (y * out_x - x * out_y) as i32
+                })
+                .max()
+                .expect("No roads"),
+            Layout::Roundabout => {
+                self.island()
+                    + self
+                        .arms()
+                        .into_iter()
+                        .map(|other| self.half_road_in(other).max(self.half_road_out(other)))
+                        .max()
+                        .expect("No roads")
+            }
+        }
+    }
+
+    // How far the middle of the road along the given arm is from the middle of the intersection, across it, to the right of cars coming in along it. At a staggered junction, the roads on either side of the main road are moved apart along it, just far enough that they only meet at its middle, with the given one further up. Every other road runs through the middle.
+    pub fn stagger(&self, arm: Airt) -> i32 {
+        let Layout::Staggered(up) = self.layout else {
+            return 0;
+        };
+        if arm == Airt::Up || arm == Airt::Down {
+            return 0;
+        }
+        let apart = (self.half_road_toward(up, Airt::Down)
+            + self.half_road_toward(up.opposite(), Airt::Up)
+            + 1)
+            / 2;
+        let down = if arm == up { -apart } else { apart };
+        -(arm.unit().0 as i32) * down
+    }
+
+    // How far from the middle the turn pocket on the road coming in along the given arm opens up, if it has one. Only a road with more than one lane, off which cars can turn left, has a pocket, and not at a roundabout, where each lane lines up with one on the ring.
+    pub fn pocket_opening(&self, arm: Airt) -> Option<i32> {
+        let start = arm.opposite();
+        let turns_left = self.movements().into_iter().any(|(s, end)| {
+            let ((in_x, in_y), (out_x, out_y)) = (s.unit(), end.unit());
+            s == start && in_x * out_y - in_y * out_x < 0.0
+        });
+        (self.pockets && self.layout != Layout::Roundabout && self.lanes_in(arm) > 1 && turns_left)
+            .then(|| self.reach(arm) + (POCKET_LENGTH * self.lane_width as f64) as i32)
+    }
+
+    pub fn arms(&self) -> Vec<Airt> {
+        self.layout.arms()
+    }
+
+    // The movements cars can make through the intersection: those the layout has roads for, and U-turns if they're allowed, less any that are banned.
+    pub fn movements(&self) -> Vec<(Airt, Airt)> {
+        let mut movements = self.layout.all_movements(self.u_turns);
+        movements.retain(|movement| !self.banned.contains(movement));
+        movements
+    }
+
+    // The route through the intersection from the given lane, or the bike lane if that's None.
+    pub fn route(&self, start: Airt, end: Airt, lane: Option<i32>) -> &Route {
+        self.routes
+            .iter()
+            .find(|route| route.start == start && route.end == end && route.lane == lane)
+            .expect("Invalid route")
+    }
+
+    // The speed of the lane for the given route, on the road it comes in along.
+    pub fn lane_speed(&self, start: Airt, end: Airt) -> i32 {
+        self.speed.for_route(start, end) * self.road(start.opposite()).speed / 100
+    }
+
+    // The range a car on the given route draws its desired speed from, on the road it comes in along.
+    pub fn desired_speed(&self, start: Airt, end: Airt) -> DesiredSpeed {
+        let desired = self
+            .desired_speeds
+            .iter()
+            .find(|desired| desired.start == start && desired.end == end)
+            .expect("Invalid turn");
+        let scale = self.road(start.opposite()).speed as f64 / 100.0;
+        DesiredSpeed {
+            start,
+            end,
+            low: desired.low * scale,
+            high: desired.high * scale,
+        }
+    }
+}
+
+// How many lanes each road has on each side by default, from 1 to 5. Cars turn left from the innermost and right from the outermost, and go straight on from the ones in between, or from either lane if there are only two.
+pub const LANES: u64 = 3;
+
+// The road along one arm of the intersection, the way out from its middle: how many lanes it has for cars coming in along it and for cars going out, how fast its lanes are, as a percentage of the usual lane speeds, and whether it's a minor road, where cars coming in give way to those on the major road. The middle of the road lines up with the middle of the intersection whatever the lanes on either side of it. By default every arm has `LANES` lanes each way at the usual speeds, and none of them is minor.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Road {
+    pub lanes_in: i32,
+    pub lanes_out: i32,
+    pub speed: i32,
+    pub minor: bool,
+}
+
+impl Default for Road {
+    fn default() -> Self {
+        Road {
+            lanes_in: LANES as i32,
+            lanes_out: LANES as i32,
+            speed: 100,
+            minor: false,
+        }
+    }
+}
+
+impl Road {
+    // The same road seen from the intersection at its other end, where the lanes going out from this one come in.
+    pub fn reversed(&self) -> Road {
+        Road {
+            lanes_in: self.lanes_out,
+            lanes_out: self.lanes_in,
+            ..*self
+        }
+    }
+}
+
+// The roads along each arm, in the order of `Airt::ALL`, as seen in the mirror: those along the left and right arms swap over.
+pub fn mirror_roads(roads: [Road; 4]) -> [Road; 4] {
+    let mut mirrored = roads;
+    mirrored.swap(Airt::Left as usize, Airt::Right as usize);
+    mirrored
+}
+
+// How far the turn pockets reach back from the edge of the intersection, in lane widths. The innermost lane of a road with a pocket is closed off further out, so that cars turning left can only move into it once they're close, leaving the road clear for everyone else until then. It has to be long enough for a car to move over into it and still have a lane width to spare, as in `cars.rs`.
+pub const POCKET_LENGTH: f64 = 6.0;
+
+// The radius of the island in the middle of a roundabout, in lane widths. It has to be big enough for a car turning right, onto the ring and straight off it again, to come off it no sooner than it came on.
+pub const ISLAND_RADIUS: f64 = 3.0;
+
+// The width of the bike lanes, in sixteenths of a lane width. Set it to 0 to leave them out, and cyclists share the lanes with everyone else.
+pub const BIKE_LANE_WIDTH: u64 = 8;
+
 pub struct Speed {
     pub fast: i32,
     pub default: i32,
     pub slow: i32,
 }
 
+impl Speed {
+    // Each lane has its own speed: fast for going straight on, slow for turning right or making a U-turn, and in between for turning left.
+    pub fn for_route(&self, start: Airt, end: Airt) -> i32 {
+        match (start, end) {
+            (start, end) if start == end => self.fast,
+            (start, end) if start == end.opposite() => self.slow,
+            (Airt::Up, Airt::Right)
+            | (Airt::Down, Airt::Left)
+            | (Airt::Left, Airt::Up)
+            | (Airt::Right, Airt::Down) => self.slow,
+            _ => self.default,
+        }
+    }
+}
+
+// How far, as a fraction of the lane speed, a driver's desired speed can be from it.
+pub const SPEED_SPREAD: f64 = 0.25;
+
+// The speeds that drivers on a route would like to go at when nothing is in their way, in pixels per tick. Each car draws its own, uniformly between `low` and `high`.
+pub struct DesiredSpeed {
+    pub start: Airt,
+    pub end: Airt,
+    pub low: f64,
+    pub high: f64,
+}
+
+impl DesiredSpeed {
+    // A range for every route, U-turns included, centered on its lane speed.
+    pub fn table(speed: &Speed, spread: f64) -> Vec<DesiredSpeed> {
+        ROUTES
+            .into_iter()
+            .chain(Airt::ALL.map(|start| (start, start.opposite())))
+            .map(|(start, end)| {
+                let lane = speed.for_route(start, end) as f64;
+                DesiredSpeed {
+                    start,
+                    end,
+                    low: lane * (1.0 - spread),
+                    high: lane * (1.0 + spread),
+                }
+            })
+            .collect()
+    }
+}
+
+// How many ticks it takes to get from a standstill to the fast lane speed, or back again.
+pub const ACCELERATION_TICKS: u64 = 40;
+pub const COMFORTABLE_BRAKING_TICKS: u64 = 16;
+pub const EMERGENCY_BRAKING_TICKS: u64 = 8;
+
+// How quickly cars can change speed, in pixels per tick per tick.
+pub struct Dynamics {
+    pub acceleration: f64,
+    pub comfortable_braking: f64, // How hard a driver brakes when they see the need to stop in good time.
+    pub emergency_braking: f64,   // The hardest a car can brake.
+}
+
+impl Dynamics {
+    pub fn new(
+        fast: i32,
+        acceleration_ticks: u64,
+        comfortable_braking_ticks: u64,
+        emergency_braking_ticks: u64,
+    ) -> Self {
+        Dynamics {
+            acceleration: fast as f64 / acceleration_ticks as f64,
+            comfortable_braking: fast as f64 / comfortable_braking_ticks as f64,
+            emergency_braking: fast as f64 / emergency_braking_ticks as f64,
+        }
+    }
+
+    // How far a car going at the given speed travels before it comes to a stop, braking comfortably.
+    pub fn stopping_distance(&self, speed: f64) -> f64 {
+        speed * speed / (2.0 * self.comfortable_braking)
+    }
+}
+
+// How many ticks a car takes to cover the given distance, from the given speed, speeding up by the given acceleration on each tick until it reaches its cruising speed, or None if it can't get going at all. Worked out in closed form, so that a car that barely moves doesn't take forever to work it out.
+pub fn ticks_to_cover(distance: f64, speed: f64, acceleration: f64, cruise: f64) -> Option<u64> {
+    if distance <= 0.0 {
+        return Some(0);
+    }
+    let first = (speed + acceleration).min(cruise);
+    if first <= 0.0 {
+        return None;
+    }
+    // The ticks before the one on which it reaches its cruising speed, and how far it goes in them.
+    let speeding_up = if acceleration > 0.0 && speed + acceleration < cruise {
+        ((cruise - speed) / acceleration).ceil() - 1.0
+    } else {
+        0.0
+    };
+    let covered = speeding_up * speed + acceleration * speeding_up * (speeding_up + 1.0) / 2.0;
+    let ticks = if distance <= covered {
+        let b = speed + acceleration / 2.0;
+        ((b * b + 2.0 * acceleration * distance).sqrt() - b) / acceleration
+    } else {
+        let steady = if speeding_up > 0.0 { cruise } else { first };
+        speeding_up + (distance - covered) / steady
+    };
+    Some(ticks.ceil() as u64)
+}
+
+// The parameters of the Intelligent Driver Model that a car uses to follow the one in front of it in its lane: the gap, in sixteenths of a lane width, that a driver leaves when they stop behind another car, and how sharply they ease off as they approach their desired speed. The rest come from the car and its driver: their acceleration, comfortable braking, desired speed and headway.
+pub const MIN_GAP: u64 = 4;
+pub const ACCELERATION_EXPONENT: u64 = 4;
+
+pub struct Following {
+    pub min_gap: f64, // In pixels.
+    pub exponent: f64,
+}
+
+impl Following {
+    pub fn new(lane_width: i32, min_gap: u64, exponent: u64) -> Self {
+        Following {
+            min_gap: min_gap as f64 * lane_width as f64 / 16.0,
+            exponent: exponent as f64,
+        }
+    }
+}
+
 // These directions are all from our point of view as we look at the screen. They describe a car's initial direction and its direction after it's turned, both from our perspective.
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum Airt {
@@ -21,3 +398,163 @@ pub enum Airt {
     Left,
     Right,
 }
+
+// Every movement through the intersection: for each side a car can arrive from, a left turn, straight on, and a right turn.
+pub const ROUTES: [(Airt, Airt); 12] = [
+    (Airt::Up, Airt::Left),
+    (Airt::Up, Airt::Up),
+    (Airt::Up, Airt::Right),
+    (Airt::Down, Airt::Left),
+    (Airt::Down, Airt::Down),
+    (Airt::Down, Airt::Right),
+    (Airt::Left, Airt::Up),
+    (Airt::Left, Airt::Left),
+    (Airt::Left, Airt::Down),
+    (Airt::Right, Airt::Up),
+    (Airt::Right, Airt::Right),
+    (Airt::Right, Airt::Down),
+];
+
+impl Airt {
+    pub const ALL: [Airt; 4] = [Airt::Up, Airt::Down, Airt::Left, Airt::Right];
+
+    pub fn opposite(&self) -> Airt {
+        match self {
+            Airt::Up => Airt::Down,
+            Airt::Down => Airt::Up,
+            Airt::Left => Airt::Right,
+            Airt::Right => Airt::Left,
+        }
+    }
+
+    // This way as seen in a mirror held up to the side of the screen: left and right swap over.
+    pub fn mirror(&self) -> Airt {
+        match self {
+            Airt::Left => Airt::Right,
+            Airt::Right => Airt::Left,
+            way => *way,
+        }
+    }
+
+    // A unit vector pointing this way in screen coordinates, where y increases downward.
+    pub fn unit(&self) -> (f64, f64) {
+        match self {
+            Airt::Up => (0.0, -1.0),
+            Airt::Down => (0.0, 1.0),
+            Airt::Left => (-1.0, 0.0),
+            Airt::Right => (1.0, 0.0),
+        }
+    }
+}
+
+// What kind of intersection it is. At a `Crossroads`, the roads simply cross. At a `Roundabout`, traffic goes counterclockwise round a ring with as many lanes as each side of the road. A car joins the ring in the lane as far out from the island as the one it came in was from the middle of the road, and leaves it for the lane as far out again on the road it turns into, so a car in an inner lane crosses the outer ones on its way on and off. Cars coming onto the ring give way to those already on it. A `TJunction` is a crossroads without the arm that would go out the given way: nobody comes in along it, nobody turns into it, and its lanes are shared out between the two turns that are left. A `Staggered` junction is a crossroads where the roads on either side of the one running up and down are moved apart along it, as in `Dimensions::stagger`, with the one on the given side, left or right, further up. A car going straight across turns onto the main road and off it again.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Layout {
+    Crossroads,
+    Roundabout,
+    TJunction(Airt),
+    Staggered(Airt),
+}
+
+impl Layout {
+    pub fn mirror(&self) -> Layout {
+        match self {
+            Layout::TJunction(missing) => Layout::TJunction(missing.mirror()),
+            Layout::Staggered(up) => Layout::Staggered(up.mirror()),
+            layout => *layout,
+        }
+    }
+    // The ways out from the middle of the intersection along which there's a road.
+    pub fn arms(&self) -> Vec<Airt> {
+        Airt::ALL
+            .into_iter()
+            .filter(|&arm| *self != Layout::TJunction(arm))
+            .collect()
+    }
+
+    // Every movement through the intersection that there are roads for: the `ROUTES` that come in along one arm and go out along another.
+    pub fn movements(&self) -> Vec<(Airt, Airt)> {
+        let arms = self.arms();
+        ROUTES
+            .into_iter()
+            .filter(|(start, end)| arms.contains(&start.opposite()) && arms.contains(end))
+            .collect()
+    }
+
+    // The movements that there are roads for, and a U-turn back along each arm as well if they're allowed, in the order of the ways they come in from.
+    pub fn all_movements(&self, u_turns: bool) -> Vec<(Airt, Airt)> {
+        let mut movements = self.movements();
+        if u_turns {
+            movements.extend(self.arms().into_iter().map(|arm| (arm.opposite(), arm)));
+            movements.sort_by_key(|&(start, _)| start as usize);
+        }
+        movements
+    }
+}
+
+// How cars avoid each other. With `Global` control, each car can see where every other car is about to move and simply doesn't move if that would cause a crash. With `Local` control, it only senses the cars around it and chooses how to accelerate; see `sensing.rs`.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Control {
+    Global,
+    Local,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mirrors_back_to_where_it_started() {
+        for way in Airt::ALL {
+            assert_eq!(way.mirror().mirror(), way);
+            assert_eq!(way.mirror().opposite(), way.opposite().mirror());
+            let (x, y) = way.unit();
+            assert_eq!(way.mirror().unit(), (-x, y));
+        }
+
+        let roads = [(1, 2), (3, 3), (2, 4), (5, 1)].map(|(lanes_in, lanes_out)| Road {
+            lanes_in,
+            lanes_out,
+            ..Road::default()
+        });
+        assert_eq!(mirror_roads(mirror_roads(roads)), roads);
+
+        let mut layouts = vec![Layout::Crossroads, Layout::Roundabout];
+        layouts.extend(Airt::ALL.map(Layout::TJunction));
+        layouts.extend([Airt::Left, Airt::Right].map(Layout::Staggered));
+        for layout in layouts {
+            assert_eq!(layout.mirror().mirror(), layout);
+
+            // The mirrored intersection has the same arms, roads and movements, each the other way round from left to right.
+            let mut arms = layout
+                .arms()
+                .iter()
+                .map(Airt::mirror)
+                .collect::<Vec<Airt>>();
+            arms.sort_by_key(|&arm| arm as usize);
+            assert_eq!(layout.mirror().arms(), arms);
+            let mirrored_movements = layout.mirror().all_movements(true);
+            for (start, end) in layout.all_movements(true) {
+                assert!(mirrored_movements.contains(&(start.mirror(), end.mirror())));
+            }
+            assert_eq!(mirrored_movements.len(), layout.all_movements(true).len());
+
+            // With as many lanes each way along each road, it's the same shape, with the same distances out to the edge of it along each arm, and the stagger on the other side. Otherwise, the lanes coming in are on the other side of each road, as they are when cars keep left.
+            let even = roads.map(|road| Road {
+                lanes_out: road.lanes_in,
+                ..road
+            });
+            let mut dimensions = Dimensions::new(1000, 800, 32);
+            dimensions.layout = layout;
+            dimensions.roads = even;
+            let mut mirrored = Dimensions::new(1000, 800, 32);
+            mirrored.layout = layout.mirror();
+            mirrored.roads = mirror_roads(even);
+            for arm in layout.arms() {
+                assert_eq!(mirrored.road(arm.mirror()), dimensions.road(arm));
+                assert_eq!(mirrored.reach(arm.mirror()), dimensions.reach(arm));
+                assert_eq!(mirrored.stagger(arm.mirror()), -dimensions.stagger(arm));
+            }
+        }
+    }
+}
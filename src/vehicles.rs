@@ -0,0 +1,106 @@
+// The kinds of vehicle that use the road. Sizes are in sixteenths of a lane width, the grid their textures are drawn on, and they're the sizes of the bodies that collide, not of the squares the textures are drawn in.
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Class {
+    Car,
+    Van,
+    Truck,
+    Bus,
+    Motorcycle,
+    Ambulance,
+    Bicycle,
+}
+
+pub struct Spec {
+    pub name: &'static str, // Plural, as in the stats and the checker's options.
+    pub length: u32,
+    pub width: u32,
+    pub top_speed: f64,    // As a fraction of the fast lane speed.
+    pub acceleration: f64, // As a fraction of a car's.
+    pub emergency: bool,   // Whether it has priority over everything else on the road.
+}
+
+// How often each class is spawned, relative to the others. Ambulances only come when they're called, with the `E` key.
+pub const MIX: [(Class, u32); 7] = [
+    (Class::Car, 60),
+    (Class::Van, 15),
+    (Class::Truck, 5),
+    (Class::Bus, 5),
+    (Class::Motorcycle, 15),
+    (Class::Ambulance, 0),
+    (Class::Bicycle, 10),
+];
+
+impl Class {
+    pub const ALL: [Class; 7] = [
+        Class::Car,
+        Class::Van,
+        Class::Truck,
+        Class::Bus,
+        Class::Motorcycle,
+        Class::Ambulance,
+        Class::Bicycle,
+    ];
+
+    pub fn spec(&self) -> Spec {
+        match self {
+            Class::Car => Spec {
+                name: "cars",
+                length: 14,
+                width: 8,
+                top_speed: 1.25,
+                acceleration: 1.0,
+                emergency: false,
+            },
+            Class::Van => Spec {
+                name: "vans",
+                length: 18,
+                width: 10,
+                top_speed: 1.0,
+                acceleration: 0.75,
+                emergency: false,
+            },
+            Class::Truck => Spec {
+                name: "trucks",
+                length: 36,
+                width: 12,
+                top_speed: 0.75,
+                acceleration: 0.4,
+                emergency: false,
+            },
+            Class::Bus => Spec {
+                name: "buses",
+                length: 32,
+                width: 12,
+                top_speed: 0.8,
+                acceleration: 0.5,
+                emergency: false,
+            },
+            Class::Motorcycle => Spec {
+                name: "motorcycles",
+                length: 8,
+                width: 4,
+                top_speed: 1.25,
+                acceleration: 1.5,
+                emergency: false,
+            },
+            Class::Ambulance => Spec {
+                name: "ambulances",
+                length: 20,
+                width: 10,
+                top_speed: 1.25,
+                acceleration: 1.0,
+                emergency: true,
+            },
+            // Ridden in the bike lane where there is one, and in the lane for their turn otherwise.
+            Class::Bicycle => Spec {
+                name: "bicycles",
+                length: 10,
+                width: 4,
+                top_speed: 0.4,
+                acceleration: 0.6,
+                emergency: false,
+            },
+        }
+    }
+}